@@ -10,4 +10,5 @@ drop table ticker;
 drop table users;
 drop table currencies;
 drop table stocks;
+drop table indices;
 drop table assets;
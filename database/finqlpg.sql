@@ -87,6 +87,7 @@ ALTER TABLE public.currencies OWNER TO qltester;
 
 CREATE TABLE public.objects (
     id text NOT NULL,
+    object_type text,
     object json NOT NULL
 );
 
@@ -102,7 +103,8 @@ CREATE TABLE public.quotes (
     ticker_id integer NOT NULL,
     price double precision NOT NULL,
     "time" timestamp with time zone NOT NULL,
-    volume double precision
+    volume double precision,
+    stale boolean DEFAULT false NOT NULL
 );
 
 
@@ -145,6 +147,32 @@ CREATE TABLE public.stocks (
 
 ALTER TABLE public.stocks OWNER TO qltester;
 
+--
+-- Name: indices; Type: TABLE; Schema: public; Owner: qltester
+--
+
+CREATE TABLE public.indices (
+    id integer NOT NULL,
+    name text NOT NULL,
+    note text
+);
+
+
+ALTER TABLE public.indices OWNER TO qltester;
+
+--
+-- Name: stock_splits; Type: TABLE; Schema: public; Owner: qltester
+--
+
+CREATE TABLE public.stock_splits (
+    asset_id integer NOT NULL,
+    split_date date NOT NULL,
+    ratio double precision NOT NULL
+);
+
+
+ALTER TABLE public.stock_splits OWNER TO qltester;
+
 --
 -- Name: ticker; Type: TABLE; Schema: public; Owner: qltester
 --
@@ -158,7 +186,8 @@ CREATE TABLE public.ticker (
     currency_id integer NOT NULL,
     factor double precision DEFAULT 1.0 NOT NULL,
     tz text,
-    cal text
+    cal text,
+    active boolean DEFAULT true NOT NULL
 );
 
 
@@ -199,7 +228,8 @@ CREATE TABLE public.transactions (
     cash_date date NOT NULL,
     related_trans integer,
     "position" double precision,
-    note text
+    note text,
+    time_stamp timestamp with time zone DEFAULT now() NOT NULL
 );
 
 
@@ -421,6 +451,30 @@ ALTER TABLE ONLY public.stocks
     ADD CONSTRAINT stocks_wkn_key UNIQUE (wkn);
 
 
+--
+-- Name: indices indices_name_key; Type: CONSTRAINT; Schema: public; Owner: qltester
+--
+
+ALTER TABLE ONLY public.indices
+    ADD CONSTRAINT indices_name_key UNIQUE (name);
+
+
+--
+-- Name: indices indices_pkey; Type: CONSTRAINT; Schema: public; Owner: qltester
+--
+
+ALTER TABLE ONLY public.indices
+    ADD CONSTRAINT indices_pkey PRIMARY KEY (id);
+
+
+--
+-- Name: stock_splits stock_splits_pkey; Type: CONSTRAINT; Schema: public; Owner: qltester
+--
+
+ALTER TABLE ONLY public.stock_splits
+    ADD CONSTRAINT stock_splits_pkey PRIMARY KEY (asset_id, split_date);
+
+
 --
 -- Name: ticker ticker_pkey; Type: CONSTRAINT; Schema: public; Owner: qltester
 --
@@ -429,6 +483,14 @@ ALTER TABLE ONLY public.ticker
     ADD CONSTRAINT ticker_pkey PRIMARY KEY (id);
 
 
+--
+-- Name: ticker ticker_name_source_key; Type: CONSTRAINT; Schema: public; Owner: qltester
+--
+
+ALTER TABLE ONLY public.ticker
+    ADD CONSTRAINT ticker_name_source_key UNIQUE (name, source);
+
+
 --
 -- Name: transactions transactions_pkey; Type: CONSTRAINT; Schema: public; Owner: qltester
 --
@@ -461,6 +523,22 @@ ALTER TABLE ONLY public.stocks
     ADD CONSTRAINT stocks_id_fkey FOREIGN KEY (id) REFERENCES public.assets(id);
 
 
+--
+-- Name: indices indices_id_fkey; Type: FK CONSTRAINT; Schema: public; Owner: qltester
+--
+
+ALTER TABLE ONLY public.indices
+    ADD CONSTRAINT indices_id_fkey FOREIGN KEY (id) REFERENCES public.assets(id);
+
+
+--
+-- Name: stock_splits stock_splits_asset_id_fkey; Type: FK CONSTRAINT; Schema: public; Owner: qltester
+--
+
+ALTER TABLE ONLY public.stock_splits
+    ADD CONSTRAINT stock_splits_asset_id_fkey FOREIGN KEY (asset_id) REFERENCES public.assets(id);
+
+
 --
 -- Name: ticker ticker_asset_id_fkey; Type: FK CONSTRAINT; Schema: public; Owner: qltester
 --
@@ -0,0 +1,40 @@
+alter table objects
+add column if not exists object_type text;
+
+-- merge duplicate tickers (same name and source) into the ticker with the lowest id,
+-- otherwise the unique constraint can't be added
+update quotes q
+set ticker_id = keep.id
+from ticker t, (select min(id) as id, name, source from ticker group by name, source) keep
+where q.ticker_id = t.id and t.name = keep.name and t.source = keep.source and t.id <> keep.id;
+
+delete from ticker t
+using ticker keep
+where t.name = keep.name and t.source = keep.source and t.id > keep.id;
+
+alter table ticker
+add constraint ticker_name_source_key unique (name, source);
+
+alter table transactions
+add column if not exists time_stamp timestamp with time zone not null default now();
+
+alter table quotes
+add column if not exists stale boolean not null default false;
+
+alter table ticker
+add column if not exists active boolean not null default true;
+
+create table if not exists indices (
+    id integer primary key,
+    name text not null unique,
+    note text,
+    foreign key(id) references assets(id)
+);
+
+create table if not exists stock_splits (
+    asset_id integer not null,
+    split_date date not null,
+    ratio float8 not null,
+    primary key(asset_id, split_date),
+    foreign key(asset_id) references assets(id)
+);
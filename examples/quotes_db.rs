@@ -83,6 +83,7 @@ async fn quote_tests(market: Market) {
         factor: 1.0,
         tz: None,
         cal: None,
+        active: true,
     };
     let basf_ticker_id = market.db().insert_ticker(&basf).await.unwrap();
     // Get ticker back
@@ -98,6 +99,7 @@ async fn quote_tests(market: Market) {
         factor: 1.0,
         tz: None,
         cal: None,
+        active: true,
     };
     let siemens_id = market.db().insert_ticker(&siemens).await.unwrap();
     // Insert another ticker, with other source
@@ -111,6 +113,7 @@ async fn quote_tests(market: Market) {
         factor: 1.0,
         tz: None,
         cal: None,
+        active: true,
     };
     let bhp_id = market.db().insert_ticker(&bhp).await.unwrap();
     println!("ok");
@@ -147,6 +150,7 @@ async fn quote_tests(market: Market) {
         price: 67.35,
         time,
         volume: None,
+        stale: false,
     };
     market.db().insert_quote(&quote).await.unwrap();
     let time = make_time(2020, 1, 2, 20, 0, 0).unwrap();
@@ -156,6 +160,7 @@ async fn quote_tests(market: Market) {
         price: 68.29,
         time,
         volume: None,
+        stale: false,
     };
     market.db().insert_quote(&quote).await.unwrap();
     let time = make_time(2020, 1, 3, 20, 0, 0).unwrap();
@@ -165,6 +170,7 @@ async fn quote_tests(market: Market) {
         price: 67.27,
         time,
         volume: None,
+        stale: false,
     };
     market.db().insert_quote(&quote).await.unwrap();
     let time = make_time(2020, 1, 6, 20, 0, 0).unwrap();
@@ -174,6 +180,7 @@ async fn quote_tests(market: Market) {
         price: 66.27,
         time,
         volume: None,
+        stale: false,
     };
     market.db().insert_quote(&quote).await.unwrap();
     let time = make_time(2020, 1, 7, 20, 0, 0).unwrap();
@@ -183,6 +190,7 @@ async fn quote_tests(market: Market) {
         price: 66.30,
         time,
         volume: None,
+        stale: false,
     };
     market.db().insert_quote(&quote).await.unwrap();
     let time = make_time(2020, 1, 8, 20, 0, 0).unwrap();
@@ -192,6 +200,7 @@ async fn quote_tests(market: Market) {
         price: 65.73,
         time,
         volume: None,
+        stale: false,
     };
     let wrong_quote_id = market.db().insert_quote(&wrong_quote).await.unwrap();
     println!("ok");
@@ -136,6 +136,7 @@ async fn main() {
         factor: 1.0,
         tz: None,
         cal: None,
+        active: true,
     };
     let ticker_id = db.insert_ticker(&ticker).await.unwrap();
     let price_offset_period = "7D".parse::<TimePeriod>().unwrap();
@@ -163,6 +164,7 @@ async fn main() {
         transaction_type: TransactionType::Cash,
         cash_flow,
         note: Some("start capital".to_string()),
+        time_stamp: None,
     });
     let asset_price = market.get_asset_price(asset_id, usd, start_time).await.unwrap();
 
@@ -178,6 +180,7 @@ async fn main() {
             date: start,
         },
         note: Some("Initial asset buy transaction".to_string()),
+        time_stamp: None,
     });
 
     let mut all_time_series = Vec::new();
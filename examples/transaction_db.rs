@@ -38,6 +38,7 @@ async fn transaction_tests(db: &dyn TransactionHandler) {
         transaction_type: TransactionType::Cash,
         cash_flow,
         note: Some("start capital".to_string()),
+        time_stamp: None,
     };
     let result = db.insert_transaction(&cash_in).await;
     match result {
@@ -56,6 +57,7 @@ async fn transaction_tests(db: &dyn TransactionHandler) {
         },
         cash_flow,
         note: None,
+        time_stamp: None,
     };
     let trans_id = db.insert_transaction(&asset_buy).await.unwrap();
     println!("ok");
@@ -69,6 +71,7 @@ async fn transaction_tests(db: &dyn TransactionHandler) {
         },
         cash_flow: CashFlow::new(-30.0, eur, NaiveDate::from_ymd(2020, 01, 15)),
         note: None,
+        time_stamp: None,
     };
     let _ = db.insert_transaction(&fee).await.unwrap();
     println!("ok");
@@ -80,6 +83,7 @@ async fn transaction_tests(db: &dyn TransactionHandler) {
         transaction_type: TransactionType::Dividend { asset_id: 1 },
         cash_flow: CashFlow::new(90.0, eur, NaiveDate::from_ymd(2020, 01, 30)),
         note: None,
+        time_stamp: None,
     };
     let dividend_id = db.insert_transaction(&dividend).await.unwrap();
     println!("ok");
@@ -93,6 +97,7 @@ async fn transaction_tests(db: &dyn TransactionHandler) {
         },
         cash_flow: CashFlow::new(-40.0, eur, NaiveDate::from_ymd(2020, 01, 30)),
         note: None,
+        time_stamp: None,
     };
     let _ = db.insert_transaction(&tax).await.unwrap();
     println!("ok");
@@ -7,15 +7,16 @@ use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fmt;
 
-use crate::datatypes::cash_flow::CashFlow;
+use crate::datatypes::cash_flow::{CashAmount, CashFlow};
 use crate::datatypes::currency::Currency;
 
 use crate::day_adjust::DayAdjust;
 use crate::day_count_conv::{DayCountConv, DayCountConvError};
-use crate::fixed_income::FixedIncome;
-use crate::rates::DiscountError;
+use crate::fixed_income::{get_cash_flows_after, FixedIncome};
+use crate::market::Market;
+use crate::rates::{Compounding, DiscountError, Discounter, FlatRate};
 use crate::time_period::TimePeriod;
-use cal_calc::{CalendarNotFound, CalendarProvider};
+use cal_calc::{CalendarNotFound, CalendarProvider, SimpleCalendar};
 
 /// Error related to bonds
 #[derive(Debug)]
@@ -23,6 +24,11 @@ pub enum BondError {
     DiscountingFailure(DiscountError),
     MissingCalendar,
     DayCountError(DayCountConvError),
+    InvalidSchedule,
+    MaturityBeforeIssue,
+    InvalidFrequency,
+    FirstCouponOutOfRange,
+    InvalidWeights,
 }
 
 impl fmt::Display for BondError {
@@ -33,6 +39,22 @@ impl fmt::Display for BondError {
                 write!(f, "invalid day count convention in this context")
             }
             BondError::DiscountingFailure(_) => write!(f, "discounting cash flows failed"),
+            BondError::InvalidSchedule => write!(
+                f,
+                "first and penultimate coupon dates must lie between issue date and maturity"
+            ),
+            BondError::MaturityBeforeIssue => write!(f, "maturity must be after issue date"),
+            BondError::InvalidFrequency => {
+                write!(f, "coupon frequency must be a positive time period")
+            }
+            BondError::FirstCouponOutOfRange => write!(
+                f,
+                "first coupon date must be after issue date and not after maturity"
+            ),
+            BondError::InvalidWeights => write!(
+                f,
+                "market values must be non-negative with a positive total"
+            ),
         }
     }
 }
@@ -83,6 +105,11 @@ pub struct Bond {
     calendar: String,
     issue_date: NaiveDate,
     maturity: NaiveDate,
+    /// (Unadjusted) end of an irregular first coupon period, if the first coupon date
+    /// is not derived from the coupon date
+    first_coupon: Option<NaiveDate>,
+    /// (Unadjusted) start of an irregular last coupon period
+    penultimate_coupon: Option<NaiveDate>,
     /// Smallest purchasable unit
     pub denomination: u32,
     volume: Option<f64>,
@@ -115,12 +142,12 @@ struct Coupon {
     coupon_date: CouponDate,
     period: TimePeriod,
     day_count_convention: DayCountConv,
+    /// Number of decimals the year fractions of coupon periods are rounded to, if any
+    #[serde(default)]
+    year_fraction_decimals: Option<u32>,
 }
 
 impl Coupon {
-    fn coupon_day(&self) -> u32 {
-        self.coupon_date.day()
-    }
     fn coupon_month(&self) -> u32 {
         self.coupon_date.month()
     }
@@ -130,27 +157,94 @@ impl Coupon {
         end: NaiveDate,
         roll_date: NaiveDate,
     ) -> Result<f64, DayCountConvError> {
-        self.day_count_convention
-            .year_fraction(start, end, Some(roll_date), Some(self.period))
+        self.day_count_convention.year_fraction_rounded(
+            start,
+            end,
+            Some(roll_date),
+            Some(self.period),
+            self.year_fraction_decimals,
+        )
     }
 }
 
 impl Bond {
     /// Calculate first coupon period end date
-    fn first_coupon_end(&self, start_date: NaiveDate) -> NaiveDate {
-        if self.coupon.coupon_month() <= start_date.month() {
-            NaiveDate::from_ymd(
-                start_date.year() + 1,
-                self.coupon.coupon_month(),
-                self.coupon.coupon_day(),
-            )
+    fn first_coupon_end(&self, start_date: NaiveDate) -> Result<NaiveDate, BondError> {
+        let year = if self.coupon.coupon_month() <= start_date.month() {
+            start_date.year() + 1
         } else {
-            NaiveDate::from_ymd(
-                start_date.year(),
-                self.coupon.coupon_month(),
-                self.coupon.coupon_day(),
-            )
+            start_date.year()
+        };
+        self.coupon
+            .coupon_date
+            .date_in_year(year)
+            .ok_or(BondError::InvalidSchedule)
+    }
+
+    /// Calculate unadjusted coupon periods as tuples of start, end and roll date
+    /// The first and, if a penultimate coupon date is given, the last period may be
+    /// irregular (stub) periods; all other periods are regular coupon periods.
+    fn coupon_periods(&self) -> Result<Vec<(NaiveDate, NaiveDate, NaiveDate)>, BondError> {
+        let first_end = match self.first_coupon {
+            Some(first_coupon) if first_coupon <= self.issue_date => {
+                return Err(BondError::InvalidSchedule)
+            }
+            Some(first_coupon) => first_coupon,
+            None => self.first_coupon_end(self.issue_date)?,
+        };
+        let regular_end = match self.penultimate_coupon {
+            Some(penultimate) if penultimate < first_end || penultimate >= self.maturity => {
+                return Err(BondError::InvalidSchedule)
+            }
+            Some(penultimate) => penultimate,
+            None => self.maturity,
+        };
+        let mut periods = vec![(self.issue_date, first_end, first_end)];
+        let mut end_date = first_end;
+        while end_date < regular_end {
+            let start_date = end_date;
+            end_date = self.coupon.period.add_to(start_date, None);
+            if self.penultimate_coupon.is_some() {
+                end_date = end_date.min(regular_end);
+            }
+            periods.push((start_date, end_date, start_date));
+        }
+        if let Some(penultimate) = self.penultimate_coupon {
+            periods.push((penultimate, self.maturity, penultimate));
         }
+        Ok(periods)
+    }
+}
+
+/// Convert clean price into dirty (invoice) price by adding the accrued interest
+pub fn clean_to_dirty(clean: f64, accrued: f64) -> f64 {
+    clean + accrued
+}
+
+/// Convert dirty (invoice) price into clean price by subtracting the accrued interest
+pub fn dirty_to_clean(dirty: f64, accrued: f64) -> f64 {
+    dirty - accrued
+}
+
+impl Bond {
+    /// Calculate the clean price for a given yield to maturity at the settlement date.
+    /// The yield follows the conventions of `FixedIncome::calculate_ytm_compounded`, i.e.
+    /// day count convention act/365 and the given compounding method. As the accrued
+    /// interest, the price is given per unit of denomination.
+    pub fn clean_price_from_yield(
+        &self,
+        ytm: f64,
+        compounding: Compounding,
+        settlement: NaiveDate,
+        calendar_provider: &dyn CalendarProvider,
+    ) -> Result<f64, BondError> {
+        let cash_flows = self.rollout_cash_flows(1., calendar_provider)?;
+        let future_cash_flows = get_cash_flows_after(&cash_flows, settlement);
+        let rate = FlatRate::new(ytm, DayCountConv::Act365, compounding, self.currency);
+        let dirty = rate
+            .discount_cash_flow_stream(&future_cash_flows, settlement)?
+            .amount;
+        Ok(dirty_to_clean(dirty, self.accrued_interest(settlement)?))
     }
 }
 
@@ -164,22 +258,10 @@ impl FixedIncome for Bond {
         calendar_provider: &dyn CalendarProvider,
     ) -> Result<Vec<CashFlow>, BondError> {
         let mut cfs = Vec::new();
-        let start_date = self.issue_date;
-        let mut end_date = self.first_coupon_end(start_date);
-        let year_fraction = self.coupon.year_fraction(start_date, end_date, end_date)?;
-        let amount =
-            position * (self.denomination as f64) * self.coupon.rate / 100. * year_fraction;
         let cal = calendar_provider.get_calendar(&self.calendar)?;
-        let pay_date = self.business_day_rule.adjust_date(end_date, cal);
-        let cf = CashFlow::new(amount, self.currency, pay_date);
-        cfs.push(cf);
         let maturity = self.maturity;
-        while end_date < maturity {
-            let start_date = end_date;
-            end_date = self.coupon.period.add_to(start_date, None);
-            let year_fraction = self
-                .coupon
-                .year_fraction(start_date, end_date, start_date)?;
+        for (start_date, end_date, roll_date) in self.coupon_periods()? {
+            let year_fraction = self.coupon.year_fraction(start_date, end_date, roll_date)?;
             let amount =
                 position * (self.denomination as f64) * self.coupon.rate / 100. * year_fraction;
             let pay_date = self.business_day_rule.adjust_date(end_date, cal);
@@ -198,21 +280,18 @@ impl FixedIncome for Bond {
     }
 
     fn accrued_interest(&self, today: NaiveDate) -> Result<f64, BondError> {
-        let mut start_date = self.issue_date;
-        if today < start_date {
+        if today < self.issue_date {
             return Ok(0.);
         }
-        let mut end_date = self.first_coupon_end(start_date);
-        while today > end_date && end_date < self.maturity {
-            start_date = end_date;
-            end_date = self.coupon.period.add_to(start_date, None);
-        }
+        let periods = self.coupon_periods()?;
+        let (start_date, end_date, roll_date) = *periods
+            .iter()
+            .find(|(_, end_date, _)| today <= *end_date)
+            .unwrap_or(&periods[periods.len() - 1]);
         if end_date >= self.maturity {
             return Ok(0.);
         }
-        let year_fraction = self
-            .coupon
-            .year_fraction(start_date, end_date, start_date)?;
+        let year_fraction = self.coupon.year_fraction(start_date, end_date, roll_date)?;
         let amount = (self.denomination as f64) * self.coupon.rate / 100. * year_fraction;
         let fraction = today.signed_duration_since(start_date).num_days() as f64
             / end_date.signed_duration_since(start_date).num_days() as f64;
@@ -221,11 +300,178 @@ impl FixedIncome for Bond {
     }
 }
 
+/// Specification of a plain fixed coupon bond as given in its prospectus,
+/// e.g. to load bonds from data files
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct BondSpec {
+    /// International security identification number
+    pub isin: String,
+    pub currency: Currency,
+    /// Face value (nominal) of the bond
+    pub face: f64,
+    /// Annual coupon rate in percent
+    pub coupon_rate: f64,
+    /// Period between two coupon payments
+    pub frequency: TimePeriod,
+    pub issue: NaiveDate,
+    pub maturity: NaiveDate,
+    pub dcc: DayCountConv,
+    /// (Unadjusted) end of the first coupon period; if not given, coupon dates
+    /// are rolled back from maturity
+    pub first_coupon: Option<NaiveDate>,
+    pub business_day_adjustment: DayAdjust,
+    /// Name of the calendar used for business day adjustment
+    pub calendar: String,
+    /// Number of decimals the year fractions of coupon periods are rounded to, e.g. to
+    /// remove floating point residue from coupon and accrued interest amounts.
+    /// Year fractions are not rounded if not given.
+    #[serde(default)]
+    pub year_fraction_decimals: Option<u32>,
+}
+
+impl BondSpec {
+    /// Check that the specification describes a valid coupon schedule
+    pub fn validate(&self) -> Result<(), BondError> {
+        if self.maturity <= self.issue {
+            return Err(BondError::MaturityBeforeIssue);
+        }
+        if !self.frequency.is_positive() {
+            return Err(BondError::InvalidFrequency);
+        }
+        if let Some(first_coupon) = self.first_coupon {
+            if first_coupon <= self.issue || first_coupon > self.maturity {
+                return Err(BondError::FirstCouponOutOfRange);
+            }
+        }
+        Ok(())
+    }
+
+    /// Convert specification into a bond with a denomination of 1, irregular first
+    /// or last coupon periods are derived from first coupon date and maturity
+    fn to_bond(&self) -> Result<Bond, BondError> {
+        let first_coupon = match self.first_coupon {
+            Some(first_coupon) => first_coupon,
+            None => {
+                let mut date = self.maturity;
+                loop {
+                    let prev_date = self.frequency.sub_from(date, None);
+                    if prev_date <= self.issue {
+                        break date;
+                    }
+                    date = prev_date;
+                }
+            }
+        };
+        let mut penultimate = first_coupon;
+        let mut last_coupon = first_coupon;
+        while last_coupon < self.maturity {
+            penultimate = last_coupon;
+            last_coupon = self.frequency.add_to(penultimate, None);
+        }
+        let penultimate_coupon = if last_coupon == self.maturity {
+            None
+        } else {
+            Some(penultimate)
+        };
+        let coupon_date = CouponDate::new(first_coupon.day(), first_coupon.month())
+            .map_err(|_| BondError::InvalidSchedule)?;
+        Ok(Bond {
+            isin: Some(self.isin.clone()),
+            security_id: None,
+            prospect_url: None,
+            issuer: None,
+            bond_type: "bond".to_string(),
+            currency: self.currency,
+            coupon: Coupon {
+                coupon_type: "fixed".to_string(),
+                rate: self.coupon_rate,
+                coupon_date,
+                period: self.frequency,
+                day_count_convention: self.dcc,
+                year_fraction_decimals: self.year_fraction_decimals,
+            },
+            business_day_rule: self.business_day_adjustment,
+            calendar: self.calendar.clone(),
+            issue_date: self.issue,
+            maturity: self.maturity,
+            first_coupon: Some(first_coupon),
+            penultimate_coupon,
+            denomination: 1,
+            volume: None,
+        })
+    }
+
+    /// Generate the cash flows of the bond for its face value, the calendar
+    /// is resolved from the market
+    #[allow(clippy::wrong_self_convention)]
+    pub fn into_cash_flows(&self, market: &Market) -> Result<Vec<CashFlow>, BondError> {
+        self.validate()?;
+        let calendar = market
+            .get_calendar(&self.calendar)
+            .map_err(|_| BondError::MissingCalendar)?;
+        self.to_bond()?
+            .rollout_cash_flows(self.face, &SimpleCalendar::new(&calendar))
+    }
+
+    /// Projected payments of the bond for its face value by (business day adjusted)
+    /// payment date, where payments on the same date, e.g. the last coupon and the
+    /// principal, are merged into a single amount
+    pub fn payment_schedule(
+        &self,
+        market: &Market,
+    ) -> Result<Vec<(NaiveDate, CashAmount)>, BondError> {
+        let mut schedule: Vec<(NaiveDate, CashAmount)> = Vec::new();
+        for cf in self.into_cash_flows(market)? {
+            match schedule.last_mut() {
+                Some((date, amount)) if *date == cf.date => amount.amount += cf.amount.amount,
+                _ => schedule.push((cf.date, cf.amount)),
+            }
+        }
+        Ok(schedule)
+    }
+}
+
+/// Calculate the market value weighted average yield of a bond portfolio, where
+/// each holding is given by the bond specification, its market value and its yield to maturity
+pub fn portfolio_yield(holdings: &[(BondSpec, f64, f64)]) -> Result<f64, BondError> {
+    let mut total_value = 0.;
+    let mut weighted_yield = 0.;
+    for (_, market_value, ytm) in holdings {
+        if market_value.is_nan() || *market_value < 0. {
+            return Err(BondError::InvalidWeights);
+        }
+        total_value += market_value;
+        weighted_yield += market_value * ytm;
+    }
+    if total_value <= 0. {
+        return Err(BondError::InvalidWeights);
+    }
+    Ok(weighted_yield / total_value)
+}
+
+/// Calculate the clean price for the face value of the bond at which it would be bought
+/// at the settlement date to yield `target_yield`. As for `Bond::clean_price_from_yield`,
+/// the yield is annually compounded with day count convention act/365.
+pub fn clean_price_for_yield(
+    spec: &BondSpec,
+    target_yield: f64,
+    settlement: NaiveDate,
+    calendar_provider: &dyn CalendarProvider,
+) -> Result<f64, BondError> {
+    spec.validate()?;
+    let clean = spec.to_bond()?.clean_price_from_yield(
+        target_yield,
+        Compounding::Annual,
+        settlement,
+        calendar_provider,
+    )?;
+    Ok(spec.face * clean)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::market::generate_calendars;
-    use cal_calc::SimpleCalendar;
     use std::str::FromStr;
 
     #[test]
@@ -320,4 +566,460 @@ mod tests {
         assert!(reference_cash_flows[3].fuzzy_cash_flows_cmp_eq(&cash_flows[3], tol));
         assert!(reference_cash_flows[4].fuzzy_cash_flows_cmp_eq(&cash_flows[4], tol));
     }
+
+    #[test]
+    fn principal_payment_on_holiday() {
+        // maturity falls on a Sunday followed by the TARGET holiday on May 1st
+        let data = r#"{
+            "isin": "DE0001234567",
+            "currency": "EUR",
+            "face": 1000,
+            "coupon_rate": 4,
+            "frequency": "1Y",
+            "issue": "2021-04-30",
+            "maturity": "2023-04-30",
+            "dcc": "act/act icma",
+            "first_coupon": null,
+            "business_day_adjustment": "following",
+            "calendar": "TARGET"
+        }"#;
+        let mut spec: BondSpec = serde_json::from_str(data).unwrap();
+        let sample_calendars = generate_calendars();
+        let calendar = SimpleCalendar::new(&sample_calendars["TARGET"]);
+        let cash_flows = spec
+            .to_bond()
+            .unwrap()
+            .rollout_cash_flows(1000., &calendar)
+            .unwrap();
+        let principal = cash_flows.last().unwrap();
+        assert_eq!(principal.amount.amount, 1000.);
+        assert_eq!(principal.date, NaiveDate::from_ymd(2023, 5, 2));
+
+        // modified following stays within the month of maturity
+        spec.business_day_adjustment = DayAdjust::Modified;
+        let cash_flows = spec
+            .to_bond()
+            .unwrap()
+            .rollout_cash_flows(1000., &calendar)
+            .unwrap();
+        assert_eq!(
+            cash_flows.last().unwrap().date,
+            NaiveDate::from_ymd(2023, 4, 28)
+        );
+    }
+
+    #[test]
+    fn clean_and_dirty_price() {
+        let data = r#"{
+            "bond_type": "bond",
+            "currency": "EUR",
+            "coupon" : {
+                "coupon_type": "fixed",
+                "rate": 5,
+                "coupon_date": "04-01",
+                "period": "6M",
+                "day_count_convention": "act/365"
+            },
+            "business_day_rule": "none",
+            "calendar": "TARGET",
+            "issue_date": "2019-10-01",
+            "maturity": "2021-10-01",
+            "denomination": 1000
+        }"#;
+        let bond: Bond = serde_json::from_str(data).unwrap();
+        let calendar = SimpleCalendar::default();
+        let tol = 1e-8;
+
+        // settlement in the middle of the coupon period
+        let settlement = NaiveDate::from_ymd(2020, 1, 15);
+        let accrued = bond.accrued_interest(settlement).unwrap();
+        assert_fuzzy_eq!(accrued, 0.05 * 1000. * 183. / 365. * 106. / 183., tol);
+        let clean = bond
+            .clean_price_from_yield(0.04, Compounding::Annual, settlement, &calendar)
+            .unwrap();
+        let dirty = clean_to_dirty(clean, accrued);
+        assert_fuzzy_eq!(dirty, clean + accrued, tol);
+        assert_fuzzy_eq!(dirty_to_clean(dirty, accrued), clean, tol);
+
+        // paying the dirty price gives back the yield
+        let purchase = CashFlow::new(-dirty, bond.currency, settlement);
+        let ytm = bond.calculate_ytm(&purchase, &calendar).unwrap();
+        assert_fuzzy_eq!(ytm, 0.04, tol);
+
+        // same price, but with continuous compounding
+        let ytm_cont = bond
+            .calculate_ytm_compounded(&purchase, &calendar, Compounding::Continuous)
+            .unwrap();
+        assert!((ytm_cont - ytm).abs() > 1e-4);
+        assert_fuzzy_eq!(ytm_cont, f64::ln(1.04), tol);
+        let clean_cont = bond
+            .clean_price_from_yield(ytm_cont, Compounding::Continuous, settlement, &calendar)
+            .unwrap();
+        assert_fuzzy_eq!(clean_cont, clean, tol);
+
+        // semi-annual compounding is equivalent to periodic compounding with two periods
+        let ytm_semi = bond
+            .calculate_ytm_compounded(&purchase, &calendar, Compounding::SemiAnnual)
+            .unwrap();
+        let ytm_periodic = bond
+            .calculate_ytm_compounded(&purchase, &calendar, Compounding::Periodic(2))
+            .unwrap();
+        assert_fuzzy_eq!(ytm_semi, ytm_periodic, tol);
+        assert!(ytm_semi < ytm);
+    }
+
+    #[test]
+    fn cash_flow_rollout_with_stubs() {
+        let data = r#"{
+            "bond_type": "bond",
+            "currency": "EUR",
+            "coupon" : {
+                "coupon_type": "fixed",
+                "rate": 5,
+                "coupon_date": "10-01",
+                "period": "1Y",
+                "day_count_convention": "act/365"
+            },
+            "business_day_rule": "none",
+            "calendar": "TARGET",
+            "issue_date": "2020-01-15",
+            "maturity": "2022-12-01",
+            "first_coupon": "2020-10-01",
+            "penultimate_coupon": "2022-10-01",
+            "denomination": 1000
+        }"#;
+        let bond: Bond = serde_json::from_str(data).unwrap();
+        let calendar = SimpleCalendar::default();
+        let cash_flows = bond.rollout_cash_flows(1., &calendar).unwrap();
+        assert_eq!(cash_flows.len(), 5);
+        let curr = Currency::from_str("EUR").unwrap();
+        let reference_cash_flows = [
+            // short first stub, prorated coupon
+            CashFlow::new(
+                0.05 * 1000. * 260. / 365.,
+                curr,
+                NaiveDate::from_ymd(2020, 10, 1),
+            ),
+            CashFlow::new(0.05 * 1000., curr, NaiveDate::from_ymd(2021, 10, 1)),
+            CashFlow::new(0.05 * 1000., curr, NaiveDate::from_ymd(2022, 10, 1)),
+            // short last stub
+            CashFlow::new(
+                0.05 * 1000. * 61. / 365.,
+                curr,
+                NaiveDate::from_ymd(2022, 12, 1),
+            ),
+            CashFlow::new(1000., curr, NaiveDate::from_ymd(2022, 12, 1)),
+        ];
+        let tol = 1e-11;
+        for (reference, cash_flow) in reference_cash_flows.iter().zip(cash_flows.iter()) {
+            assert!(reference.fuzzy_cash_flows_cmp_eq(cash_flow, tol));
+        }
+        // accrued interest within first stub
+        let accrued = bond
+            .accrued_interest(NaiveDate::from_ymd(2020, 5, 24))
+            .unwrap();
+        assert_fuzzy_eq!(accrued, 0.05 * 1000. * 130. / 365., tol);
+
+        let data = data.replace("\"2020-10-01\"", "\"2019-10-01\"");
+        let bond: Bond = serde_json::from_str(&data).unwrap();
+        assert!(matches!(
+            bond.rollout_cash_flows(1., &calendar),
+            Err(BondError::InvalidSchedule)
+        ));
+    }
+
+    #[test]
+    fn coupon_amounts_follow_day_count() {
+        let data = r#"{
+            "bond_type": "bond",
+            "currency": "EUR",
+            "coupon" : {
+                "coupon_type": "fixed",
+                "rate": 4,
+                "coupon_date": "07-01",
+                "period": "6M",
+                "day_count_convention": "act/365"
+            },
+            "business_day_rule": "none",
+            "calendar": "TARGET",
+            "issue_date": "2020-11-15",
+            "maturity": "2022-01-01",
+            "first_coupon": "2021-07-01",
+            "denomination": 1000
+        }"#;
+        let bond: Bond = serde_json::from_str(data).unwrap();
+        let calendar = SimpleCalendar::default();
+        let cash_flows = bond.rollout_cash_flows(1., &calendar).unwrap();
+        assert_eq!(cash_flows.len(), 3);
+        let tol = 1e-11;
+        let regular = 0.04 * 1000. / 2.;
+        // long first coupon period of 228 days
+        assert_fuzzy_eq!(cash_flows[0].amount.amount, 0.04 * 1000. * 228. / 365., tol);
+        assert!(cash_flows[0].amount.amount > regular);
+        // regular period of 184 days
+        assert_fuzzy_eq!(cash_flows[1].amount.amount, 0.04 * 1000. * 184. / 365., tol);
+        assert!((cash_flows[1].amount.amount - regular).abs() > tol);
+    }
+
+    #[tokio::test]
+    async fn bond_spec_cash_flows() {
+        let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
+        assert!(
+            db_url.is_ok(),
+            "environment variable $FINQL_TEST_DATABASE_URL is not set"
+        );
+        let db = crate::postgres::PostgresDB::new(&db_url.unwrap())
+            .await
+            .unwrap();
+        let market = Market::new(std::sync::Arc::new(db)).await;
+
+        let data = r#"{
+            "isin": "DE0001234567",
+            "currency": "EUR",
+            "face": 1000,
+            "coupon_rate": 5,
+            "frequency": "6M",
+            "issue": "2020-11-15",
+            "maturity": "2022-10-01",
+            "dcc": "icma",
+            "first_coupon": null,
+            "business_day_adjustment": "modified",
+            "calendar": "TARGET"
+        }"#;
+        let spec: BondSpec = serde_json::from_str(data).unwrap();
+        let cash_flows = spec.into_cash_flows(&market).unwrap();
+        assert_eq!(cash_flows.len(), 5);
+        let curr = Currency::from_str("EUR").unwrap();
+        let reference_cash_flows = [
+            CashFlow::new(
+                0.05 * 1000. / 2. * 137. / 182.,
+                curr,
+                NaiveDate::from_ymd(2021, 4, 1),
+            ),
+            CashFlow::new(0.05 * 1000. / 2., curr, NaiveDate::from_ymd(2021, 10, 1)),
+            CashFlow::new(0.05 * 1000. / 2., curr, NaiveDate::from_ymd(2022, 4, 1)),
+            CashFlow::new(0.05 * 1000. / 2., curr, NaiveDate::from_ymd(2022, 10, 3)),
+            CashFlow::new(1000., curr, NaiveDate::from_ymd(2022, 10, 3)),
+        ];
+        let tol = 1e-11;
+        for (reference, cash_flow) in reference_cash_flows.iter().zip(cash_flows.iter()) {
+            assert!(reference.fuzzy_cash_flows_cmp_eq(cash_flow, tol));
+        }
+
+        let mut spec = spec;
+        spec.calendar = "unknown".to_string();
+        assert!(spec.into_cash_flows(&market).is_err());
+    }
+
+    #[tokio::test]
+    async fn bond_spec_payment_schedule() {
+        let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
+        assert!(
+            db_url.is_ok(),
+            "environment variable $FINQL_TEST_DATABASE_URL is not set"
+        );
+        let db = crate::postgres::PostgresDB::new(&db_url.unwrap())
+            .await
+            .unwrap();
+        let market = Market::new(std::sync::Arc::new(db)).await;
+
+        let data = r#"{
+            "isin": "DE0001234567",
+            "currency": "EUR",
+            "face": 1000,
+            "coupon_rate": 4,
+            "frequency": "6M",
+            "issue": "2021-04-15",
+            "maturity": "2023-04-15",
+            "dcc": "icma",
+            "first_coupon": null,
+            "business_day_adjustment": "modified",
+            "calendar": "TARGET"
+        }"#;
+        let spec: BondSpec = serde_json::from_str(data).unwrap();
+        let schedule = spec.payment_schedule(&market).unwrap();
+        // Good Friday and Easter Monday 2022 are TARGET holidays, the other
+        // unadjusted dates in 2022 and 2023 fall on Saturdays
+        let reference = [
+            (NaiveDate::from_ymd(2021, 10, 15), 20.),
+            (NaiveDate::from_ymd(2022, 4, 19), 20.),
+            (NaiveDate::from_ymd(2022, 10, 17), 20.),
+            (NaiveDate::from_ymd(2023, 4, 17), 1020.),
+        ];
+        assert_eq!(schedule.len(), reference.len());
+        let tol = 1e-11;
+        for ((date, amount), (ref_date, ref_amount)) in schedule.iter().zip(reference.iter()) {
+            assert_eq!(date, ref_date);
+            assert_eq!(amount.currency.to_string(), "EUR");
+            assert_fuzzy_eq!(amount.amount, *ref_amount, tol);
+        }
+    }
+
+    #[test]
+    fn bond_spec_validation() {
+        let data = r#"{
+            "isin": "DE0001234567",
+            "currency": "EUR",
+            "face": 1000,
+            "coupon_rate": 5,
+            "frequency": "1Y",
+            "issue": "2020-10-01",
+            "maturity": "2025-10-01",
+            "dcc": "act/act icma",
+            "first_coupon": "2021-10-01",
+            "business_day_adjustment": "following",
+            "calendar": "TARGET"
+        }"#;
+        let spec: BondSpec = serde_json::from_str(data).unwrap();
+        assert!(spec.validate().is_ok());
+
+        let mut invalid_spec = spec.clone();
+        invalid_spec.maturity = NaiveDate::from_ymd(2020, 9, 30);
+        assert!(matches!(
+            invalid_spec.validate(),
+            Err(BondError::MaturityBeforeIssue)
+        ));
+
+        let mut invalid_spec = spec.clone();
+        invalid_spec.frequency = TimePeriod::from_str("0M").unwrap();
+        assert!(matches!(
+            invalid_spec.validate(),
+            Err(BondError::InvalidFrequency)
+        ));
+
+        let mut invalid_spec = spec.clone();
+        invalid_spec.first_coupon = Some(NaiveDate::from_ymd(2026, 10, 1));
+        assert!(matches!(
+            invalid_spec.validate(),
+            Err(BondError::FirstCouponOutOfRange)
+        ));
+
+        let mut invalid_spec = spec;
+        invalid_spec.first_coupon = Some(NaiveDate::from_ymd(2020, 10, 1));
+        assert!(matches!(
+            invalid_spec.validate(),
+            Err(BondError::FirstCouponOutOfRange)
+        ));
+    }
+
+    #[test]
+    fn value_weighted_portfolio_yield() {
+        let data = r#"{
+            "isin": "DE0001234567",
+            "currency": "EUR",
+            "face": 1000,
+            "coupon_rate": 4,
+            "frequency": "6M",
+            "issue": "2021-04-15",
+            "maturity": "2023-04-15",
+            "dcc": "icma",
+            "first_coupon": null,
+            "business_day_adjustment": "modified",
+            "calendar": "TARGET"
+        }"#;
+        let short_bond: BondSpec = serde_json::from_str(data).unwrap();
+        let mut long_bond = short_bond.clone();
+        long_bond.isin = "DE0007654321".to_string();
+        long_bond.maturity = NaiveDate::from_ymd(2031, 4, 15);
+
+        let holdings = vec![(short_bond, 3000., 0.02), (long_bond, 1000., 0.04)];
+        let tol = 1e-12;
+        assert_fuzzy_eq!(portfolio_yield(&holdings).unwrap(), 0.025, tol);
+
+        let mut invalid_holdings = holdings.clone();
+        invalid_holdings[1].1 = -1000.;
+        assert!(matches!(
+            portfolio_yield(&invalid_holdings),
+            Err(BondError::InvalidWeights)
+        ));
+        assert!(matches!(
+            portfolio_yield(&[]),
+            Err(BondError::InvalidWeights)
+        ));
+    }
+
+    #[test]
+    fn clean_price_for_target_yield() {
+        let data = r#"{
+            "isin": "DE0001234567",
+            "currency": "EUR",
+            "face": 1000,
+            "coupon_rate": 4,
+            "frequency": "6M",
+            "issue": "2021-04-15",
+            "maturity": "2026-04-15",
+            "dcc": "icma",
+            "first_coupon": null,
+            "business_day_adjustment": "modified",
+            "calendar": "TARGET"
+        }"#;
+        let spec: BondSpec = serde_json::from_str(data).unwrap();
+        let sample_calendars = generate_calendars();
+        let calendar = SimpleCalendar::new(&sample_calendars["TARGET"]);
+        let settlement = NaiveDate::from_ymd(2022, 1, 3);
+        let tol = 1e-8;
+
+        let clean = clean_price_for_yield(&spec, 0.05, settlement, &calendar).unwrap();
+        // coupon below target yield, i.e. the bond trades below par
+        assert!(clean < spec.face);
+
+        // paying the dirty price per unit of face value gives back the target yield
+        let bond = spec.to_bond().unwrap();
+        let dirty = clean_to_dirty(
+            clean / spec.face,
+            bond.accrued_interest(settlement).unwrap(),
+        );
+        let purchase = CashFlow::new(-dirty, bond.currency, settlement);
+        let ytm = bond.calculate_ytm(&purchase, &calendar).unwrap();
+        assert_fuzzy_eq!(ytm, 0.05, tol);
+    }
+
+    #[test]
+    fn rounded_coupon_year_fractions() {
+        let data = r#"{
+            "isin": "DE0001234567",
+            "currency": "EUR",
+            "face": 1000,
+            "coupon_rate": 4,
+            "frequency": "3M",
+            "issue": "2020-12-15",
+            "maturity": "2022-01-31",
+            "dcc": "act/act icma",
+            "first_coupon": "2021-04-30",
+            "business_day_adjustment": "none",
+            "calendar": "TARGET"
+        }"#;
+        let mut spec: BondSpec = serde_json::from_str(data).unwrap();
+        assert_eq!(spec.year_fraction_decimals, None);
+        let sample_calendars = generate_calendars();
+        let calendar = SimpleCalendar::new(&sample_calendars["TARGET"]);
+        let cash_flows = spec
+            .to_bond()
+            .unwrap()
+            .rollout_cash_flows(1000., &calendar)
+            .unwrap();
+
+        spec.year_fraction_decimals = Some(2);
+        let rounded_cash_flows = spec
+            .to_bond()
+            .unwrap()
+            .rollout_cash_flows(1000., &calendar)
+            .unwrap();
+        assert_eq!(cash_flows.len(), rounded_cash_flows.len());
+        // the irregular first period is not a whole number of quarters
+        let first_yf = cash_flows[0].amount.amount / 40.;
+        assert!((first_yf * 100. - (first_yf * 100.).round()).abs() > 1e-3);
+        for (cf, rounded_cf) in cash_flows.iter().zip(rounded_cash_flows.iter()) {
+            assert_eq!(cf.date, rounded_cf.date);
+        }
+        for (cf, rounded_cf) in cash_flows
+            .iter()
+            .zip(rounded_cash_flows.iter())
+            .take(cash_flows.len() - 1)
+        {
+            let yf = cf.amount.amount / 40.;
+            let rounded_yf = (yf * 100.).round() / 100.;
+            assert!((rounded_cf.amount.amount - 40. * rounded_yf).abs() < 1e-12);
+        }
+    }
 }
@@ -0,0 +1,287 @@
+//! User defined calendars
+//! Built-in calendars are static. User defined calendars allow to add ad-hoc holidays
+//! (e.g. extraordinary exchange closures) or to remove holidays from a given base calendar.
+//! User calendars are stored persistently as objects via an `ObjectHandler`.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use cal_calc::{Calendar, Holiday};
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::datatypes::{DataError, ObjectHandler};
+
+/// Object type used to store user calendars
+pub const USER_CALENDAR: &str = "user_calendar";
+
+/// Default first year covered by materialized user calendars, same as for the built-in calendars
+pub const DEFAULT_FIRST_YEAR: i32 = 1990;
+/// Default last year covered by materialized user calendars, same as for the built-in calendars
+pub const DEFAULT_LAST_YEAR: i32 = 2050;
+
+fn default_first_year() -> i32 {
+    DEFAULT_FIRST_YEAR
+}
+
+fn default_last_year() -> i32 {
+    DEFAULT_LAST_YEAR
+}
+
+/// Error related to user defined calendars
+#[derive(Error, Debug)]
+pub enum CalendarError {
+    #[error("Unknown base calendar: {0}")]
+    BaseCalendarNotFound(String),
+    #[error("Invalid year range {0} to {1}")]
+    InvalidYearRange(i32, i32),
+    #[error("Failed to access user calendar in database")]
+    DataError(#[from] DataError),
+}
+
+/// Calendar defined by the user, optionally based on an existing calendar
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UserCalendar {
+    pub name: String,
+    /// Name of calendar the user calendar is derived from.
+    /// If none is given, only Saturdays and Sundays are non-business days.
+    pub base_calendar: Option<String>,
+    /// Holidays in addition to the holidays of the base calendar
+    pub additional_holidays: Vec<NaiveDate>,
+    /// Holidays of the base calendar which should be business days
+    pub removed_holidays: Vec<NaiveDate>,
+    /// First year covered by the materialized calendar
+    #[serde(default = "default_first_year")]
+    pub first_year: i32,
+    /// Last year covered by the materialized calendar
+    #[serde(default = "default_last_year")]
+    pub last_year: i32,
+}
+
+impl UserCalendar {
+    pub fn new(name: &str, base_calendar: Option<String>) -> UserCalendar {
+        UserCalendar {
+            name: name.to_string(),
+            base_calendar,
+            additional_holidays: Vec::new(),
+            removed_holidays: Vec::new(),
+            first_year: DEFAULT_FIRST_YEAR,
+            last_year: DEFAULT_LAST_YEAR,
+        }
+    }
+
+    /// Set the range of years covered by the materialized calendar
+    pub fn set_year_range(&mut self, first_year: i32, last_year: i32) {
+        self.first_year = first_year;
+        self.last_year = last_year;
+    }
+
+    /// Add a holiday; if the holiday has been removed before, the removal is reverted
+    pub fn add_holiday(&mut self, date: NaiveDate) {
+        self.removed_holidays.retain(|d| *d != date);
+        if !self.additional_holidays.contains(&date) {
+            self.additional_holidays.push(date);
+        }
+    }
+
+    /// Remove a holiday; if the holiday has been added before, the addition is reverted
+    pub fn remove_holiday(&mut self, date: NaiveDate) {
+        self.additional_holidays.retain(|d| *d != date);
+        if !self.removed_holidays.contains(&date) {
+            self.removed_holidays.push(date);
+        }
+    }
+}
+
+/// Construct calendar from user calendar by applying additional and removed holidays
+/// to the base calendar. Holidays of the base calendar are taken into account for the
+/// years `first_year` to `last_year` of the user calendar only.
+pub fn materialize(
+    user_cal: &UserCalendar,
+    base_calendars: &BTreeMap<String, Arc<Calendar>>,
+) -> Result<Calendar, CalendarError> {
+    let (first_year, last_year) = (user_cal.first_year, user_cal.last_year);
+    let start = NaiveDate::from_ymd_opt(first_year, 1, 1)
+        .filter(|_| first_year <= last_year && NaiveDate::from_ymd_opt(last_year, 12, 31).is_some())
+        .ok_or(CalendarError::InvalidYearRange(first_year, last_year))?;
+    let mut holidays = Vec::new();
+    match &user_cal.base_calendar {
+        None => {
+            holidays.push(Holiday::WeekDay(Weekday::Sat));
+            holidays.push(Holiday::WeekDay(Weekday::Sun));
+        }
+        Some(base_name) => {
+            let base = base_calendars
+                .get(base_name)
+                .ok_or_else(|| CalendarError::BaseCalendarNotFound(base_name.clone()))?;
+            // Calendar does not expose its holidays, therefore reconstruct them day by day
+            for offset in 0..7 {
+                let date = start + Duration::days(offset);
+                if base.is_weekend(date) {
+                    holidays.push(Holiday::WeekDay(date.weekday()));
+                }
+            }
+            let mut date = start;
+            while date.year() <= last_year {
+                if base.is_holiday(date) && !user_cal.removed_holidays.contains(&date) {
+                    holidays.push(Holiday::SingularDay(date));
+                }
+                date += Duration::days(1);
+            }
+        }
+    }
+    for date in &user_cal.additional_holidays {
+        holidays.push(Holiday::SingularDay(*date));
+    }
+    Ok(Calendar::calc_calendar(&holidays, first_year, last_year))
+}
+
+/// Store user calendar in database, replacing any previous version
+pub async fn save_user_calendar<DB: ObjectHandler + Sync>(
+    db: &DB,
+    user_cal: &UserCalendar,
+) -> Result<(), CalendarError> {
+    let names = db.get_object_ids(USER_CALENDAR).await?;
+    if names.contains(&user_cal.name) {
+        db.update_object(&user_cal.name, user_cal).await?;
+    } else {
+        db.store_object(&user_cal.name, USER_CALENDAR, user_cal)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Load user calendar from database
+pub async fn load_user_calendar<DB: ObjectHandler + Sync>(
+    db: &DB,
+    name: &str,
+) -> Result<UserCalendar, CalendarError> {
+    Ok(db.get_object(name).await?)
+}
+
+/// Get the names of all user calendars stored in database
+pub async fn list_user_calendars<DB: ObjectHandler + Sync>(
+    db: &DB,
+) -> Result<Vec<String>, CalendarError> {
+    Ok(db.get_object_ids(USER_CALENDAR).await?)
+}
+
+/// Remove user calendar from database
+pub async fn delete_user_calendar<DB: ObjectHandler + Sync>(
+    db: &DB,
+    name: &str,
+) -> Result<(), CalendarError> {
+    Ok(db.delete_object(name).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    use crate::datatypes::QuoteHandler;
+    use crate::market::{generate_calendars, Market, MarketError};
+    use crate::postgres::PostgresDB;
+    use crate::time_period::TimePeriod;
+
+    #[test]
+    fn materialize_user_calendar() {
+        let base_calendars = generate_calendars()
+            .into_iter()
+            .map(|(name, calendar)| (name, Arc::new(calendar)))
+            .collect();
+        let mut user_cal = UserCalendar::new("USER_DEFINED", Some("TARGET".to_string()));
+        // Thursday, not a TARGET holiday
+        let new_holiday = NaiveDate::from_ymd(2021, 11, 4);
+        // Christmas day, a Friday
+        let christmas = NaiveDate::from_ymd(2020, 12, 25);
+        user_cal.add_holiday(new_holiday);
+        user_cal.remove_holiday(christmas);
+        let cal = materialize(&user_cal, &base_calendars).unwrap();
+        assert!(cal.is_holiday(new_holiday));
+        assert!(cal.is_business_day(christmas));
+        assert!(cal.is_holiday(NaiveDate::from_ymd(2021, 1, 1)));
+        assert!(cal.is_weekend(NaiveDate::from_ymd(2021, 11, 6)));
+
+        let unknown = UserCalendar::new("USER_DEFINED", Some("unknown".to_string()));
+        assert!(materialize(&unknown, &base_calendars).is_err());
+
+        user_cal.set_year_range(2021, 2022);
+        let cal = materialize(&user_cal, &base_calendars).unwrap();
+        assert!(cal.is_holiday(new_holiday));
+        assert!(cal.is_holiday(NaiveDate::from_ymd(2022, 12, 26)));
+        user_cal.set_year_range(2022, 2021);
+        assert!(materialize(&user_cal, &base_calendars).is_err());
+    }
+
+    #[tokio::test]
+    async fn user_calendar_in_database() {
+        let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
+        assert!(
+            db_url.is_ok(),
+            "environment variable $FINQL_TEST_DATABASE_URL is not set"
+        );
+        let db = PostgresDB::new(&db_url.unwrap()).await.unwrap();
+        db.clean().await.unwrap();
+
+        let mut user_cal = UserCalendar::new("USER_DEFINED", Some("TARGET".to_string()));
+        save_user_calendar(&db, &user_cal).await.unwrap();
+        let new_holiday = NaiveDate::from_ymd(2021, 11, 4);
+        user_cal.add_holiday(new_holiday);
+        save_user_calendar(&db, &user_cal).await.unwrap();
+
+        assert_eq!(
+            list_user_calendars(&db).await.unwrap(),
+            vec!["USER_DEFINED".to_string()]
+        );
+        let stored_cal = load_user_calendar(&db, "USER_DEFINED").await.unwrap();
+        assert_eq!(stored_cal, user_cal);
+
+        let db = Arc::new(db);
+        let qh: Arc<dyn QuoteHandler + Send + Sync> = db.clone();
+        let market = Market::new(qh).await;
+        assert!(market.get_calendar("USER_DEFINED").is_err());
+        market.load_user_calendars(db.as_ref()).await.unwrap();
+        let cal = market.get_calendar("USER_DEFINED").unwrap();
+        let one_bday = TimePeriod::from_str("1B").unwrap();
+        let day_before = NaiveDate::from_ymd(2021, 11, 3);
+        assert_eq!(
+            one_bday.add_to(day_before, Some(&cal)),
+            NaiveDate::from_ymd(2021, 11, 5)
+        );
+
+        // calendars stored in the database are found without loading them explicitly
+        let market = Market::new(db.clone()).await;
+        assert!(market.get_calendar("USER_DEFINED").is_err());
+        let cal = market
+            .get_or_load_calendar(db.as_ref(), "USER_DEFINED")
+            .await
+            .unwrap();
+        assert!(cal.is_holiday(new_holiday));
+        assert!(market.get_calendar("USER_DEFINED").is_ok());
+        assert!(matches!(
+            market.get_or_load_calendar(db.as_ref(), "UNKNOWN").await,
+            Err(MarketError::CalendarNotFound)
+        ));
+
+        // calendars saved via the market are available immediately
+        let other_cal = UserCalendar::new("OTHER", None);
+        market
+            .save_user_calendar(db.as_ref(), &other_cal)
+            .await
+            .unwrap();
+        assert!(market.get_calendar("OTHER").is_ok());
+        let invalid_cal = UserCalendar::new("INVALID", Some("unknown".to_string()));
+        assert!(market
+            .save_user_calendar(db.as_ref(), &invalid_cal)
+            .await
+            .is_err());
+        delete_user_calendar(db.as_ref(), "OTHER").await.unwrap();
+
+        delete_user_calendar(db.as_ref(), "USER_DEFINED")
+            .await
+            .unwrap();
+        assert!(list_user_calendars(db.as_ref()).await.unwrap().is_empty());
+    }
+}
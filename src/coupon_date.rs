@@ -1,3 +1,4 @@
+use chrono::NaiveDate;
 use serde::de::{self, Visitor};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::error;
@@ -6,7 +7,8 @@ use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
 /// Month and day that serves as a reference for rolling out the cash flows
-/// This should equal the (unadjusted) first coupon's end date
+/// This should equal the (unadjusted) first coupon's end date.
+/// February 29th is a valid coupon date, which falls on February 28th in non-leap years.
 #[derive(Debug, PartialEq)]
 pub struct CouponDate {
     day: u32,
@@ -62,10 +64,9 @@ impl CouponDate {
         if day == 0 || month == 0 || month > 12 {
             return Err(CouponDateError::DayOutOfRange);
         }
-        // Any year that is not a leap year will do.
-        // We exclude explicitly February 29th, which is not a proper chosen coupon date
-        let last = cal_calc::last_day_of_month(2019, month);
-        if day > 0 && month > 0 && month <= 12 && day <= last {
+        // Any leap year will do to allow for February 29th
+        let last = cal_calc::last_day_of_month(2020, month);
+        if day <= last {
             Ok(CouponDate { day, month })
         } else {
             Err(CouponDateError::DayToBig)
@@ -77,24 +78,39 @@ impl CouponDate {
     pub fn month(&self) -> u32 {
         self.month
     }
+
+    /// Resolve the coupon date to the concrete date in the given year.
+    /// A coupon date on February 29th resolves to February 28th in non-leap years.
+    /// Returns None if the year is out of the range of representable dates.
+    pub fn date_in_year(&self, year: i32) -> Option<NaiveDate> {
+        // Since day and month are valid in leap years, only February 29th may fail for valid years
+        NaiveDate::from_ymd_opt(year, self.month, self.day)
+            .or_else(|| NaiveDate::from_ymd_opt(year, 2, 28).filter(|_| self.month == 2))
+    }
 }
 
-/// Write CouponDate as in the form dd.mm
+/// Write CouponDate as in the form mm-dd
 impl Display for CouponDate {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{:0>2}.{:0>2}", self.day, self.month)
+        write!(f, "{:0>2}-{:0>2}", self.month, self.day)
     }
 }
 
-/// Transform a string into a CouponDate
+/// Transform a string of the form mm-dd into a CouponDate
+/// For backward compatibility, the format dd.mm is accepted as well.
 impl FromStr for CouponDate {
     type Err = CouponDateError;
 
     fn from_str(coupon_date: &str) -> Result<Self, Self::Err> {
-        let nums: Vec<_> = coupon_date.trim().split('.').collect();
-        let day = nums[0].parse::<u32>()?;
-        let month = nums[1].parse::<u32>()?;
-        CouponDate::new(day, month)
+        let coupon_date = coupon_date.trim();
+        let (day, month) = if let Some((month, day)) = coupon_date.split_once('-') {
+            (day, month)
+        } else if let Some((day, month)) = coupon_date.split_once('.') {
+            (day, month)
+        } else {
+            return Err(CouponDateError::ParseError);
+        };
+        CouponDate::new(day.parse::<u32>()?, month.parse::<u32>()?)
     }
 }
 
@@ -113,7 +129,7 @@ impl<'de> Visitor<'de> for CouponDateVisitor {
     type Value = CouponDate;
 
     fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        formatter.write_str("a coupon date of the format <day>.<month>")
+        formatter.write_str("a coupon date of the format <month>-<day>")
     }
 
     fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
@@ -145,6 +161,8 @@ mod tests {
         let cd = CouponDate::new(2, 1);
         assert!(cd.is_ok());
         let cd = CouponDate::new(29, 2);
+        assert!(cd.is_ok());
+        let cd = CouponDate::new(30, 2);
         assert!(cd.is_err());
         let cd = CouponDate::new(31, 11);
         assert!(cd.is_err());
@@ -158,27 +176,68 @@ mod tests {
 
     #[test]
     fn deserialize_coupon_date() {
-        let input = r#""10.12""#;
+        let input = r#""12-10""#;
 
         let cd: CouponDate = serde_json::from_str(input).unwrap();
         assert_eq!(cd.day, 10);
         assert_eq!(cd.month, 12);
         let cdt = CouponDate { day: 10, month: 12 };
         assert_eq!(cd, cdt);
+
+        // legacy format
+        let input = r#""10.12""#;
+        let cd: CouponDate = serde_json::from_str(input).unwrap();
+        assert_eq!(cd, cdt);
+
+        let input = r#""02-30""#;
+        assert!(serde_json::from_str::<CouponDate>(input).is_err());
     }
     #[test]
     fn serialize_coupon_date() {
         let cd = CouponDate::new(2, 1).unwrap();
         let json = serde_json::to_string(&cd).unwrap();
-        assert_eq!(json, r#""02.01""#);
+        assert_eq!(json, r#""01-02""#);
         let cd = CouponDate::new(22, 2).unwrap();
         let json = serde_json::to_string(&cd).unwrap();
-        assert_eq!(json, r#""22.02""#);
+        assert_eq!(json, r#""02-22""#);
         let cd = CouponDate::new(10, 12).unwrap();
         let json = serde_json::to_string(&cd).unwrap();
-        assert_eq!(json, r#""10.12""#);
+        assert_eq!(json, r#""12-10""#);
         let cd = CouponDate::new(1, 12).unwrap();
         let json = serde_json::to_string(&cd).unwrap();
-        assert_eq!(json, r#""01.12""#);
+        assert_eq!(json, r#""12-01""#);
+    }
+
+    #[test]
+    fn resolve_coupon_date() {
+        let cd = CouponDate::new(15, 3).unwrap();
+        assert_eq!(cd.to_string(), "03-15");
+        assert_eq!(
+            cd.date_in_year(2019).unwrap(),
+            NaiveDate::from_ymd(2019, 3, 15)
+        );
+        assert_eq!(
+            cd.date_in_year(2020).unwrap(),
+            NaiveDate::from_ymd(2020, 3, 15)
+        );
+        assert_eq!(
+            cd.date_in_year(2021).unwrap(),
+            NaiveDate::from_ymd(2021, 3, 15)
+        );
+
+        let cd = CouponDate::new(29, 2).unwrap();
+        assert_eq!(
+            cd.date_in_year(2020).unwrap(),
+            NaiveDate::from_ymd(2020, 2, 29)
+        );
+        assert_eq!(
+            cd.date_in_year(2021).unwrap(),
+            NaiveDate::from_ymd(2021, 2, 28)
+        );
+        assert_eq!(
+            cd.date_in_year(2100).unwrap(),
+            NaiveDate::from_ymd(2100, 2, 28)
+        );
+        assert_eq!(cd.date_in_year(300_000), None);
     }
 }
@@ -1,13 +1,14 @@
 ///! Implementation of a container for basic asset data
 use serde::{Deserialize, Serialize};
 
-use super::{Currency, DataError, DataItem, Stock};
+use super::{Currency, DataError, DataItem, Index, Stock};
 
 ///! Asset enum could contain any supported asset
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Asset {
     Currency(Currency),
     Stock(Stock),
+    Index(Index),
 }
 
 ///! AssetSelector is useful for creation of choice list to choose an asset from
@@ -23,6 +24,7 @@ impl Asset {
         match self {
             Self::Currency(_) => "currency".into(),
             Self::Stock(_) => "stock".into(),
+            Self::Index(_) => "index".into(),
         }
     }
 
@@ -30,6 +32,7 @@ impl Asset {
         match self {
             Self::Currency(c) => c.iso_code.to_string(),
             Self::Stock(s) => s.name.clone(),
+            Self::Index(i) => i.name.clone(),
         }
     }
 }
@@ -40,6 +43,7 @@ impl DataItem for Asset {
         match self {
             Asset::Currency(c) => c.get_id(),
             Asset::Stock(s) => s.get_id(),
+            Asset::Index(i) => i.get_id(),
         }
     }
 
@@ -56,6 +60,11 @@ impl DataItem for Asset {
                 s.set_id(id)?;
                 Asset::Stock(s)
             }
+            Asset::Index(i) => {
+                let mut i = i.clone();
+                i.set_id(id)?;
+                Asset::Index(i)
+            }
         };
         Ok(())
     }
@@ -16,6 +16,13 @@ pub trait AssetHandler {
     async fn get_asset_list(&self) -> Result<Vec<AssetSelector>, DataError>;
     async fn update_asset(&self, asset: &Asset) -> Result<(), DataError>;
     async fn delete_asset(&self, id: i32) -> Result<(), DataError>;
+    /// Delete asset together with all tickers of (or quoted in) this asset and their quotes.
+    /// Fails if the asset is still referenced by any transaction.
+    async fn delete_asset_cascade(&self, id: i32) -> Result<(), DataError>;
+    /// Merge two assets of the same asset class representing the same security by
+    /// moving all transactions and tickers referring to `merge_id` to `keep_id` and
+    /// deleting the asset with id `merge_id` afterwards.
+    async fn merge_assets(&self, keep_id: i32, merge_id: i32) -> Result<(), DataError>;
     async fn get_all_currencies(&self) -> Result<Vec<Currency>, DataError>;
     /// Get a list of currencies as list of AssetSelectors
     async fn get_currency_list(&self) -> Result<Vec<AssetSelector>, DataError>;
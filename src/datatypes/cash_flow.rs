@@ -9,6 +9,8 @@ use chrono::{DateTime, Local, NaiveDate};
 use serde::{Deserialize, Serialize};
 
 use super::{Currency, CurrencyConverter, CurrencyError};
+use crate::day_count_conv::DayCountConv;
+use crate::fixed_income::FixedIncomeError;
 
 /// Container for an amount of money in some currency
 #[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
@@ -161,6 +163,54 @@ impl CashFlow {
             && !cf.amount.amount.is_nan()
             && (self.amount.amount - cf.amount.amount).abs() <= tol
     }
+
+    /// Present value at settlement date with annually compounded yield
+    pub fn present_value(
+        &self,
+        yield_rate: f64,
+        settlement: NaiveDate,
+        day_count: DayCountConv,
+    ) -> Result<f64, FixedIncomeError> {
+        let yf = day_count.year_fraction(settlement, self.date, None, None)?;
+        Ok(self.amount.amount / (1.0 + yield_rate).powf(yf))
+    }
+
+    /// Present value at settlement date with continuously compounded yield
+    pub fn present_value_continuous(
+        &self,
+        yield_rate: f64,
+        settlement: NaiveDate,
+        day_count: DayCountConv,
+    ) -> Result<f64, FixedIncomeError> {
+        let yf = day_count.year_fraction(settlement, self.date, None, None)?;
+        Ok(self.amount.amount * (-yield_rate * yf).exp())
+    }
+
+    /// Time weighted share of this cash flow in the total present value of a set of cash flows
+    /// Summing up the weights of all cash flows yields the Macaulay duration.
+    pub fn duration_weight(
+        &self,
+        yield_rate: f64,
+        settlement: NaiveDate,
+        day_count: DayCountConv,
+        total_present_value: f64,
+    ) -> Result<f64, FixedIncomeError> {
+        let yf = day_count.year_fraction(settlement, self.date, None, None)?;
+        Ok(yf * self.present_value(yield_rate, settlement, day_count)? / total_present_value)
+    }
+}
+
+/// Net present value of a set of cash flows at the valuation date, discounted with an
+/// annually compounded flat rate. Currencies of the cash flows are not checked.
+pub fn npv(
+    cash_flows: &[CashFlow],
+    rate: f64,
+    valuation_date: NaiveDate,
+    dcc: DayCountConv,
+) -> Result<f64, FixedIncomeError> {
+    cash_flows.iter().try_fold(0.0, |sum, cf| {
+        Ok(sum + cf.present_value(rate, valuation_date, dcc)?)
+    })
 }
 
 impl Display for CashFlow {
@@ -179,3 +229,58 @@ impl Neg for CashFlow {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datatypes::CurrencyISOCode;
+
+    #[test]
+    fn cash_flow_present_value() {
+        let tol = 1e-11;
+        let curr = Currency::new(None, CurrencyISOCode::new("EUR").unwrap(), None);
+        let settlement = NaiveDate::from_ymd(2020, 1, 1);
+        let dcc = DayCountConv::Act365;
+        let cf = CashFlow::new(100.0, curr, NaiveDate::from_ymd(2022, 1, 1));
+        assert_fuzzy_eq!(cf.present_value(0.0, settlement, dcc).unwrap(), 100.0, tol);
+        assert_fuzzy_eq!(
+            cf.present_value_continuous(0.0, settlement, dcc).unwrap(),
+            100.0,
+            tol
+        );
+        let yf = 731. / 365.;
+        assert_fuzzy_eq!(
+            cf.present_value(0.05, settlement, dcc).unwrap(),
+            100.0 / 1.05_f64.powf(yf),
+            tol
+        );
+        assert_fuzzy_eq!(
+            cf.present_value_continuous(0.05, settlement, dcc).unwrap(),
+            100.0 * (-0.05 * yf).exp(),
+            tol
+        );
+
+        // Macaulay duration of a zero bond equals its time to maturity
+        let total = cf.present_value(0.05, settlement, dcc).unwrap();
+        assert_fuzzy_eq!(
+            cf.duration_weight(0.05, settlement, dcc, total).unwrap(),
+            yf,
+            tol
+        );
+    }
+
+    #[test]
+    fn net_present_value() {
+        let tol = 1e-11;
+        let curr = Currency::new(None, CurrencyISOCode::new("EUR").unwrap(), None);
+        let valuation_date = NaiveDate::from_ymd(2020, 1, 1);
+        let cash_flows = [
+            CashFlow::new(-100.0, curr, NaiveDate::from_ymd(2020, 1, 1)),
+            CashFlow::new(110.0, curr, NaiveDate::from_ymd(2021, 12, 31)),
+        ];
+        // 2021-12-31 is 730 days after valuation date, i.e. exactly two years act/365
+        let value = npv(&cash_flows, 0.05, valuation_date, DayCountConv::Act365).unwrap();
+        assert_fuzzy_eq!(value, -100.0 + 110.0 / (1.05 * 1.05), tol);
+        assert!(npv(&cash_flows, 0.05, valuation_date, DayCountConv::ActActICMA).is_err());
+    }
+}
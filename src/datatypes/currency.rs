@@ -1,7 +1,9 @@
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::str::FromStr;
 
 use super::{DataError, DataItem};
+use crate::helpers::format_number;
 use async_trait::async_trait;
 use chrono::{DateTime, Local};
 use serde::de::{self, Visitor};
@@ -31,7 +33,7 @@ pub enum CurrencyError {
     DataBaseError(String),
 }
 
-#[derive(Debug, Clone, PartialEq, Copy)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Copy)]
 pub struct CurrencyISOCode {
     iso_code: [char; 3],
 }
@@ -61,6 +63,44 @@ impl CurrencyISOCode {
     }
 }
 
+/// Commonly used symbols of currencies as pairs of ISO code and symbol
+const CURRENCY_SYMBOLS: [(&str, &str); 15] = [
+    ("EUR", "€"),
+    ("USD", "$"),
+    ("GBP", "£"),
+    ("JPY", "¥"),
+    ("CNY", "CN¥"),
+    ("AUD", "A$"),
+    ("CAD", "CA$"),
+    ("HKD", "HK$"),
+    ("NZD", "NZ$"),
+    ("BRL", "R$"),
+    ("INR", "₹"),
+    ("KRW", "₩"),
+    ("RUB", "₽"),
+    ("TRY", "₺"),
+    ("ILS", "₪"),
+];
+
+impl CurrencyISOCode {
+    /// Commonly used symbol of the currency, if there is any
+    pub fn symbol(&self) -> Option<&'static str> {
+        let code = self.to_string();
+        CURRENCY_SYMBOLS
+            .iter()
+            .find(|(iso_code, _)| *iso_code == code)
+            .map(|(_, symbol)| *symbol)
+    }
+
+    /// Currency denoted by the given symbol, if known
+    pub fn from_symbol(symbol: &str) -> Option<CurrencyISOCode> {
+        CURRENCY_SYMBOLS
+            .iter()
+            .find(|(_, s)| *s == symbol)
+            .and_then(|(iso_code, _)| CurrencyISOCode::new(iso_code).ok())
+    }
+}
+
 impl fmt::Display for CurrencyISOCode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -80,13 +120,34 @@ impl FromStr for CurrencyISOCode {
 }
 
 /// Special type for currencies
-#[derive(Debug, Clone, PartialEq, Copy)]
+#[derive(Debug, Clone, Eq, Copy)]
 pub struct Currency {
     pub id: Option<i32>,
     pub iso_code: CurrencyISOCode,
     pub rounding_digits: i32,
 }
 
+/// Currencies are equal if they have the same ISO code, the id and rounding digits are ignored
+impl PartialEq for Currency {
+    fn eq(&self, other: &Self) -> bool {
+        self.iso_code == other.iso_code
+    }
+}
+
+/// The hash depends on the ISO code only, consistent with equality, so that the same currency
+/// always ends up in the same bucket, whether or not it has been stored in the database
+impl Hash for Currency {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.iso_code.hash(state);
+    }
+}
+
+/// Check if two currencies have the same ISO code, ignoring the id and rounding digits,
+/// which is the same as comparing them with `==`
+pub fn is_same_currency(a: &Currency, b: &Currency) -> bool {
+    a == b
+}
+
 impl Currency {
     pub fn new(id: Option<i32>, iso_code: CurrencyISOCode, rounding_digits: Option<i32>) -> Self {
         Self {
@@ -185,6 +246,20 @@ impl Currency {
     pub fn rounding_digits(&self) -> i32 {
         self.rounding_digits
     }
+
+    /// Format amount rounded to the currency's rounding digits and prefixed by the currency
+    /// symbol, e.g. `€1,234.56`. Currencies without symbol are prefixed by their ISO code.
+    pub fn format_amount(&self, amount: f64) -> String {
+        let number = format_number(amount, self.rounding_digits, ',', '.');
+        let (sign, number) = match number.strip_prefix('-') {
+            Some(number) => ("-", number),
+            None => ("", number.as_str()),
+        };
+        match self.iso_code.symbol() {
+            Some(symbol) => format!("{}{}{}", sign, symbol, number),
+            None => format!("{}{} {}", sign, self.iso_code, number),
+        }
+    }
 }
 
 /// Trait for calculating FX rates for currency conversion
@@ -202,6 +277,36 @@ pub trait CurrencyConverter {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::HashMap;
+
+    fn hash_of<T: Hash>(value: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn hash_currency() {
+        let eur_code = CurrencyISOCode::new("EUR").unwrap();
+        let eur1 = Currency::new(Some(1), eur_code, None);
+        let eur2 = Currency::new(Some(2), eur_code, Some(4));
+        let usd = Currency::new(Some(1), CurrencyISOCode::new("USD").unwrap(), None);
+        assert_eq!(hash_of(&eur1), hash_of(&eur2));
+        assert_eq!(eur1, eur2);
+        assert!(eur1 != usd);
+        assert!(is_same_currency(&eur1, &eur2));
+        assert!(!is_same_currency(&eur1, &usd));
+        assert_eq!(
+            hash_of(&eur_code),
+            hash_of(&CurrencyISOCode::new("eur").unwrap())
+        );
+
+        let mut fx_rates = HashMap::new();
+        fx_rates.insert(eur_code, 1.0);
+        fx_rates.insert(usd.iso_code, 1.2);
+        assert_eq!(fx_rates[&CurrencyISOCode::new("USD").unwrap()], 1.2);
+    }
 
     #[test]
     fn read_write_currency() {
@@ -237,6 +342,19 @@ mod tests {
         let curr: Currency = serde_json::from_str(input).unwrap();
         assert_eq!(format!("{}", curr), "EUR");
     }
+    #[test]
+    fn format_amounts() {
+        let eur = Currency::from_str("EUR").unwrap();
+        assert_eq!(eur.iso_code.symbol(), Some("€"));
+        assert_eq!(eur.format_amount(1234.561), "€1,234.56");
+        assert_eq!(eur.format_amount(-1234.56), "-€1,234.56");
+        let jpy = Currency::from_str("JPY").unwrap();
+        assert_eq!(jpy.format_amount(1234567.8), "¥1,234,568");
+        let chf = Currency::from_str("CHF").unwrap();
+        assert_eq!(chf.iso_code.symbol(), None);
+        assert_eq!(chf.format_amount(1234.5), "CHF 1,234.50");
+    }
+
     #[test]
     fn serialize_currency() {
         let curr = Currency {
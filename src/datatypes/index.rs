@@ -0,0 +1,42 @@
+//! Implementation of a container for benchmark indices, e.g. the S&P 500
+use super::{DataError, DataItem};
+use serde::{Deserialize, Serialize};
+
+/// Benchmark index which is not traded itself, its levels are stored as quotes
+/// of a ticker with a non-vendor source like "manual"
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Index {
+    pub id: Option<i32>,
+    pub name: String,
+    pub note: Option<String>,
+}
+
+impl Index {
+    pub fn new(id: Option<i32>, name: String, note: Option<String>) -> Self {
+        Self { id, name, note }
+    }
+}
+
+impl DataItem for Index {
+    // get id or return error if id hasn't been set yet
+    fn get_id(&self) -> Result<i32, DataError> {
+        match self.id {
+            Some(id) => Ok(id),
+            None => Err(DataError::DataAccessFailure(
+                "Can't get id of temporary index".to_string(),
+            )),
+        }
+    }
+    // set id or return error if id has already been set
+    fn set_id(&mut self, id: i32) -> Result<(), DataError> {
+        match self.id {
+            Some(_) => Err(DataError::DataAccessFailure(
+                "Can't change id of persistent index".to_string(),
+            )),
+            None => {
+                self.id = Some(id);
+                Ok(())
+            }
+        }
+    }
+}
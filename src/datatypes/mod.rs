@@ -8,6 +8,7 @@ pub mod asset_handler;
 pub mod cash_flow;
 pub mod currency;
 pub mod date_time_helper;
+pub mod index;
 pub mod object_handler;
 pub mod quote;
 pub mod quote_handler;
@@ -17,13 +18,14 @@ pub mod transaction_handler;
 
 pub use asset::{Asset, AssetSelector};
 pub use asset_handler::AssetHandler;
-pub use cash_flow::{CashAmount, CashFlow};
+pub use cash_flow::{npv, CashAmount, CashFlow};
 pub use currency::{Currency, CurrencyConverter, CurrencyError, CurrencyISOCode};
-pub use object_handler::ObjectHandler;
-pub use quote::{Quote, Ticker};
+pub use index::Index;
+pub use object_handler::{ObjectHandler, Versioned};
+pub use quote::{Quote, Ticker, TiebreakStrategy};
 pub use quote_handler::QuoteHandler;
-pub use stock::Stock;
-pub use transaction::{Transaction, TransactionType};
+pub use stock::{Stock, StockSplit};
+pub use transaction::{CompositeTransaction, Transaction, TransactionType};
 pub use transaction_handler::TransactionHandler;
 
 #[derive(Error, Debug)]
@@ -42,6 +44,8 @@ pub enum DataError {
     InvalidTransaction(String),
     #[error("Invalid currency")]
     InvalidCurrency(#[from] CurrencyError),
+    #[error("unsupported schema version of stored object: {0}")]
+    UnsupportedSchemaVersion(u32),
 }
 
 pub trait DataItem {
@@ -1,5 +1,6 @@
 use async_trait::async_trait;
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
 
 use super::DataError;
 
@@ -18,4 +19,172 @@ pub trait ObjectHandler {
         object: &T,
     ) -> Result<(), DataError>;
     async fn get_object<T: DeserializeOwned>(&self, id: &str) -> Result<T, DataError>;
+    /// Replace the stored object with given id
+    async fn update_object<T: Serialize + Sync>(
+        &self,
+        id: &str,
+        object: &T,
+    ) -> Result<(), DataError>;
+    async fn delete_object(&self, id: &str) -> Result<(), DataError>;
+    /// Get the ids of all objects of the given object type
+    async fn get_object_ids(&self, object_type: &str) -> Result<Vec<String>, DataError>;
+}
+
+/// Objects whose serialized layout may change over time. The schema version is stored
+/// alongside the object, such that payloads written by older versions of a type
+/// can be upgraded when they are loaded.
+pub trait Versioned {
+    /// Current version of the serialized layout
+    const SCHEMA_VERSION: u32;
+
+    /// Convert the payload of an object stored with an older schema version into
+    /// the current layout. Objects stored without any version are passed in as version 0.
+    fn upgrade(version: u32, _object: Value) -> Result<Value, DataError> {
+        Err(DataError::UnsupportedSchemaVersion(version))
+    }
+}
+
+/// Wrapper storing the object together with the version of its layout
+#[derive(Serialize, Deserialize)]
+struct VersionedObject<T> {
+    schema_version: u32,
+    object: T,
+}
+
+/// Store object with the current schema version of its type
+pub async fn store_versioned_object<DB, T>(
+    db: &DB,
+    id: &str,
+    object_type: &str,
+    object: &T,
+) -> Result<(), DataError>
+where
+    DB: ObjectHandler + Sync,
+    T: Versioned + Serialize + Sync,
+{
+    let versioned = VersionedObject {
+        schema_version: T::SCHEMA_VERSION,
+        object,
+    };
+    db.store_object(id, object_type, &versioned).await
+}
+
+/// Replace the stored object with given id, tagged with the current schema version of its type
+pub async fn update_versioned_object<DB, T>(db: &DB, id: &str, object: &T) -> Result<(), DataError>
+where
+    DB: ObjectHandler + Sync,
+    T: Versioned + Serialize + Sync,
+{
+    let versioned = VersionedObject {
+        schema_version: T::SCHEMA_VERSION,
+        object,
+    };
+    db.update_object(id, &versioned).await
+}
+
+/// Load a versioned object, payloads of older schema versions are upgraded
+/// via `Versioned::upgrade` before deserialization
+pub async fn get_versioned_object<DB, T>(db: &DB, id: &str) -> Result<T, DataError>
+where
+    DB: ObjectHandler + Sync,
+    T: Versioned + DeserializeOwned,
+{
+    let value: Value = db.get_object(id).await?;
+    let (version, object) =
+        if value.get("schema_version").is_some() && value.get("object").is_some() {
+            let versioned: VersionedObject<Value> = serde_json::from_value(value)?;
+            (versioned.schema_version, versioned.object)
+        } else {
+            (0, value)
+        };
+    let object = match version {
+        v if v == T::SCHEMA_VERSION => object,
+        v if v < T::SCHEMA_VERSION => T::upgrade(v, object)?,
+        v => return Err(DataError::UnsupportedSchemaVersion(v)),
+    };
+    Ok(serde_json::from_value(object)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres::PostgresDB;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct RateSpecV1 {
+        name: String,
+        rate: f64,
+    }
+
+    impl Versioned for RateSpecV1 {
+        const SCHEMA_VERSION: u32 = 1;
+    }
+
+    /// Second version of the layout, the rate is stored in percent
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct RateSpecV2 {
+        name: String,
+        rate_in_percent: f64,
+    }
+
+    impl Versioned for RateSpecV2 {
+        const SCHEMA_VERSION: u32 = 2;
+
+        fn upgrade(version: u32, object: Value) -> Result<Value, DataError> {
+            match version {
+                1 => {
+                    let old: RateSpecV1 = serde_json::from_value(object)?;
+                    Ok(serde_json::to_value(RateSpecV2 {
+                        name: old.name,
+                        rate_in_percent: old.rate * 100.,
+                    })?)
+                }
+                _ => Err(DataError::UnsupportedSchemaVersion(version)),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn upgrade_versioned_object() {
+        let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
+        assert!(
+            db_url.is_ok(),
+            "environment variable $FINQL_TEST_DATABASE_URL is not set"
+        );
+        let db = PostgresDB::new(&db_url.unwrap()).await.unwrap();
+        db.clean().await.unwrap();
+
+        let spec = RateSpecV1 {
+            name: "deposit".to_string(),
+            rate: 0.025,
+        };
+        store_versioned_object(&db, "rate_spec", "rate_spec", &spec)
+            .await
+            .unwrap();
+        let loaded: RateSpecV1 = get_versioned_object(&db, "rate_spec").await.unwrap();
+        assert_eq!(loaded, spec);
+
+        // a version 1 payload is upgraded when loaded as version 2
+        let upgraded: RateSpecV2 = get_versioned_object(&db, "rate_spec").await.unwrap();
+        assert_eq!(upgraded.name, "deposit");
+        assert!((upgraded.rate_in_percent - 2.5).abs() < 1e-12);
+
+        // payloads of newer versions can't be downgraded
+        update_versioned_object(&db, "rate_spec", &upgraded)
+            .await
+            .unwrap();
+        assert!(matches!(
+            get_versioned_object::<_, RateSpecV1>(&db, "rate_spec").await,
+            Err(DataError::UnsupportedSchemaVersion(2))
+        ));
+
+        // objects stored without version are passed to the upgrade as version 0
+        db.store_object("legacy_spec", "rate_spec", &spec)
+            .await
+            .unwrap();
+        assert!(matches!(
+            get_versioned_object::<_, RateSpecV2>(&db, "legacy_spec").await,
+            Err(DataError::UnsupportedSchemaVersion(0))
+        ));
+    }
 }
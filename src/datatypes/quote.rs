@@ -7,7 +7,7 @@ use serde::{Deserialize, Serialize};
 use super::Currency;
 use super::{DataError, DataItem};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Ticker {
     pub id: Option<i32>,
     pub asset: i32,
@@ -15,23 +15,72 @@ pub struct Ticker {
     pub currency: Currency,
     pub source: String,
     pub priority: i32,
+    /// Factor to convert prices as delivered by the market data source into
+    /// prices in the ticker's currency, e.g. 0.01 for quotes in pence of a ticker in GBP.
+    /// The factor is applied once when quotes are fetched, i.e. stored quotes are
+    /// always given in the ticker's currency and must not be scaled again.
     pub factor: f64,
     pub tz: Option<String>,
     pub cal: Option<String>,
+    /// Inactive tickers, e.g. of delisted securities, are skipped by `Market::update_quotes`
+    #[serde(default = "default_active")]
+    pub active: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+fn default_active() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Quote {
     pub id: Option<i32>,
     pub ticker: i32,
     pub price: f64,
     pub time: DateTime<Local>,
     pub volume: Option<f64>,
+    /// Quote was outdated when it has been fetched, e.g. a quote of the previous
+    /// trading day delivered after market close
+    #[serde(default)]
+    pub stale: bool,
 }
 
+impl Ticker {
+    /// Convert a price as delivered by the market data source into the ticker's currency
+    pub fn apply_factor(&self, price: f64) -> f64 {
+        price * self.factor
+    }
+
+    /// Convert a price in the ticker's currency back into the market data source's unit
+    pub fn remove_factor(&self, price: f64) -> f64 {
+        price / self.factor
+    }
+}
+
+/// Strategy to select a quote if several tickers of the same asset
+/// have quotes with the same time stamp
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TiebreakStrategy {
+    /// Take the quote of the ticker with the highest priority, i.e. lowest priority number
+    HighestPriority,
+    /// Take the quote that has been stored last
+    Latest,
+    /// Take the average price of all quotes given in the currency of the
+    /// highest priority quote
+    Average,
+}
+
+/// Quotes are ordered by time and ticker, the remaining fields are only used
+/// as tie-breaker to be consistent with equality
 impl Ord for Quote {
     fn cmp(&self, other: &Self) -> Ordering {
-        (self.time, &self.ticker).cmp(&(other.time, &other.ticker))
+        (self.time, &self.ticker, &self.id)
+            .cmp(&(other.time, &other.ticker, &other.id))
+            .then_with(|| self.price.total_cmp(&other.price))
+            .then_with(|| match (self.volume, other.volume) {
+                (Some(v1), Some(v2)) => v1.total_cmp(&v2),
+                (v1, v2) => v1.is_some().cmp(&v2.is_some()),
+            })
+            .then_with(|| self.stale.cmp(&other.stale))
     }
 }
 
@@ -41,13 +90,17 @@ impl PartialOrd for Quote {
     }
 }
 
-impl PartialEq for Quote {
-    fn eq(&self, other: &Self) -> bool {
-        (self.time, &self.ticker) == (other.time, &other.ticker)
-    }
+impl Eq for Quote {}
+
+/// Check if two quotes are equal, ignoring their ids
+pub fn quote_same_value(a: &Quote, b: &Quote) -> bool {
+    a.ticker == b.ticker && a.price == b.price && a.time == b.time && a.volume == b.volume
 }
 
-impl Eq for Quote {}
+/// Check if two tickers refer to the same name at the same market data source
+pub fn ticker_same_source(a: &Ticker, b: &Ticker) -> bool {
+    a.name == b.name && a.source == b.source
+}
 
 impl DataItem for Quote {
     // get id or return error if id hasn't been set yet
@@ -96,3 +149,58 @@ impl DataItem for Ticker {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datatypes::date_time_helper::make_time;
+    use std::str::FromStr;
+
+    #[test]
+    fn compare_quotes() {
+        let time = make_time(2021, 11, 5, 18, 0, 0).unwrap();
+        let quote1 = Quote {
+            id: Some(1),
+            ticker: 1,
+            price: 100.0,
+            time,
+            volume: None,
+            stale: false,
+        };
+        let mut quote2 = quote1.clone();
+        quote2.id = Some(2);
+        assert_ne!(quote1, quote2);
+        assert!(quote_same_value(&quote1, &quote2));
+        quote2.id = Some(1);
+        assert_eq!(quote1, quote2);
+        quote2.price = 101.0;
+        assert!(!quote_same_value(&quote1, &quote2));
+
+        let mut quotes = vec![quote1.clone(), quote1.clone(), quote2];
+        quotes.dedup();
+        assert_eq!(quotes.len(), 2);
+    }
+
+    #[test]
+    fn compare_ticker() {
+        let ticker1 = Ticker {
+            id: Some(1),
+            asset: 1,
+            name: "AAPL".to_string(),
+            currency: Currency::from_str("USD").unwrap(),
+            source: "yahoo".to_string(),
+            priority: 1,
+            factor: 1.0,
+            tz: None,
+            cal: None,
+            active: true,
+        };
+        let mut ticker2 = ticker1.clone();
+        assert_eq!(ticker1, ticker2);
+        ticker2.priority = 2;
+        assert_ne!(ticker1, ticker2);
+        assert!(ticker_same_source(&ticker1, &ticker2));
+        ticker2.source = "manual".to_string();
+        assert!(!ticker_same_source(&ticker1, &ticker2));
+    }
+}
@@ -5,7 +5,8 @@ use std::sync::Arc;
 
 use super::AssetHandler;
 use super::DataError;
-use super::{Currency, CurrencyISOCode, Quote, Ticker};
+use super::{Currency, CurrencyISOCode, Quote, Ticker, TiebreakStrategy};
+use crate::market_quotes::stats::TickerQuoteCoverage;
 
 /// Handler for globally available market quotes data
 #[async_trait]
@@ -24,10 +25,15 @@ pub trait QuoteHandler: AssetHandler {
     async fn get_all_ticker_for_asset(&self, asset_id: i32) -> Result<Vec<Ticker>, DataError>;
 
     async fn update_ticker(&self, ticker: &Ticker) -> Result<(), DataError>;
+    /// Mark ticker as inactive, e.g. for delisted securities, to exclude it from quote updates
+    async fn deactivate_ticker(&self, id: i32) -> Result<(), DataError>;
     async fn delete_ticker(&self, id: i32) -> Result<(), DataError>;
 
     /// Insert, get, update and delete for market data sources
     async fn insert_quote(&self, quote: &Quote) -> Result<i32, DataError>;
+    /// Insert quote only if it is newer than the latest quote stored for the same ticker
+    /// Returns the id of the new quote or None, if the quote has not been inserted.
+    async fn insert_quote_if_newer(&self, quote: &Quote) -> Result<Option<i32>, DataError>;
 
     /// Get the last quote in database for a specific currency iso code on or before the given time
     async fn get_last_fx_quote_before(
@@ -37,12 +43,40 @@ pub trait QuoteHandler: AssetHandler {
     ) -> Result<(Quote, Currency), DataError>;
 
     /// Get the last quote in database for a specific asset id on or before the given time
+    /// If several tickers have quotes at that time, the quote of the ticker with the highest
+    /// priority (i.e. the lowest priority number) is returned.
     async fn get_last_quote_before_by_id(
         &self,
         asset_id: i32,
         time: DateTime<Local>,
     ) -> Result<(Quote, Currency), DataError>;
 
+    /// Get the last quote in database for a specific asset id on or before the given time
+    /// Returns `None` if no such quote exists.
+    async fn try_get_last_quote_before_by_id(
+        &self,
+        asset_id: i32,
+        time: DateTime<Local>,
+    ) -> Result<Option<(Quote, Currency)>, DataError>;
+
+    /// Get the last quote in database for a specific asset id on or before the given time,
+    /// using the given strategy to choose between quotes of different tickers with the same time.
+    /// Averaged quotes are not stored in the database and therefore have no id.
+    async fn get_last_quote_before_with_strategy(
+        &self,
+        asset_id: i32,
+        time: DateTime<Local>,
+        strategy: TiebreakStrategy,
+    ) -> Result<(Quote, Currency), DataError>;
+
+    /// Get the first quote in database for a specific asset id after the given time
+    /// Returns `None` if no such quote exists.
+    async fn try_get_first_quote_after_by_id(
+        &self,
+        asset_id: i32,
+        time: DateTime<Local>,
+    ) -> Result<Option<(Quote, Currency)>, DataError>;
+
     /// Get all quotes within a time range for a specific asset id
     async fn get_quotes_in_range_by_id(
         &self,
@@ -51,8 +85,37 @@ pub trait QuoteHandler: AssetHandler {
         end: DateTime<Local>,
     ) -> Result<Vec<(Quote, i32)>, DataError>;
 
+    /// Get all quotes within a time range for a set of asset ids in a single query
+    /// Returns tuples of asset id, quote and currency id of the quote.
+    async fn get_quotes_in_range_for_assets(
+        &self,
+        asset_ids: &[i32],
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> Result<Vec<(i32, Quote, i32)>, DataError>;
+
     async fn get_all_quotes_for_ticker(&self, ticker_id: i32) -> Result<Vec<Quote>, DataError>;
+    /// Get the most recent quote of a ticker, or `None` if there are no quotes for the ticker
+    async fn get_last_quote_for_ticker(&self, ticker_id: i32) -> Result<Option<Quote>, DataError>;
+    /// Get number of quotes and time of first and last quote for all tickers
+    async fn get_quote_coverage_for_all_tickers(
+        &self,
+    ) -> Result<Vec<TickerQuoteCoverage>, DataError>;
+    /// Get time of the first and last quote of an asset over all its tickers
+    /// Returns `None` if there are no quotes for the asset.
+    async fn get_quote_range_for_asset(
+        &self,
+        asset_id: i32,
+    ) -> Result<Option<(DateTime<Local>, DateTime<Local>)>, DataError>;
+    /// Get all tickers together with the time of their most recent quote,
+    /// which is `None` for tickers without any quotes
+    async fn tickers_with_last_quote_time(
+        &self,
+    ) -> Result<Vec<(Ticker, Option<DateTime<Local>>)>, DataError>;
     async fn update_quote(&self, quote: &Quote) -> Result<(), DataError>;
     async fn delete_quote(&self, id: i32) -> Result<(), DataError>;
     async fn remove_duplicates(&self) -> Result<(), DataError>;
+    /// Remove quotes of the same ticker and time whose prices differ by not more than `tol`,
+    /// keeping the quote with the lowest id
+    async fn remove_duplicates_with_tolerance(&self, tol: f64) -> Result<(), DataError>;
 }
@@ -1,5 +1,6 @@
 use super::{DataError, DataItem};
 ///! Implementation of a container for basic asset data
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -52,3 +53,11 @@ impl DataItem for Stock {
         }
     }
 }
+
+/// Stock split, where each share held before `date` is turned into `ratio` shares,
+/// e.g. a ratio of 2.0 for a 2:1 split or 0.1 for a 1:10 reverse split
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StockSplit {
+    pub date: NaiveDate,
+    pub ratio: f64,
+}
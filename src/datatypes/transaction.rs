@@ -1,6 +1,7 @@
 use super::CashFlow;
 use super::{DataError, DataItem};
 ///! Implementation of basic transaction types
+use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
 
 /// Type of transaction
@@ -14,6 +15,20 @@ pub enum TransactionType {
     Fee { transaction_ref: Option<i32> },
 }
 
+impl TransactionType {
+    /// Short code of the transaction type, as used to store transactions in the database
+    pub fn code(&self) -> &'static str {
+        match self {
+            TransactionType::Cash => "c",
+            TransactionType::Asset { .. } => "a",
+            TransactionType::Dividend { .. } => "d",
+            TransactionType::Interest { .. } => "i",
+            TransactionType::Tax { .. } => "t",
+            TransactionType::Fee { .. } => "f",
+        }
+    }
+}
+
 /// Basic transaction data
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Transaction {
@@ -22,6 +37,8 @@ pub struct Transaction {
     pub transaction_type: TransactionType,
     pub cash_flow: CashFlow,
     pub note: Option<String>,
+    /// Time the transaction has been stored to the database, set by the database
+    pub time_stamp: Option<DateTime<Local>>,
 }
 
 impl Transaction {
@@ -51,6 +68,43 @@ impl Transaction {
             _ => self.transaction_type,
         }
     }
+
+    /// Check that the sign of the cash flow amount is consistent with the transaction type
+    /// By convention, buying an asset or paying fees or taxes reduces cash, while selling an
+    /// asset or receiving dividends or interest increases cash. Cash transactions may have
+    /// either sign.
+    pub fn validate_sign(&self) -> Result<(), DataError> {
+        let amount = self.cash_flow.amount.amount;
+        let valid = match self.transaction_type {
+            TransactionType::Cash => true,
+            TransactionType::Asset { position, .. } => {
+                if position > 0.0 {
+                    amount <= 0.0
+                } else {
+                    amount >= 0.0
+                }
+            }
+            TransactionType::Dividend { .. } | TransactionType::Interest { .. } => amount >= 0.0,
+            TransactionType::Tax { .. } | TransactionType::Fee { .. } => amount <= 0.0,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(DataError::InvalidTransaction(format!(
+                "cash amount {} has wrong sign for transaction type {:?}",
+                amount, self.transaction_type
+            )))
+        }
+    }
+}
+
+/// Trade together with its related fees and taxes, which are stored as a single unit.
+/// The transaction references of fees and taxes are set to the trade when stored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompositeTransaction {
+    pub trade: Transaction,
+    pub fees: Vec<Transaction>,
+    pub taxes: Vec<Transaction>,
 }
 
 impl DataItem for Transaction {
@@ -76,3 +130,53 @@ impl DataItem for Transaction {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datatypes::{CashAmount, Currency, CurrencyISOCode};
+    use chrono::NaiveDate;
+
+    fn transaction(transaction_type: TransactionType, amount: f64) -> Transaction {
+        Transaction {
+            id: None,
+            transaction_type,
+            cash_flow: CashFlow {
+                amount: CashAmount {
+                    amount,
+                    currency: Currency::new(None, CurrencyISOCode::new("EUR").unwrap(), None),
+                },
+                date: NaiveDate::from_ymd(2021, 3, 1),
+            },
+            note: None,
+            time_stamp: None,
+        }
+    }
+
+    #[test]
+    fn validate_cash_flow_sign() {
+        let buy = TransactionType::Asset {
+            asset_id: 1,
+            position: 10.0,
+        };
+        assert!(transaction(buy, -1000.0).validate_sign().is_ok());
+        assert!(matches!(
+            transaction(buy, 1000.0).validate_sign(),
+            Err(DataError::InvalidTransaction(_))
+        ));
+        let sell = TransactionType::Asset {
+            asset_id: 1,
+            position: -10.0,
+        };
+        assert!(transaction(sell, 1000.0).validate_sign().is_ok());
+        let dividend = TransactionType::Dividend { asset_id: 1 };
+        assert!(transaction(dividend, -5.0).validate_sign().is_err());
+        let fee = TransactionType::Fee {
+            transaction_ref: None,
+        };
+        assert!(transaction(fee, -5.0).validate_sign().is_ok());
+        assert!(transaction(TransactionType::Cash, -5.0)
+            .validate_sign()
+            .is_ok());
+    }
+}
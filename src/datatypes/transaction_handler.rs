@@ -2,7 +2,8 @@ use async_trait::async_trait;
 
 use super::AssetHandler;
 use super::DataError;
-use super::Transaction;
+use super::StockSplit;
+use super::{CompositeTransaction, Transaction};
 
 /// Handler for globally available data of transactions and related data
 #[async_trait]
@@ -13,4 +14,24 @@ pub trait TransactionHandler: AssetHandler {
     async fn get_all_transactions(&self) -> Result<Vec<Transaction>, DataError>;
     async fn update_transaction(&self, transaction: &Transaction) -> Result<(), DataError>;
     async fn delete_transaction(&self, id: i32) -> Result<(), DataError>;
+
+    /// Insert trade with its fees and taxes within a single database transaction, fees and
+    /// taxes are referencing the trade. Returns the ids of the trade, the fees and the taxes
+    /// in this order.
+    async fn insert_composite(
+        &self,
+        composite: &CompositeTransaction,
+    ) -> Result<Vec<i32>, DataError>;
+
+    /// Multiply the positions of all asset transactions of the given asset before each split's
+    /// date by the split's ratio, leaving the cash amounts unchanged. All splits are applied
+    /// within a single database transaction. Applied splits are recorded per asset and date,
+    /// splits that have already been applied are skipped. Fails without adjusting any
+    /// transaction if a split's ratio is not a positive number.
+    /// Returns the number of adjusted transactions.
+    async fn apply_stock_splits(
+        &self,
+        asset_id: i32,
+        splits: &[StockSplit],
+    ) -> Result<usize, DataError>;
 }
@@ -5,7 +5,7 @@ use serde::{Deserialize, Serialize};
 /// Rules to adjust dates to business days
 /// The rule "Modified Preceding" commonly referred to in text books
 /// was intentionally left out since
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
 pub enum DayAdjust {
     #[serde(rename = "none")]
     None,
@@ -24,17 +24,17 @@ impl DayAdjust {
         match self {
             DayAdjust::None => date,
             DayAdjust::Following => {
-                if cal.is_holiday(date) {
-                    cal.next_bday(date)
-                } else {
+                if cal.is_business_day(date) {
                     date
+                } else {
+                    cal.next_bday(date)
                 }
             }
             DayAdjust::Preceding => {
-                if cal.is_holiday(date) {
-                    cal.prev_bday(date)
-                } else {
+                if cal.is_business_day(date) {
                     date
+                } else {
+                    cal.prev_bday(date)
                 }
             }
             DayAdjust::Modified => {
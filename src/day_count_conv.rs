@@ -14,6 +14,10 @@ pub enum DayCountConv {
     #[serde(alias = "Act/Act")]
     #[serde(alias = "Act/Act ICMA")]
     ActActICMA,
+    #[serde(rename = "afb")]
+    #[serde(alias = "act/act afb")]
+    #[serde(alias = "Act/Act AFB")]
+    ActActAFB,
     #[serde(rename = "act/365")]
     #[serde(alias = "Act/365f")]
     Act365,
@@ -80,6 +84,7 @@ impl DayCountConv {
         match self {
             DayCountConv::Act365 => Ok(since(end, start).num_days() as f64 / 365.),
             DayCountConv::Act365l => Ok(DayCountConv::calc_act_365_leap(start, end)),
+            DayCountConv::ActActAFB => Ok(DayCountConv::calc_act_act_afb(start, end)),
             DayCountConv::Act360 => Ok(since(end, start).num_days() as f64 / 360.),
             // Check that this method is not applied to scenarios where it does not yield sensible results.
             // E.g. for one-day periods from 30th to 31st of the same month, with zero result
@@ -112,6 +117,26 @@ impl DayCountConv {
         }
     }
 
+    /// Calculate the year fraction as `year_fraction` does, optionally rounded to the given
+    /// number of decimals. Rounding removes floating point residue, e.g. of Act/Act ICMA year
+    /// fractions composed of broken periods, which would otherwise propagate into accrued
+    /// interest. Without decimals given, the year fraction is returned unchanged.
+    /// Bonds use this for their coupon periods if `BondSpec::year_fraction_decimals` is set.
+    pub fn year_fraction_rounded(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+        roll_date: Option<NaiveDate>,
+        time_period: Option<TimePeriod>,
+        decimals: Option<u32>,
+    ) -> Result<f64, DayCountConvError> {
+        let yf = self.year_fraction(start, end, roll_date, time_period)?;
+        Ok(match decimals {
+            Some(decimals) => round_to_decimals(yf, decimals),
+            None => yf,
+        })
+    }
+
     /// Implementation of act/365leap day count method
     fn calc_act_365_leap(start: NaiveDate, end: NaiveDate) -> f64 {
         let mut yf = (end.year() - start.year()) as f64;
@@ -121,6 +146,41 @@ impl DayCountConv {
             / DayCountConv::days_in_year(start.year()) as f64
     }
 
+    /// Implementation of Act/Act AFB day count method: Whole years are counted backwards
+    /// from the end date, the remaining period is divided by 366 if it contains
+    /// the 29th of February, or by 365 otherwise.
+    fn calc_act_act_afb(start: NaiveDate, end: NaiveDate) -> f64 {
+        if end < start {
+            return -DayCountConv::calc_act_act_afb(end, start);
+        }
+        let mut years = 0;
+        let mut remainder_end = end;
+        while let Some(year_back) = DayCountConv::years_before(end, years + 1) {
+            if year_back < start {
+                break;
+            }
+            years += 1;
+            remainder_end = year_back;
+        }
+        let contains_leap_day = (start.year()..=remainder_end.year()).any(|year| {
+            match NaiveDate::from_ymd_opt(year, 2, 29) {
+                Some(leap_day) => start <= leap_day && leap_day < remainder_end,
+                None => false,
+            }
+        });
+        let denominator = if contains_leap_day { 366. } else { 365. };
+        years as f64 + remainder_end.signed_duration_since(start).num_days() as f64 / denominator
+    }
+
+    /// Same day and month the given number of years before date, where the 29th of February
+    /// is moved to the 28th of February in non-leap years. Returns None if the resulting date
+    /// is out of range.
+    fn years_before(date: NaiveDate, years: i32) -> Option<NaiveDate> {
+        let year = date.year() - years;
+        NaiveDate::from_ymd_opt(year, date.month(), date.day())
+            .or_else(|| NaiveDate::from_ymd_opt(year, 2, 28).filter(|_| date.month() == 2))
+    }
+
     /// Implementation of 30/360 day count method
     fn calc_30_360(start: NaiveDate, end: NaiveDate) -> f64 {
         let yf = (end.year() - start.year()) as f64 + (end.month() - start.month()) as f64 / 12.;
@@ -202,6 +262,21 @@ impl DayCountConv {
         }
     }
 
+    /// Approximate number of calendar days corresponding to a given year fraction.
+    /// This is the inverse of `year_fraction` only for the act/x conventions; for the 30/360 style
+    /// conventions and Act/Act ICMA, it is a rough estimate (e.g. for settlement periods).
+    pub fn approx_days(&self, year_fraction: f64) -> f64 {
+        match self {
+            DayCountConv::Act365 => year_fraction * 365.,
+            DayCountConv::Act360 | DayCountConv::D30_360 | DayCountConv::D30E360 => {
+                year_fraction * 360.
+            }
+            DayCountConv::Act365l | DayCountConv::ActActICMA | DayCountConv::ActActAFB => {
+                year_fraction * 365.25
+            }
+        }
+    }
+
     /// Calculate the number of day in a given year.
     fn days_in_year(year: i32) -> u32 {
         if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) {
@@ -218,6 +293,17 @@ impl DayCountConv {
     }
 }
 
+/// Round value to the given number of decimals
+fn round_to_decimals(value: f64, decimals: u32) -> f64 {
+    let factor = 10_f64.powi(decimals as i32);
+    (value * factor).round() / factor
+}
+
+/// Approximate number of calendar days corresponding to a given year fraction, rounded to whole days
+pub fn round_to_nearest_day(year_fraction: f64, day_count: DayCountConv) -> i64 {
+    day_count.approx_days(year_fraction).round() as i64
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -298,6 +384,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn calc_year_fractions_act_act_afb() {
+        let tol = 1e-11;
+        let dcc = DayCountConv::ActActAFB;
+        let date = NaiveDate::from_ymd;
+        // Examples from ISDA's memo "EMU and market conventions" (1998)
+        let periods = [
+            (date(2003, 11, 1), date(2004, 5, 1), 182. / 366.),
+            (date(1999, 2, 1), date(1999, 7, 1), 150. / 365.),
+            (date(2000, 1, 15), date(2000, 6, 15), 152. / 366.),
+            (date(1999, 7, 30), date(2000, 1, 30), 184. / 365.),
+            (date(1999, 11, 30), date(2000, 4, 30), 152. / 366.),
+            // multi-year periods spanning a leap day: whole years are counted back
+            // from the end date, only the remaining period determines the denominator
+            (date(1994, 2, 10), date(1997, 6, 30), 3. + 140. / 365.),
+            (date(2000, 2, 1), date(2003, 1, 15), 2. + 349. / 366.),
+            (date(2000, 2, 29), date(2004, 2, 29), 4.),
+        ];
+        for (start, end, yf) in periods.iter() {
+            assert_fuzzy_eq!(
+                dcc.year_fraction(*start, *end, None, None).unwrap(),
+                *yf,
+                tol
+            );
+            assert_fuzzy_eq!(
+                dcc.year_fraction(*end, *start, None, None).unwrap(),
+                -*yf,
+                tol
+            );
+        }
+    }
+
     #[test]
     fn calc_year_fractions_30_360() {
         let tol = 1e-11;
@@ -1069,4 +1187,68 @@ mod tests {
             tol
         );
     }
+
+    #[test]
+    fn approximate_days() {
+        let date = NaiveDate::from_ymd;
+        let periods = [
+            (date(2021, 1, 1), date(2021, 1, 31)),
+            (date(2021, 2, 1), date(2021, 2, 28)),
+            (date(2020, 2, 1), date(2020, 2, 29)),
+            (date(2021, 11, 5), date(2021, 11, 8)),
+            (date(2021, 12, 1), date(2021, 12, 31)),
+        ];
+        let conventions = [
+            DayCountConv::Act365,
+            DayCountConv::Act365l,
+            DayCountConv::ActActAFB,
+            DayCountConv::Act360,
+            DayCountConv::D30_360,
+            DayCountConv::D30E360,
+        ];
+        for dcc in conventions.iter() {
+            for (start, end) in periods.iter() {
+                let yf = dcc.year_fraction(*start, *end, None, None).unwrap();
+                let days = end.signed_duration_since(*start).num_days();
+                assert!((dcc.approx_days(yf) - days as f64).abs() <= 1.);
+                assert!((round_to_nearest_day(yf, *dcc) - days).abs() <= 1);
+            }
+        }
+        assert_eq!(round_to_nearest_day(0.5, DayCountConv::Act365), 183);
+        assert_eq!(round_to_nearest_day(0.5, DayCountConv::D30_360), 180);
+        assert_eq!(round_to_nearest_day(1.0, DayCountConv::ActActICMA), 365);
+    }
+
+    #[test]
+    fn rounded_icma_year_fraction() {
+        let dcc = DayCountConv::ActActICMA;
+        let quarterly = "3M".parse::<TimePeriod>().unwrap();
+        let roll_date = Some(NaiveDate::from_ymd(2020, 1, 31));
+        let start = NaiveDate::from_ymd(2020, 12, 15);
+        let end = NaiveDate::from_ymd(2021, 4, 15);
+
+        // the broken periods at start and end leave some residue
+        let yf = dcc
+            .year_fraction(start, end, roll_date, Some(quarterly))
+            .unwrap();
+        assert_ne!(yf, 1. / 3.);
+        assert!((yf - 1. / 3.).abs() < 1e-15);
+        // rounding is off by default
+        let unrounded = dcc
+            .year_fraction_rounded(start, end, roll_date, Some(quarterly), None)
+            .unwrap();
+        assert_eq!(unrounded, yf);
+        let rounded = dcc
+            .year_fraction_rounded(start, end, roll_date, Some(quarterly), Some(10))
+            .unwrap();
+        assert_eq!(rounded, round_to_decimals(1. / 3., 10));
+
+        // a year fraction of 0.08333333329 instead of 1/12, as reported for accruals, is
+        // indistinguishable from 1/12 once both are rounded to 8 decimals
+        assert_ne!(0.08333333329, 1. / 12.);
+        assert_eq!(
+            round_to_decimals(0.08333333329, 8),
+            round_to_decimals(1. / 12., 8)
+        );
+    }
 }
@@ -0,0 +1,124 @@
+/// Fetch economic data from FRED (Federal Reserve Economic Data), see https://fred.stlouisfed.org/docs/api/fred/
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+use tokio_compat_02::FutureExt;
+
+use super::{EconomicDataError, EconomicDataProvider, EconomicIndicator};
+use crate::datatypes::date_time_helper::date_time_from_str;
+
+const FRED_URL: &str = "https://api.stlouisfed.org/fred/series/observations";
+
+#[derive(Debug, Deserialize)]
+struct FredObservation {
+    date: String,
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct FredObservations {
+    observations: Vec<FredObservation>,
+}
+
+pub struct FredProvider {
+    token: String,
+}
+
+impl FredProvider {
+    pub fn new(token: String) -> FredProvider {
+        FredProvider { token }
+    }
+
+    /// Parse the JSON response of a FRED series observations request
+    /// Missing observations, which are marked by a '.', are skipped.
+    pub fn parse_observations(
+        text: &str,
+    ) -> Result<Vec<(DateTime<Local>, f64)>, EconomicDataError> {
+        let response: FredObservations = serde_json::from_str(text)?;
+        let mut observations = Vec::new();
+        for obs in response.observations {
+            if obs.value == "." {
+                continue;
+            }
+            let time = date_time_from_str(&obs.date, "%Y-%m-%d", 0, None)?;
+            observations.push((time, obs.value.parse()?));
+        }
+        Ok(observations)
+    }
+}
+
+#[async_trait]
+impl EconomicDataProvider for FredProvider {
+    async fn fetch_indicator(
+        &self,
+        indicator: &EconomicIndicator,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> Result<Vec<(DateTime<Local>, f64)>, EconomicDataError> {
+        let mut url = format!(
+            "{}?series_id={}&api_key={}&file_type=json&observation_start={}&observation_end={}",
+            FRED_URL,
+            indicator.code,
+            self.token,
+            start.format("%Y-%m-%d"),
+            end.format("%Y-%m-%d")
+        );
+        // let FRED aggregate the observations if the frequency is supported
+        let frequency = match indicator.frequency.frequency() {
+            Ok(12) => Some("m"),
+            Ok(4) => Some("q"),
+            Ok(2) => Some("sa"),
+            Ok(1) => Some("a"),
+            _ => None,
+        };
+        if let Some(frequency) = frequency {
+            url = format!("{}&frequency={}", url, frequency);
+        }
+        let resp = reqwest::get(&url).compat().await?;
+        if !resp.status().is_success() {
+            return Err(EconomicDataError::UnexpectedError(
+                "unexpected server response".to_string(),
+            ));
+        }
+        let body = resp.text().await?;
+        Self::parse_observations(&body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datatypes::date_time_helper::make_time;
+
+    #[test]
+    fn parse_fred_observations() {
+        let response = r#"{
+            "realtime_start": "2022-11-20",
+            "realtime_end": "2022-11-20",
+            "observation_start": "2020-01-01",
+            "observation_end": "2020-04-01",
+            "units": "lin",
+            "output_type": 1,
+            "file_type": "json",
+            "order_by": "observation_date",
+            "sort_order": "asc",
+            "count": 4,
+            "offset": 0,
+            "limit": 100000,
+            "observations": [
+                {"realtime_start": "2022-11-20", "realtime_end": "2022-11-20", "date": "2020-01-01", "value": "3.5"},
+                {"realtime_start": "2022-11-20", "realtime_end": "2022-11-20", "date": "2020-02-01", "value": "3.5"},
+                {"realtime_start": "2022-11-20", "realtime_end": "2022-11-20", "date": "2020-03-01", "value": "."},
+                {"realtime_start": "2022-11-20", "realtime_end": "2022-11-20", "date": "2020-04-01", "value": "14.7"}
+            ]
+        }"#;
+        let observations = FredProvider::parse_observations(response).unwrap();
+        assert_eq!(observations.len(), 3);
+        assert_eq!(observations[0].0, make_time(2020, 1, 1, 0, 0, 0).unwrap());
+        assert_eq!(observations[0].1, 3.5);
+        assert_eq!(observations[2].0, make_time(2020, 4, 1, 0, 0, 0).unwrap());
+        assert_eq!(observations[2].1, 14.7);
+
+        assert!(FredProvider::parse_observations(r#"{"error_code": 400}"#).is_err());
+    }
+}
@@ -0,0 +1,74 @@
+//! Interface to providers of macroeconomic data, e.g. GDP growth, unemployment rates or inflation
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use thiserror::Error;
+
+use crate::time_period::TimePeriod;
+
+pub mod fred;
+
+#[derive(Error, Debug)]
+pub enum EconomicDataError {
+    #[error("Fetching economic data from provider failed")]
+    FetchFailed(#[from] reqwest::Error),
+    #[error("Parsing date failed")]
+    ParseDateFailed(#[from] chrono::format::ParseError),
+    #[error("Parsing number failed")]
+    ParseNumberFailed(#[from] std::num::ParseFloatError),
+    #[error("Conversion of date/time failed")]
+    DateTimeError(#[from] crate::datatypes::date_time_helper::DateTimeError),
+    #[error("JSON parsing error")]
+    JSONError(#[from] serde_json::Error),
+    #[error("Unexpected error: '{0}'")]
+    UnexpectedError(String),
+}
+
+/// Economic time series as identified by the data provider
+#[derive(Debug, Clone, PartialEq)]
+pub struct EconomicIndicator {
+    /// Code of the series used by the data provider
+    pub code: String,
+    /// ISO country code of the economy the indicator refers to
+    pub country: String,
+    /// Period between two observations
+    pub frequency: TimePeriod,
+}
+
+impl EconomicIndicator {
+    pub fn new(code: &str, country: &str, frequency: TimePeriod) -> EconomicIndicator {
+        EconomicIndicator {
+            code: code.to_string(),
+            country: country.to_string(),
+            frequency,
+        }
+    }
+
+    /// US consumer price index for all urban consumers
+    pub fn us_cpi() -> EconomicIndicator {
+        Self::new("CPIAUCSL", "US", TimePeriod::from_str("1M").unwrap())
+    }
+
+    /// US real gross domestic product growth rate, annualized, in percent
+    pub fn us_gdp_growth() -> EconomicIndicator {
+        Self::new("A191RL1Q225SBEA", "US", TimePeriod::from_str("3M").unwrap())
+    }
+
+    /// US unemployment rate in percent
+    pub fn us_unemployment() -> EconomicIndicator {
+        Self::new("UNRATE", "US", TimePeriod::from_str("1M").unwrap())
+    }
+}
+
+/// General interface for providers of economic data
+#[async_trait]
+pub trait EconomicDataProvider: Send + Sync {
+    /// Fetch all observations of the indicator between start and end date
+    async fn fetch_indicator(
+        &self,
+        indicator: &EconomicIndicator,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> Result<Vec<(DateTime<Local>, f64)>, EconomicDataError>;
+}
@@ -1,4 +1,6 @@
+use std::error;
 use std::f64;
+use std::fmt;
 
 use argmin::core::{CostFunction, Error, Executor};
 use argmin::solver::brent::BrentRoot;
@@ -6,7 +8,7 @@ use chrono::NaiveDate;
 
 use crate::datatypes::CashFlow;
 
-use crate::day_count_conv::DayCountConv;
+use crate::day_count_conv::{DayCountConv, DayCountConvError};
 use crate::rates::{Compounding, DiscountError, Discounter, FlatRate};
 use cal_calc::CalendarProvider;
 
@@ -39,9 +41,20 @@ pub trait FixedIncome {
         &self,
         purchase_cash_flow: &CashFlow,
         calendar_provider: &dyn CalendarProvider,
+    ) -> Result<f64, Self::Error> {
+        self.calculate_ytm_compounded(purchase_cash_flow, calendar_provider, Compounding::Annual)
+    }
+
+    /// Calculate the yield to maturity (YTM) for the given compounding method
+    fn calculate_ytm_compounded(
+        &self,
+        purchase_cash_flow: &CashFlow,
+        calendar_provider: &dyn CalendarProvider,
+        compounding: Compounding,
     ) -> Result<f64, Self::Error> {
         let cash_flows = self.rollout_cash_flows(1., calendar_provider)?;
-        let value = calculate_cash_flows_ytm(&cash_flows, purchase_cash_flow)?;
+        let value =
+            calculate_cash_flows_ytm_compounded(&cash_flows, purchase_cash_flow, compounding)?;
         Ok(value)
     }
 }
@@ -55,11 +68,21 @@ pub trait FixedIncome {
 pub fn calculate_cash_flows_ytm(
     cash_flows: &[CashFlow],
     init_cash_flow: &CashFlow,
+) -> Result<f64, DiscountError> {
+    calculate_cash_flows_ytm_compounded(cash_flows, init_cash_flow, Compounding::Annual)
+}
+
+/// Calculate the internal rate of return of a stream of cash flows like `calculate_cash_flows_ytm`,
+/// but for the given compounding method instead of annual compounding
+pub fn calculate_cash_flows_ytm_compounded(
+    cash_flows: &[CashFlow],
+    init_cash_flow: &CashFlow,
+    compounding: Compounding,
 ) -> Result<f64, DiscountError> {
     let rate = FlatRate::new(
         0.05,
         DayCountConv::Act365,
-        Compounding::Annual,
+        compounding,
         init_cash_flow.amount.currency,
     );
     let init_param = 0.5;
@@ -108,6 +131,248 @@ impl<'a> CostFunction for FlatRateDiscounter<'a> {
     }
 }
 
+/// Error related to pricing of fixed income instruments
+#[derive(Debug)]
+pub enum FixedIncomeError {
+    MissingSettlementDate,
+    NoFutureCashFlows,
+    InvalidFaceValue,
+    SolverFailed,
+    DayCountError(DayCountConvError),
+}
+
+impl fmt::Display for FixedIncomeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FixedIncomeError::MissingSettlementDate => write!(f, "settlement date is not set"),
+            FixedIncomeError::NoFutureCashFlows => {
+                write!(f, "there are no cash flows after the settlement date")
+            }
+            FixedIncomeError::InvalidFaceValue => write!(f, "face value must be positive"),
+            FixedIncomeError::SolverFailed => write!(f, "failed to calculate yield to maturity"),
+            FixedIncomeError::DayCountError(_) => {
+                write!(f, "invalid day count convention in this context")
+            }
+        }
+    }
+}
+
+impl error::Error for FixedIncomeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            FixedIncomeError::DayCountError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<DayCountConvError> for FixedIncomeError {
+    fn from(error: DayCountConvError) -> Self {
+        FixedIncomeError::DayCountError(error)
+    }
+}
+
+/// Key figures of a bond for a given price
+#[derive(Debug, Clone, Copy)]
+pub struct BondAnalysis {
+    pub price: f64,
+    pub ytm: f64,
+    pub modified_duration: f64,
+    pub convexity: f64,
+}
+
+/// Pricer of a stream of fixed cash flows, e.g. a bond's cash flows
+/// Prices are dirty prices, quoted in percent of the face value. With the default
+/// face value of 100, the price equals the present value of the cash flows.
+#[derive(Debug, Clone)]
+pub struct BondPricer {
+    cash_flows: Vec<CashFlow>,
+    day_count: DayCountConv,
+    compounding: Compounding,
+    settlement: Option<NaiveDate>,
+    face_value: f64,
+}
+
+impl BondPricer {
+    /// Create new pricer with day count convention act/365, annual compounding
+    /// and a face value of 100
+    pub fn new(cash_flows: Vec<CashFlow>) -> BondPricer {
+        BondPricer {
+            cash_flows,
+            day_count: DayCountConv::Act365,
+            compounding: Compounding::Annual,
+            settlement: None,
+            face_value: 100.,
+        }
+    }
+
+    pub fn day_count(mut self, conv: DayCountConv) -> BondPricer {
+        self.day_count = conv;
+        self
+    }
+
+    pub fn compounding(mut self, compounding: Compounding) -> BondPricer {
+        self.compounding = compounding;
+        self
+    }
+
+    pub fn settlement(mut self, date: NaiveDate) -> BondPricer {
+        self.settlement = Some(date);
+        self
+    }
+
+    pub fn face_value(mut self, face_value: f64) -> BondPricer {
+        self.face_value = face_value;
+        self
+    }
+
+    /// Calculate the price for the given yield
+    pub fn price(&self, yield_rate: f64) -> Result<f64, FixedIncomeError> {
+        let (price, _, _) = self.price_and_derivatives(yield_rate)?;
+        Ok(price)
+    }
+
+    /// Calculate the yield to maturity for the given price
+    pub fn yield_to_maturity(&self, price: f64) -> Result<f64, FixedIncomeError> {
+        // Fail early on invalid input instead of within the solver
+        self.price_and_derivatives(0.)?;
+        let solver = BrentRoot::new(0., 0.5, 1e-11);
+        let func = PriceDifference {
+            pricer: self,
+            price,
+        };
+        let res = Executor::new(func, solver)
+            .configure(|state| state.max_iters(100).param(0.05))
+            .run();
+        match res {
+            Ok(mut val) => val.state.take_param().ok_or(FixedIncomeError::SolverFailed),
+            Err(_) => Err(FixedIncomeError::SolverFailed),
+        }
+    }
+
+    /// Calculate the modified duration, i.e. the relative price change per change of yield
+    pub fn modified_duration(&self, yield_rate: f64) -> Result<f64, FixedIncomeError> {
+        let (price, first_derivative, _) = self.price_and_derivatives(yield_rate)?;
+        Ok(-first_derivative / price)
+    }
+
+    /// Calculate the convexity, i.e. the relative second derivative of the price with respect to the yield
+    pub fn convexity(&self, yield_rate: f64) -> Result<f64, FixedIncomeError> {
+        let (price, _, second_derivative) = self.price_and_derivatives(yield_rate)?;
+        Ok(second_derivative / price)
+    }
+
+    /// Calculate yield to maturity, modified duration and convexity for the given price
+    pub fn full_analysis(&self, price: f64) -> Result<BondAnalysis, FixedIncomeError> {
+        let ytm = self.yield_to_maturity(price)?;
+        Ok(BondAnalysis {
+            price,
+            ytm,
+            modified_duration: self.modified_duration(ytm)?,
+            convexity: self.convexity(ytm)?,
+        })
+    }
+
+    /// Calculate price and its first and second derivative with respect to the yield
+    fn price_and_derivatives(&self, yield_rate: f64) -> Result<(f64, f64, f64), FixedIncomeError> {
+        let settlement = self
+            .settlement
+            .ok_or(FixedIncomeError::MissingSettlementDate)?;
+        if self.face_value <= 0. {
+            return Err(FixedIncomeError::InvalidFaceValue);
+        }
+        let cash_flows = get_cash_flows_after(&self.cash_flows, settlement);
+        if cash_flows.is_empty() {
+            return Err(FixedIncomeError::NoFutureCashFlows);
+        }
+        let mut value = (0., 0., 0.);
+        for cf in &cash_flows {
+            let t = self
+                .day_count
+                .year_fraction(settlement, cf.date, None, None)?;
+            let (df, d1, d2) = discount_factor_derivatives(self.compounding, yield_rate, t);
+            value.0 += cf.amount.amount * df;
+            value.1 += cf.amount.amount * d1;
+            value.2 += cf.amount.amount * d2;
+        }
+        let scale = 100. / self.face_value;
+        Ok((value.0 * scale, value.1 * scale, value.2 * scale))
+    }
+}
+
+/// Calculate the present value of the cash flows of many bonds concurrently
+/// Each bond is given by its cash flows, yield, day count convention and settlement date;
+/// the result contains one price (see `BondPricer`) per bond in the same order.
+pub fn parallel_discount_cash_flows(
+    bond_specs: &[(Vec<CashFlow>, f64, DayCountConv, NaiveDate)],
+) -> Vec<Result<f64, FixedIncomeError>> {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = bond_specs.len().div_ceil(threads).max(1);
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = bond_specs
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|(cash_flows, yield_rate, day_count, settlement)| {
+                            BondPricer::new(cash_flows.clone())
+                                .day_count(*day_count)
+                                .settlement(*settlement)
+                                .price(*yield_rate)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+/// Discount factor for year fraction `t` and its first and second derivative with respect to the rate
+fn discount_factor_derivatives(compounding: Compounding, rate: f64, t: f64) -> (f64, f64, f64) {
+    let periodic = |n: f64| {
+        let base = 1. + rate / n;
+        let df = base.powf(-n * t);
+        (df, -t * df / base, t * (t + 1. / n) * df / (base * base))
+    };
+    match compounding {
+        Compounding::Simple => {
+            let df = 1. / (1. + rate * t);
+            (df, -t * df * df, 2. * t * t * df * df * df)
+        }
+        Compounding::Annual => periodic(1.),
+        Compounding::SemiAnnual => periodic(2.),
+        Compounding::Quarterly => periodic(4.),
+        Compounding::Monthly => periodic(12.),
+        Compounding::Periodic(n) => periodic(f64::from(n.max(1))),
+        Compounding::Continuous => {
+            let df = (-rate * t).exp();
+            (df, -t * df, t * t * df)
+        }
+    }
+}
+
+/// Difference between the price of a bond for a given yield and a given price
+#[derive(Clone)]
+struct PriceDifference<'a> {
+    pricer: &'a BondPricer,
+    price: f64,
+}
+
+impl<'a> CostFunction for PriceDifference<'a> {
+    // one dimensional problem, no vector needed
+    type Param = f64;
+    type Output = f64;
+
+    fn cost(&self, p: &Self::Param) -> Result<Self::Output, Error> {
+        Ok(self.pricer.price(*p)? - self.price)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use chrono::{Local, TimeZone};
@@ -236,4 +501,113 @@ mod tests {
             .unwrap();
         assert_fuzzy_eq!(tmp.amount, (7500.0 + 100.0 * fx_rate).round(), tol);
     }
+
+    #[test]
+    fn bond_pricer() {
+        let tol = 1e-9;
+        let curr = Currency::from_str("EUR").unwrap();
+        // three year bond with 5% annual coupon, settlement at issue date
+        let cash_flows = vec![
+            CashFlow::new(5., curr, NaiveDate::from_ymd(2021, 10, 1)),
+            CashFlow::new(5., curr, NaiveDate::from_ymd(2022, 10, 1)),
+            CashFlow::new(105., curr, NaiveDate::from_ymd(2023, 10, 1)),
+        ];
+        let pricer = BondPricer::new(cash_flows.clone())
+            .day_count(DayCountConv::D30_360)
+            .settlement(NaiveDate::from_ymd(2020, 10, 1));
+
+        // bond priced at par for yield equal to coupon rate
+        assert_fuzzy_eq!(pricer.price(0.05).unwrap(), 100., tol);
+        let price = pricer.price(0.04).unwrap();
+        let reference = 5. / 1.04 + 5. / 1.04_f64.powi(2) + 105. / 1.04_f64.powi(3);
+        assert_fuzzy_eq!(price, reference, tol);
+        assert_fuzzy_eq!(pricer.yield_to_maturity(price).unwrap(), 0.04, tol);
+
+        // duration and convexity consistent with finite differences of the price
+        let h = 1e-5;
+        let duration = pricer.modified_duration(0.04).unwrap();
+        let price_up = pricer.price(0.04 + h).unwrap();
+        let price_down = pricer.price(0.04 - h).unwrap();
+        assert_fuzzy_eq!(duration, -(price_up - price_down) / (2. * h * price), 1e-6);
+        let convexity = pricer.convexity(0.04).unwrap();
+        assert_fuzzy_eq!(
+            convexity,
+            (price_up + price_down - 2. * price) / (h * h * price),
+            1e-3
+        );
+
+        let analysis = pricer.full_analysis(price).unwrap();
+        assert_fuzzy_eq!(analysis.price, price, tol);
+        assert_fuzzy_eq!(analysis.ytm, 0.04, tol);
+        assert_fuzzy_eq!(analysis.modified_duration, duration, 1e-7);
+        assert_fuzzy_eq!(analysis.convexity, convexity, 1e-6);
+
+        // price in percent of face value
+        let pricer = pricer.face_value(1000.);
+        assert_fuzzy_eq!(pricer.price(0.04).unwrap(), price / 10., tol);
+
+        let pricer = BondPricer::new(cash_flows);
+        assert!(matches!(
+            pricer.price(0.04),
+            Err(FixedIncomeError::MissingSettlementDate)
+        ));
+        let pricer = pricer.settlement(NaiveDate::from_ymd(2024, 1, 1));
+        assert!(matches!(
+            pricer.price(0.04),
+            Err(FixedIncomeError::NoFutureCashFlows)
+        ));
+    }
+
+    #[test]
+    fn parallel_pricing_of_bonds() {
+        use rand::Rng;
+
+        let curr = Currency::from_str("EUR").unwrap();
+        let settlement = NaiveDate::from_ymd(2021, 1, 15);
+        let day_counts = [
+            DayCountConv::Act365,
+            DayCountConv::Act360,
+            DayCountConv::D30_360,
+        ];
+        let mut rng = rand::thread_rng();
+        let mut bond_specs: Vec<_> = (0..1000)
+            .map(|_| {
+                let coupon = rng.gen_range(0.0..10.0);
+                let years = rng.gen_range(1..30);
+                let mut cash_flows: Vec<CashFlow> = (1..=years)
+                    .map(|year| {
+                        CashFlow::new(coupon, curr, NaiveDate::from_ymd(2021 + year, 6, 30))
+                    })
+                    .collect();
+                cash_flows.push(CashFlow::new(
+                    100.,
+                    curr,
+                    NaiveDate::from_ymd(2021 + years, 6, 30),
+                ));
+                let day_count = day_counts[rng.gen_range(0..day_counts.len())];
+                (cash_flows, rng.gen_range(0.0..0.1), day_count, settlement)
+            })
+            .collect();
+        // a bond without future cash flows yields an error at the same position
+        bond_specs[500].3 = NaiveDate::from_ymd(2080, 1, 1);
+
+        let prices = parallel_discount_cash_flows(&bond_specs);
+        assert_eq!(prices.len(), bond_specs.len());
+        for ((cash_flows, yield_rate, day_count, settlement), price) in
+            bond_specs.iter().zip(prices.iter())
+        {
+            let sequential = BondPricer::new(cash_flows.clone())
+                .day_count(*day_count)
+                .settlement(*settlement)
+                .price(*yield_rate);
+            match (sequential, price) {
+                (Ok(expected), Ok(price)) => assert_fuzzy_eq!(*price, expected, 1e-10),
+                (Err(_), Err(err)) => {
+                    assert!(matches!(err, FixedIncomeError::NoFutureCashFlows))
+                }
+                _ => panic!("parallel and sequential pricing differ"),
+            }
+        }
+        assert!(prices[500].is_err());
+    }
 }
@@ -4,12 +4,13 @@ use std::sync::Arc;
 use std::sync::RwLock;
 
 use async_trait::async_trait;
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Local, NaiveDate};
 
 use crate::datatypes::{
-    Asset, Currency, CurrencyConverter, CurrencyError, DataError, DataItem, Quote, QuoteHandler,
-    Ticker,
+    Asset, CashAmount, Currency, CurrencyConverter, CurrencyError, DataError, DataItem, Quote,
+    QuoteHandler, Ticker,
 };
+use crate::day_count_conv::{DayCountConv, DayCountConvError};
 
 /// Insert fx rate quote in database including the inverse quote
 /// fx_rate is the price of one unit of base currency in terms of the quote currency.
@@ -27,7 +28,7 @@ pub async fn insert_fx_quote(
     };
     let currency_pair = format!("{base_currency}/{quote_currency}");
     let ticker_id = quotes
-        .insert_ticker(&Ticker {
+        .insert_if_new_ticker(&Ticker {
             id: None,
             name: currency_pair,
             asset: base_id,
@@ -37,6 +38,7 @@ pub async fn insert_fx_quote(
             factor: 1.0,
             tz: None,
             cal: None,
+            active: true,
         })
         .await?;
     quotes
@@ -46,6 +48,7 @@ pub async fn insert_fx_quote(
             price: fx_rate,
             time,
             volume: None,
+            stale: false,
         })
         .await?;
     // Insert inverse fx quote
@@ -58,7 +61,7 @@ pub async fn insert_fx_quote(
     };
     let currency_pair = format!("{quote_currency}/{base_currency}");
     let ticker_id = quotes
-        .insert_ticker(&Ticker {
+        .insert_if_new_ticker(&Ticker {
             id: None,
             name: currency_pair,
             asset: quote_id,
@@ -68,6 +71,7 @@ pub async fn insert_fx_quote(
             factor: 1.0,
             tz: None,
             cal: None,
+            active: true,
         })
         .await?;
     quotes
@@ -77,11 +81,29 @@ pub async fn insert_fx_quote(
             price: 1.0 / fx_rate,
             time,
             volume: None,
+            stale: false,
         })
         .await?;
     Ok(())
 }
 
+/// Simple interest accrued on a cash balance at the annual `rate` between `start` and `end`,
+/// e.g. for money market accounts or fx forward points, where typically Act/360 is used.
+/// Fails for day count conventions which require a coupon period, like Act/Act ICMA.
+pub fn accrue_cash(
+    balance: CashAmount,
+    rate: f64,
+    start: NaiveDate,
+    end: NaiveDate,
+    dcc: DayCountConv,
+) -> Result<CashAmount, DayCountConvError> {
+    let year_fraction = dcc.year_fraction(start, end, None, None)?;
+    Ok(CashAmount {
+        amount: balance.amount * rate * year_fraction,
+        currency: balance.currency,
+    })
+}
+
 /// Currency converter based of stored list of exchange rates, ignoring dates
 pub struct SimpleCurrencyConverter {
     fx_rates: RwLock<HashMap<String, f64>>,
@@ -151,7 +173,7 @@ mod tests {
     use chrono::Local;
 
     use crate::datatypes::CurrencyISOCode;
-    use crate::market::{CachePolicy, Market};
+    use crate::market::Market;
     use crate::postgres::PostgresDB;
 
     async fn prepare_db(db: Arc<dyn QuoteHandler + Send + Sync>) {
@@ -187,4 +209,24 @@ mod tests {
         let fx = market.fx_rate(usd, eur, time).await.unwrap();
         assert_fuzzy_eq!(fx, 0.9, tol);
     }
+
+    #[test]
+    fn test_accrue_cash() {
+        let tol = 1.0e-10_f64;
+        let eur = Currency::new(None, CurrencyISOCode::new("EUR").unwrap(), None);
+        let balance = CashAmount {
+            amount: 1_000_000.0,
+            currency: eur,
+        };
+        let start = NaiveDate::from_ymd(2021, 1, 15);
+        let end = NaiveDate::from_ymd(2021, 4, 15);
+        let interest = accrue_cash(balance, 0.02, start, end, DayCountConv::Act360).unwrap();
+        // 90 days of interest at 2% p.a.
+        assert_fuzzy_eq!(interest.amount, 5000.0, tol);
+        assert_eq!(interest.currency, eur);
+
+        let interest = accrue_cash(balance, 0.02, start, end, DayCountConv::Act365).unwrap();
+        assert_fuzzy_eq!(interest.amount, 20000.0 * 90.0 / 365.0, tol);
+        assert!(accrue_cash(balance, 0.02, start, end, DayCountConv::ActActICMA).is_err());
+    }
 }
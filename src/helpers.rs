@@ -1,4 +1,29 @@
-///! Useful helper functions that do not belong to any other module
+//! Useful helper functions that do not belong to any other module
+
+use crate::datatypes::{CashAmount, Currency, CurrencyISOCode};
+use thiserror::Error;
+
+/// Error related to parsing amounts
+#[derive(Error, Debug, PartialEq)]
+pub enum ParseError {
+    #[error("no currency found in amount '{0}'")]
+    MissingCurrency(String),
+    #[error("unknown currency '{0}'")]
+    UnknownCurrency(String),
+    #[error("invalid number '{0}'")]
+    InvalidNumber(String),
+    #[error("ambiguous decimal separator in number '{0}'")]
+    AmbiguousNumber(String),
+}
+
+/// Locales supported for formatting of amounts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    /// US English, e.g. `$1,234.56`
+    EnUs,
+    /// German, e.g. `1.234,56 €`
+    DeDe,
+}
 
 /// Returns true if some optional String argument is not None and  the value equals a given str reference
 pub fn some_equal(opt: &Option<String>, s: &str) -> bool {
@@ -7,3 +32,207 @@ pub fn some_equal(opt: &Option<String>, s: &str) -> bool {
         Some(opt_s) => opt_s == s,
     }
 }
+
+/// Format a number rounded to the given number of decimal digits, with the integral part
+/// separated into groups of thousands, e.g. `1,234.56`
+pub fn format_number(
+    amount: f64,
+    digits: i32,
+    group_separator: char,
+    decimal_separator: char,
+) -> String {
+    let formatted = format!("{:.*}", digits.max(0) as usize, amount.abs());
+    let (integral, fraction) = match formatted.split_once('.') {
+        Some((integral, fraction)) => (integral, Some(fraction)),
+        None => (formatted.as_str(), None),
+    };
+    let mut result = String::new();
+    if amount < 0.0 && formatted.chars().any(|c| c.is_ascii_digit() && c != '0') {
+        result.push('-');
+    }
+    for (i, c) in integral.chars().enumerate() {
+        if i > 0 && (integral.len() - i) % 3 == 0 {
+            result.push(group_separator);
+        }
+        result.push(c);
+    }
+    if let Some(fraction) = fraction {
+        result.push(decimal_separator);
+        result.push_str(fraction);
+    }
+    result
+}
+
+/// Format amount in the given currency according to the conventions of the locale
+/// regarding digit grouping, decimal separator and placement of the currency symbol.
+/// Currencies without symbol are labeled with their ISO code.
+pub fn format_amount_locale(amount: f64, currency: &Currency, locale: Locale) -> String {
+    match locale {
+        Locale::EnUs => currency.format_amount(amount),
+        Locale::DeDe => {
+            let number = format_number(amount, currency.rounding_digits(), '.', ',');
+            match currency.iso_code.symbol() {
+                Some(symbol) => format!("{} {}", number, symbol),
+                None => format!("{} {}", number, currency.iso_code),
+            }
+        }
+    }
+}
+
+/// Parse an amount with the currency given as prefix or suffix, either by ISO code or symbol,
+/// e.g. `€1.234,56` or `USD 1,234.56`. Both `.` and `,` are accepted as decimal separator;
+/// numbers with a single separator followed by exactly three digits (e.g. `1,234`) are
+/// rejected as ambiguous.
+pub fn parse_amount(s: &str) -> Result<CashAmount, ParseError> {
+    let s = s.trim();
+    let (negative, unsigned) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest.trim_start()),
+        None => (false, s),
+    };
+    let is_number_char = |c: char| c.is_ascii_digit() || c == '.' || c == ',';
+    let number_start = unsigned
+        .find(is_number_char)
+        .ok_or_else(|| ParseError::InvalidNumber(s.to_string()))?;
+    let number_end = unsigned
+        .rfind(is_number_char)
+        .map(|pos| pos + 1)
+        .unwrap_or(number_start);
+    let prefix = unsigned[..number_start].trim();
+    let suffix = unsigned[number_end..].trim();
+    let currency_text = match (prefix.is_empty(), suffix.is_empty()) {
+        (false, true) => prefix,
+        (true, false) => suffix,
+        _ => return Err(ParseError::MissingCurrency(s.to_string())),
+    };
+    let iso_code = CurrencyISOCode::from_symbol(currency_text)
+        .or_else(|| CurrencyISOCode::new(currency_text).ok())
+        .ok_or_else(|| ParseError::UnknownCurrency(currency_text.to_string()))?;
+    let amount = parse_number(&unsigned[number_start..number_end])?;
+    Ok(CashAmount {
+        amount: if negative { -amount } else { amount },
+        currency: Currency::new(None, iso_code, None),
+    })
+}
+
+/// Parse a number with `.` or `,` as decimal separator and the respective other character
+/// as optional separator of groups of thousands
+fn parse_number(number: &str) -> Result<f64, ParseError> {
+    let invalid = || ParseError::InvalidNumber(number.to_string());
+    let last_dot = number.rfind('.');
+    let last_comma = number.rfind(',');
+    let decimal_separator = match (last_dot, last_comma) {
+        (Some(dot), Some(comma)) => Some(if dot > comma { '.' } else { ',' }),
+        (Some(pos), None) | (None, Some(pos)) => {
+            let separator = number[pos..].chars().next().unwrap();
+            if number.matches(separator).count() > 1 {
+                // repeated separator can only separate groups of thousands
+                None
+            } else if number.len() - pos - 1 == 3 {
+                return Err(ParseError::AmbiguousNumber(number.to_string()));
+            } else {
+                Some(separator)
+            }
+        }
+        (None, None) => None,
+    };
+    let (integral, fraction) = match decimal_separator {
+        Some(separator) => number.rsplit_once(separator).ok_or_else(invalid)?,
+        None => (number, ""),
+    };
+    if fraction.contains(|c: char| !c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let groups: Vec<&str> = integral.split(['.', ',']).collect();
+    let valid_groups = groups.iter().enumerate().all(|(i, group)| {
+        if i == 0 {
+            !group.is_empty() && (groups.len() == 1 || group.len() <= 3)
+        } else {
+            group.len() == 3
+        }
+    });
+    if !valid_groups {
+        return Err(invalid());
+    }
+    format!("{}.{}", groups.concat(), fraction)
+        .trim_end_matches('.')
+        .parse()
+        .map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn format_numbers() {
+        assert_eq!(format_number(1234.56, 2, ',', '.'), "1,234.56");
+        assert_eq!(format_number(1234567.891, 2, '.', ','), "1.234.567,89");
+        assert_eq!(format_number(-999.999, 2, ',', '.'), "-1,000.00");
+        assert_eq!(format_number(123456.7, 0, ',', '.'), "123,457");
+        assert_eq!(format_number(-0.001, 2, ',', '.'), "0.00");
+    }
+
+    #[test]
+    fn format_amounts_by_locale() {
+        let eur = Currency::from_str("EUR").unwrap();
+        assert_eq!(
+            format_amount_locale(1234.56, &eur, Locale::DeDe),
+            "1.234,56 €"
+        );
+        assert_eq!(
+            format_amount_locale(1234.56, &eur, Locale::EnUs),
+            "€1,234.56"
+        );
+        let usd = Currency::from_str("USD").unwrap();
+        assert_eq!(
+            format_amount_locale(-1234.56, &usd, Locale::DeDe),
+            "-1.234,56 $"
+        );
+        assert_eq!(
+            format_amount_locale(-1234.56, &usd, Locale::EnUs),
+            "-$1,234.56"
+        );
+        let chf = Currency::from_str("CHF").unwrap();
+        assert_eq!(
+            format_amount_locale(1234.56, &chf, Locale::DeDe),
+            "1.234,56 CHF"
+        );
+    }
+
+    #[test]
+    fn parse_amounts() {
+        let tol = 1e-10;
+        let amount = parse_amount("€1.234,56").unwrap();
+        assert_eq!(amount.currency.to_string(), "EUR");
+        assert_fuzzy_eq!(amount.amount, 1234.56, tol);
+        let amount = parse_amount("1.234,56 €").unwrap();
+        assert_fuzzy_eq!(amount.amount, 1234.56, tol);
+
+        let amount = parse_amount("USD 1,234.56").unwrap();
+        assert_eq!(amount.currency.to_string(), "USD");
+        assert_fuzzy_eq!(amount.amount, 1234.56, tol);
+        let amount = parse_amount("-$1,234,567").unwrap();
+        assert_eq!(amount.currency.to_string(), "USD");
+        assert_fuzzy_eq!(amount.amount, -1234567.0, tol);
+        let amount = parse_amount("JPY 1234").unwrap();
+        assert_fuzzy_eq!(amount.amount, 1234.0, tol);
+
+        assert_eq!(
+            parse_amount("€1,234"),
+            Err(ParseError::AmbiguousNumber("1,234".to_string()))
+        );
+        assert!(matches!(
+            parse_amount("1,234.56"),
+            Err(ParseError::MissingCurrency(_))
+        ));
+        assert!(matches!(
+            parse_amount("XX 1,234.56"),
+            Err(ParseError::UnknownCurrency(_))
+        ));
+        assert!(matches!(
+            parse_amount("EUR 12,34,56.7"),
+            Err(ParseError::InvalidNumber(_))
+        ));
+    }
+}
@@ -0,0 +1,256 @@
+//! Parsers for transaction exports of specific brokers
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use chrono::NaiveDate;
+
+use super::{parse_german_number, ImportError, ImportTransaction};
+use crate::datatypes::{CashFlow, Currency, Stock, Transaction, TransactionType};
+
+/// Parse the transaction export of comdirect's securities account
+/// The file is semicolon separated with German number and date formatting. Any lines before
+/// the header line (starting with "Buchungstag") are skipped. The following columns are used:
+/// "Buchungstag", "Vorgang" (one of "Kauf", "Verkauf", "Dividende", "Ertrag" or "Zinsen"),
+/// "Bezeichnung", "WKN", "ISIN", "Stück", "Währung", "Betrag" (gross amount of the booking),
+/// and optionally "Provision" and "Steuern", which are imported as related fee and tax
+/// transactions.
+pub fn parse_comdirect_csv(text: &str) -> Result<Vec<ImportTransaction>, ImportError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(b';')
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    let mut columns: Option<BTreeMap<String, usize>> = None;
+    let mut imports = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let columns = match &columns {
+            Some(columns) => columns,
+            None => {
+                if record.get(0) == Some("Buchungstag") {
+                    columns = Some(
+                        record
+                            .iter()
+                            .enumerate()
+                            .map(|(i, name)| (name.to_string(), i))
+                            .collect(),
+                    );
+                }
+                continue;
+            }
+        };
+        if record.iter().all(|field| field.trim().is_empty()) {
+            continue;
+        }
+        let field = |name: &str| -> Result<&str, ImportError> {
+            columns
+                .get(name)
+                .and_then(|i| record.get(*i))
+                .map(|field| field.trim())
+                .ok_or_else(|| ImportError::MissingField(name.to_string()))
+        };
+        let optional_amount = |name: &str| -> Result<f64, ImportError> {
+            match field(name) {
+                Ok(value) if !value.is_empty() => parse_german_number(value),
+                _ => Ok(0.0),
+            }
+        };
+
+        let date = NaiveDate::parse_from_str(field("Buchungstag")?, "%d.%m.%Y")?;
+        let currency = Currency::from_str(field("Währung")?)?;
+        let amount = parse_german_number(field("Betrag")?)?.abs();
+        let (transaction_type, amount) = match field("Vorgang")? {
+            "Kauf" => (
+                TransactionType::Asset {
+                    asset_id: 0,
+                    position: parse_german_number(field("Stück")?)?.abs(),
+                },
+                -amount,
+            ),
+            "Verkauf" => (
+                TransactionType::Asset {
+                    asset_id: 0,
+                    position: -parse_german_number(field("Stück")?)?.abs(),
+                },
+                amount,
+            ),
+            "Dividende" | "Ertrag" => (TransactionType::Dividend { asset_id: 0 }, amount),
+            "Zinsen" => (TransactionType::Interest { asset_id: 0 }, amount),
+            other => return Err(ImportError::UnknownTransactionType(other.to_string())),
+        };
+        let non_empty = |value: &str| {
+            if value.is_empty() {
+                None
+            } else {
+                Some(value.to_string())
+            }
+        };
+        let asset = Some(Stock::new(
+            None,
+            field("Bezeichnung")?.to_string(),
+            non_empty(field("ISIN")?),
+            non_empty(field("WKN")?),
+            None,
+        ));
+
+        let mut related = Vec::new();
+        let fee = optional_amount("Provision")?;
+        if fee != 0.0 {
+            related.push(Transaction {
+                id: None,
+                transaction_type: TransactionType::Fee {
+                    transaction_ref: None,
+                },
+                cash_flow: CashFlow::new(-fee.abs(), currency, date),
+                note: None,
+                time_stamp: None,
+            });
+        }
+        // positive taxes are paid, negative taxes are refunded
+        let tax = optional_amount("Steuern")?;
+        if tax != 0.0 {
+            related.push(Transaction {
+                id: None,
+                transaction_type: TransactionType::Tax {
+                    transaction_ref: None,
+                },
+                cash_flow: CashFlow::new(-tax, currency, date),
+                note: None,
+                time_stamp: None,
+            });
+        }
+        imports.push(ImportTransaction {
+            asset,
+            transaction: Transaction {
+                id: None,
+                transaction_type,
+                cash_flow: CashFlow::new(amount, currency, date),
+                note: None,
+                time_stamp: None,
+            },
+            related,
+        });
+    }
+    Ok(imports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datatypes::TransactionHandler;
+    use crate::import::store_transactions;
+    use crate::postgres::PostgresDB;
+
+    const COMDIRECT_STATEMENT: &str = r#""Umsätze Depot 123456789"
+
+"Buchungstag";"Vorgang";"Bezeichnung";"WKN";"ISIN";"Stück";"Währung";"Betrag";"Provision";"Steuern"
+"04.01.2021";"Kauf";"Apple Inc.";"865985";"US0378331005";"10";"EUR";"1.052,40";"9,90";""
+"15.02.2021";"Dividende";"Apple Inc.";"865985";"US0378331005";"10";"EUR";"1,68";"";"0,25"
+"01.06.2021";"Verkauf";"Apple Inc.";"865985";"US0378331005";"4";"EUR";"412,80";"9,90";"-1,50"
+"#;
+
+    #[test]
+    fn parse_comdirect_statement() {
+        let tol = 1e-11;
+        let imports = parse_comdirect_csv(COMDIRECT_STATEMENT).unwrap();
+        assert_eq!(imports.len(), 3);
+
+        let buy = &imports[0];
+        let stock = buy.asset.as_ref().unwrap();
+        assert_eq!(stock.name, "Apple Inc.");
+        assert_eq!(stock.isin.as_deref(), Some("US0378331005"));
+        assert_eq!(stock.wkn.as_deref(), Some("865985"));
+        assert_eq!(
+            buy.transaction.cash_flow.date,
+            NaiveDate::from_ymd(2021, 1, 4)
+        );
+        assert_fuzzy_eq!(buy.transaction.cash_flow.amount.amount, -1052.40, tol);
+        assert_eq!(buy.transaction.cash_flow.amount.currency.to_string(), "EUR");
+        assert!(matches!(
+            buy.transaction.transaction_type,
+            TransactionType::Asset { position, .. } if position == 10.0
+        ));
+        assert_eq!(buy.related.len(), 1);
+        assert!(matches!(
+            buy.related[0].transaction_type,
+            TransactionType::Fee { .. }
+        ));
+        assert_fuzzy_eq!(buy.related[0].cash_flow.amount.amount, -9.90, tol);
+
+        let dividend = &imports[1];
+        assert!(matches!(
+            dividend.transaction.transaction_type,
+            TransactionType::Dividend { .. }
+        ));
+        assert_fuzzy_eq!(dividend.transaction.cash_flow.amount.amount, 1.68, tol);
+        assert_eq!(dividend.related.len(), 1);
+        assert_fuzzy_eq!(dividend.related[0].cash_flow.amount.amount, -0.25, tol);
+
+        let sell = &imports[2];
+        assert!(matches!(
+            sell.transaction.transaction_type,
+            TransactionType::Asset { position, .. } if position == -4.0
+        ));
+        assert_fuzzy_eq!(sell.transaction.cash_flow.amount.amount, 412.80, tol);
+        assert_eq!(sell.related.len(), 2);
+        assert_fuzzy_eq!(sell.related[1].cash_flow.amount.amount, 1.50, tol);
+
+        let invalid = COMDIRECT_STATEMENT.replace("Verkauf", "Umbuchung");
+        assert!(matches!(
+            parse_comdirect_csv(&invalid),
+            Err(ImportError::UnknownTransactionType(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn store_comdirect_statement() {
+        let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
+        assert!(
+            db_url.is_ok(),
+            "environment variable $FINQL_TEST_DATABASE_URL is not set"
+        );
+        let db = PostgresDB::new(&db_url.unwrap()).await.unwrap();
+        db.clean().await.unwrap();
+
+        let imports = parse_comdirect_csv(COMDIRECT_STATEMENT).unwrap();
+        let summary = store_transactions(&imports, &db).await.unwrap();
+        assert_eq!(summary.inserted, 3);
+        assert_eq!(summary.skipped, 0);
+        let transactions = db.get_all_transactions().await.unwrap();
+        assert_eq!(transactions.len(), 7);
+        // all transactions refer to the same, newly created asset
+        let asset_ids: Vec<i32> = transactions
+            .iter()
+            .filter_map(|t| match t.transaction_type {
+                TransactionType::Asset { asset_id, .. } => Some(asset_id),
+                TransactionType::Dividend { asset_id } => Some(asset_id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(asset_ids.len(), 3);
+        assert!(asset_ids.iter().all(|id| *id == asset_ids[0]));
+        let buy = transactions
+            .iter()
+            .find(|t| {
+                matches!(
+                    t.transaction_type,
+                    TransactionType::Asset { position, .. } if position > 0.0
+                )
+            })
+            .unwrap();
+        let fee = transactions
+            .iter()
+            .find(|t| matches!(t.transaction_type, TransactionType::Fee { .. }))
+            .unwrap();
+        assert!(matches!(
+            fee.transaction_type,
+            TransactionType::Fee { transaction_ref } if transaction_ref == buy.id
+        ));
+
+        // importing the same statement again does not book any transaction twice
+        let summary = store_transactions(&imports, &db).await.unwrap();
+        assert_eq!(summary.inserted, 0);
+        assert_eq!(summary.skipped, 3);
+        assert_eq!(db.get_all_transactions().await.unwrap().len(), 7);
+    }
+}
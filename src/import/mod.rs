@@ -0,0 +1,118 @@
+//! Import of transactions from external sources, e.g. broker statements
+//! Parsers produce `ImportTransaction`s, whose assets and currencies are resolved
+//! against the database when storing them with `store_transactions`.
+
+use thiserror::Error;
+
+use crate::datatypes::{
+    Asset, CurrencyError, DataError, Stock, Transaction, TransactionHandler, TransactionType,
+};
+
+pub mod brokers;
+
+/// Error related to the import of transactions
+#[derive(Error, Debug)]
+pub enum ImportError {
+    #[error("Reading CSV data failed")]
+    CsvError(#[from] csv::Error),
+    #[error("Parsing date failed")]
+    ParseDateFailed(#[from] chrono::format::ParseError),
+    #[error("Invalid number '{0}'")]
+    InvalidNumber(String),
+    #[error("Invalid currency")]
+    InvalidCurrency(#[from] CurrencyError),
+    #[error("Missing field '{0}'")]
+    MissingField(String),
+    #[error("Unknown transaction type '{0}'")]
+    UnknownTransactionType(String),
+    #[error("Storing transactions failed")]
+    DataError(#[from] DataError),
+}
+
+/// Transaction read from an external source
+#[derive(Debug, Clone)]
+pub struct ImportTransaction {
+    /// Asset the transaction refers to, if any. The asset id of the transaction
+    /// is set when the transaction is stored.
+    pub asset: Option<Stock>,
+    pub transaction: Transaction,
+    /// Fees and taxes related to the transaction
+    pub related: Vec<Transaction>,
+}
+
+/// Number of imported transactions which have been inserted or skipped as duplicates
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ImportSummary {
+    pub inserted: usize,
+    pub skipped: usize,
+}
+
+/// Asset id of transactions which refer to an asset directly
+fn asset_id(transaction_type: &TransactionType) -> Option<i32> {
+    match transaction_type {
+        TransactionType::Asset { asset_id, .. }
+        | TransactionType::Dividend { asset_id }
+        | TransactionType::Interest { asset_id } => Some(*asset_id),
+        _ => None,
+    }
+}
+
+/// Check whether two transactions agree in date, type, asset and amount
+fn is_duplicate(transaction: &Transaction, other: &Transaction) -> bool {
+    transaction.cash_flow.date == other.cash_flow.date
+        && transaction.transaction_type.code() == other.transaction_type.code()
+        && asset_id(&transaction.transaction_type) == asset_id(&other.transaction_type)
+        && transaction.cash_flow.amount.currency.iso_code
+            == other.cash_flow.amount.currency.iso_code
+        && (transaction.cash_flow.amount.amount - other.cash_flow.amount.amount).abs() < 1e-9
+}
+
+/// Store imported transactions in the database. Assets are looked up by WKN or ISIN and
+/// inserted if they are not yet known, currencies are created if required.
+/// Transactions which match an already stored transaction in date, type, asset and amount
+/// are skipped together with their related transactions, so that importing the same
+/// file twice does not book transactions twice.
+pub async fn store_transactions(
+    imports: &[ImportTransaction],
+    db: &dyn TransactionHandler,
+) -> Result<ImportSummary, ImportError> {
+    let existing = db.get_all_transactions().await?;
+    let mut summary = ImportSummary::default();
+    for import in imports {
+        let mut transaction = import.transaction.clone();
+        if let Some(stock) = &import.asset {
+            let asset = Asset::Stock(stock.clone());
+            let asset_id = match db.get_asset_id(&asset).await {
+                Some(asset_id) => asset_id,
+                None => db.insert_asset(&asset).await?,
+            };
+            transaction.set_asset_id(asset_id);
+        }
+        transaction.cash_flow.amount.currency = db
+            .get_or_new_currency(transaction.cash_flow.amount.currency.iso_code)
+            .await?;
+        if existing.iter().any(|t| is_duplicate(&transaction, t)) {
+            summary.skipped += 1;
+            continue;
+        }
+        let id = db.insert_transaction(&transaction).await?;
+        for related in &import.related {
+            let mut related = related.clone();
+            related.set_transaction_ref(id);
+            related.cash_flow.amount.currency = transaction.cash_flow.amount.currency;
+            db.insert_transaction(&related).await?;
+        }
+        summary.inserted += 1;
+    }
+    Ok(summary)
+}
+
+/// Parse number in German formatting, i.e. with thousands dot and decimal comma
+pub fn parse_german_number(num_str: &str) -> Result<f64, ImportError> {
+    num_str
+        .trim()
+        .replace('.', "")
+        .replace(',', ".")
+        .parse()
+        .map_err(|_| ImportError::InvalidNumber(num_str.to_string()))
+}
@@ -39,21 +39,26 @@ pub mod macros;
 
 // module exports
 pub mod bond;
+pub mod calendar_store;
 pub mod coupon_date;
 pub mod datatypes;
 pub mod day_adjust;
 pub mod day_count_conv;
+pub mod economic_data;
 pub mod fixed_income;
 pub mod fx_rates;
 pub mod helpers;
+pub mod import;
 pub mod market;
 pub mod market_quotes;
 pub mod period_date;
 pub mod portfolio;
 pub mod postgres;
 pub mod rates;
+pub mod statistics;
 pub mod strategy;
 pub mod time_period;
 pub mod time_series;
+pub mod vol_surface;
 
 pub use market::Market;
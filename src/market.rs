@@ -3,33 +3,53 @@
 /// source, e.g a database, files, or REST service.
 /// Market data consist of non-static data, like interest rates,
 /// asset prices, or foreign exchange rates.
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, RwLock};
 
-use chrono::{DateTime, Local, NaiveDate};
-use std::collections::BTreeMap;
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate};
+use std::collections::{BTreeMap, BTreeSet};
 
 use async_trait::async_trait;
+use serde::Serialize;
 use thiserror::Error;
 
+use crate::bond::{BondError, BondSpec};
+use crate::calendar_store::{self, CalendarError, UserCalendar};
 use crate::datatypes::{
-    date_time_helper::naive_date_to_date_time, Asset, Currency, CurrencyConverter, CurrencyError,
-    CurrencyISOCode, QuoteHandler,
+    date_time_helper::{naive_date_to_date_time, DateTimeError},
+    Asset, CashAmount, CashFlow, Currency, CurrencyConverter, CurrencyError, CurrencyISOCode,
+    DataError, ObjectHandler, Quote, QuoteHandler, StockSplit, Ticker, Transaction,
+    TransactionHandler, TransactionType,
 };
+use crate::day_count_conv::{DayCountConv, DayCountConvError};
+use crate::fixed_income::get_cash_flows_after;
+use crate::portfolio::{calculate_position_and_pnl, PortfolioSnapshot, PositionError};
+use crate::rates::Discounter;
+use crate::time_period::TimePeriod;
+use crate::vol_surface::{self, VolPoint, VolatilitySurface};
 
-use crate::market_quotes::{self, MarketDataSourceError, MarketQuoteProvider};
+use crate::market_quotes::{self, MarketDataSourceError, MarketQuoteProvider, StaleQuotePolicy};
 use cal_calc::Calendar;
+use chrono_tz::Tz;
 
 /// Error related to market data object
 #[derive(Error, Debug)]
 pub enum MarketError {
     #[error("Unknown calendar")]
     CalendarNotFound,
+    #[error("User calendar error")]
+    UserCalendarError(#[from] CalendarError),
     #[error("Market quote error")]
     MarketQuoteError(#[from] market_quotes::MarketQuoteError),
     #[error("Database error")]
     DBError(#[from] crate::datatypes::DataError),
     #[error("Missing market data provider token")]
     MissingProviderToken,
+    #[error("No market data provider for source {0}")]
+    MissingProvider(String),
+    #[error("Unknown ticker with id {0}")]
+    UnknownTicker(i32),
     #[error("Currency conversion failure")]
     CurrencyConversionError,
     #[error("date/time conversion error")]
@@ -46,6 +66,29 @@ pub enum MarketError {
     CurrencyNotInDatabase(String),
     #[error("Missing quote for currency pair {0}/{1}")]
     MissingQuoteForCurrencyPair(String, String),
+    #[error("Day count convention error")]
+    DayCountError(#[from] DayCountConvError),
+    #[error("Position calculation failed")]
+    PositionError(Box<PositionError>),
+    #[error("Failed to write csv")]
+    CsvError(#[from] csv::Error),
+    #[error("Bond valuation failed")]
+    BondError(#[from] BondError),
+    #[error("Invalid threshold for quote jumps: {0}")]
+    InvalidQuoteJump(f64),
+}
+
+/// Row of the csv export of a portfolio time series
+#[derive(Serialize)]
+struct PortfolioTimeSeriesRow {
+    date: NaiveDate,
+    value: f64,
+    trading_pnl: f64,
+    unrealized_pnl: f64,
+    dividend: f64,
+    interest: f64,
+    fees: f64,
+    tax: f64,
 }
 
 #[derive(Clone)]
@@ -54,6 +97,37 @@ struct TimeRange {
     end: DateTime<Local>,
 }
 
+/// Cached prices of an asset, together with the time ranges for which all quotes
+/// of the asset have been loaded into the cache
+#[derive(Default)]
+struct CachedPrices {
+    ranges: Vec<TimeRange>,
+    quotes: BTreeMap<DateTime<Local>, (f64, i32)>,
+}
+
+impl CachedPrices {
+    /// Add all quotes loaded for the given time range
+    fn add_range(
+        &mut self,
+        range: TimeRange,
+        quotes: impl Iterator<Item = (DateTime<Local>, (f64, i32))>,
+    ) {
+        self.quotes.extend(quotes);
+        self.ranges.push(range);
+    }
+
+    /// Last quote before the given time, if the cache is known to contain it, i.e. if
+    /// the time is within a loaded range and there is a quote between the start of that
+    /// range and the given time
+    fn last_before(&self, time: DateTime<Local>) -> Option<(f64, i32)> {
+        self.ranges
+            .iter()
+            .filter(|range| range.start < time && time <= range.end)
+            .find_map(|range| self.quotes.range(range.start..time).next_back())
+            .map(|entry| *entry.1)
+    }
+}
+
 /// Caching policy for Market
 #[derive(Clone)]
 enum CachePolicy {
@@ -63,6 +137,18 @@ enum CachePolicy {
     PredefinedPeriod(TimeRange),
 }
 
+/// Method to determine fx rates at times between stored fx quotes
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FxFillPolicy {
+    /// Use the last fx rate on or before the given time
+    LastBefore,
+    /// Use the fx rate closest in time, preferring the earlier one in case of a tie
+    Nearest,
+    /// Interpolate linearly in time between the last rate before and the first rate after
+    /// the given time, or use the last rate before if there is no later rate
+    Linear,
+}
+
 async fn currency_map(db: Arc<dyn QuoteHandler + Sync + Send>) -> BTreeMap<i32, Currency> {
     let mut currency_map = BTreeMap::new();
     if let Ok(currency_vec) = db.get_all_currencies().await {
@@ -76,17 +162,56 @@ async fn currency_map(db: Arc<dyn QuoteHandler + Sync + Send>) -> BTreeMap<i32,
 /// Container or adaptor to market data
 struct MarketImpl {
     /// Stored calendars
-    calendars: BTreeMap<String, Calendar>,
+    calendars: BTreeMap<String, Arc<Calendar>>,
+    /// User defined calendars
+    user_calendars: RwLock<BTreeMap<String, Arc<Calendar>>>,
     /// Pre-fetched asset prices
-    prices: RwLock<BTreeMap<i32, BTreeMap<DateTime<Local>, (f64, i32)>>>,
+    prices: RwLock<BTreeMap<i32, CachedPrices>>,
     /// collection of market data quotes provider
     providers: RwLock<BTreeMap<String, Arc<dyn MarketQuoteProvider + Sync + Send>>>,
     /// Quotes database
     db: Arc<dyn QuoteHandler + Sync + Send>,
     /// Caching policy
     cache_policy: RwLock<CachePolicy>,
+    /// Method to determine fx rates between stored fx quotes
+    fx_fill_policy: RwLock<FxFillPolicy>,
     /// List of currency for fast access
     currencies: RwLock<BTreeMap<i32, Currency>>,
+    /// Number of asset price requests served from cache
+    cache_hits: AtomicU64,
+    /// Number of asset price requests not found in cache
+    cache_misses: AtomicU64,
+    /// Skip updates of lower priority tickers once an asset's quote has been updated
+    skip_redundant_updates: AtomicBool,
+    /// Fetch quotes for inactive tickers in `update_quotes`
+    include_inactive_tickers: AtomicBool,
+    /// Relative change between consecutive quotes regarded as anomalous by `validate_quotes`
+    max_quote_jump: RwLock<f64>,
+}
+
+/// Default relative change between consecutive quotes regarded as anomalous
+const DEFAULT_MAX_QUOTE_JUMP: f64 = 0.5;
+
+/// Suspicious quote found by `Market::validate_quotes`
+#[derive(Debug, Clone, PartialEq)]
+pub enum QuoteAnomaly {
+    /// Price is zero, negative or not a finite number
+    InvalidPrice { time: DateTime<Local>, price: f64 },
+    /// Price differs from the previous valid price by more than the configured threshold
+    Jump {
+        time: DateTime<Local>,
+        price: f64,
+        previous_price: f64,
+    },
+}
+
+/// Statistics on the content of the price cache
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheStats {
+    pub asset_count: usize,
+    pub total_price_points: usize,
+    pub oldest_entry: Option<DateTime<Local>>,
+    pub newest_entry: Option<DateTime<Local>>,
 }
 
 #[derive(Clone)]
@@ -99,12 +224,22 @@ impl Market {
         Self {
             inner: Arc::new(MarketImpl {
                 // Set of default calendars
-                calendars: generate_calendars(),
+                calendars: generate_calendars()
+                    .into_iter()
+                    .map(|(name, calendar)| (name, Arc::new(calendar)))
+                    .collect(),
+                user_calendars: RwLock::new(BTreeMap::new()),
                 providers: RwLock::new(BTreeMap::new()),
                 prices: RwLock::new(BTreeMap::new()),
                 db: db.clone(),
                 cache_policy: RwLock::new(CachePolicy::None),
+                fx_fill_policy: RwLock::new(FxFillPolicy::LastBefore),
                 currencies: RwLock::new(currency_map(db).await),
+                cache_hits: AtomicU64::new(0),
+                cache_misses: AtomicU64::new(0),
+                skip_redundant_updates: AtomicBool::new(false),
+                include_inactive_tickers: AtomicBool::new(false),
+                max_quote_jump: RwLock::new(DEFAULT_MAX_QUOTE_JUMP),
             }),
         }
     }
@@ -121,12 +256,22 @@ impl Market {
         Ok(Self {
             inner: Arc::new(MarketImpl {
                 // Set of default calendars
-                calendars: generate_calendars(),
+                calendars: generate_calendars()
+                    .into_iter()
+                    .map(|(name, calendar)| (name, Arc::new(calendar)))
+                    .collect(),
+                user_calendars: RwLock::new(BTreeMap::new()),
                 providers: RwLock::new(BTreeMap::new()),
                 prices: RwLock::new(BTreeMap::new()),
                 db: db.clone(),
                 cache_policy: RwLock::new(cache_policy),
+                fx_fill_policy: RwLock::new(FxFillPolicy::LastBefore),
                 currencies: RwLock::new(currency_map(db).await),
+                cache_hits: AtomicU64::new(0),
+                cache_misses: AtomicU64::new(0),
+                skip_redundant_updates: AtomicBool::new(false),
+                include_inactive_tickers: AtomicBool::new(false),
+                max_quote_jump: RwLock::new(DEFAULT_MAX_QUOTE_JUMP),
             }),
         })
     }
@@ -157,13 +302,137 @@ impl Market {
         Ok(())
     }
 
-    /// Get calendar from market
-    pub fn get_calendar(&self, name: &str) -> Result<&Calendar, MarketError> {
-        if self.inner.calendars.contains_key(name) {
-            Ok(&self.inner.calendars[name])
-        } else {
-            Err(MarketError::CalendarNotFound)
+    /// Set the method used by `fx_rate` to determine fx rates between stored fx quotes
+    pub fn set_fx_fill_policy(&self, fx_fill_policy: FxFillPolicy) -> Result<(), MarketError> {
+        let mut policy = self
+            .inner
+            .fx_fill_policy
+            .write()
+            .map_err(|_| MarketError::CacheFailure)?;
+        *policy = fx_fill_policy;
+        Ok(())
+    }
+
+    /// Set the relative change between consecutive quotes, e.g. 0.5 for 50%, beyond which
+    /// `validate_quotes` regards a quote as anomalous. The threshold must be a finite,
+    /// non-negative number.
+    pub fn set_max_quote_jump(&self, max_jump: f64) -> Result<(), MarketError> {
+        if !max_jump.is_finite() || max_jump < 0.0 {
+            return Err(MarketError::InvalidQuoteJump(max_jump));
+        }
+        let mut max_quote_jump = self
+            .inner
+            .max_quote_jump
+            .write()
+            .map_err(|_| MarketError::CacheFailure)?;
+        *max_quote_jump = max_jump;
+        Ok(())
+    }
+
+    /// If enabled, `update_quotes` fetches only the latest quote of the highest priority
+    /// ticker of an asset that could be updated successfully, instead of all tickers
+    pub fn set_skip_redundant_updates(&self, enabled: bool) {
+        self.inner
+            .skip_redundant_updates
+            .store(enabled, Ordering::Relaxed);
+    }
+
+    /// If enabled, `update_quotes` fetches quotes for inactive tickers as well
+    pub fn set_include_inactive_tickers(&self, enabled: bool) {
+        self.inner
+            .include_inactive_tickers
+            .store(enabled, Ordering::Relaxed);
+    }
+
+    /// Get calendar from market, either one of the built-in calendars or
+    /// a user defined calendar. User defined calendars are not looked up in the database,
+    /// they must have been added via `add_user_calendar`, `load_user_calendars`,
+    /// `save_user_calendar` or `get_or_load_calendar` before.
+    pub fn get_calendar(&self, name: &str) -> Result<Arc<Calendar>, MarketError> {
+        if let Some(calendar) = self.inner.calendars.get(name) {
+            return Ok(calendar.clone());
+        }
+        let user_calendars = self
+            .inner
+            .user_calendars
+            .read()
+            .map_err(|_| MarketError::CacheFailure)?;
+        (*user_calendars)
+            .get(name)
+            .cloned()
+            .ok_or(MarketError::CalendarNotFound)
+    }
+
+    /// Number of business days of the given calendar in the given year, e.g. to be used
+    /// as number of periods per year for the annualization of daily returns
+    pub fn periods_per_year(&self, cal_name: &str, year: i32) -> Result<usize, MarketError> {
+        let calendar = self.get_calendar(cal_name)?;
+        let (start, end) = NaiveDate::from_ymd_opt(year, 1, 1)
+            .zip(NaiveDate::from_ymd_opt(year + 1, 1, 1))
+            .ok_or(DateTimeError::DateTimeConversionFailed)?;
+        Ok(start
+            .iter_days()
+            .take_while(|date| *date < end)
+            .filter(|date| calendar.is_business_day(*date))
+            .count())
+    }
+
+    /// Add user defined calendar to market, replacing any previous calendar of the same name
+    pub fn add_user_calendar(&self, user_cal: &UserCalendar) -> Result<(), MarketError> {
+        let calendar = calendar_store::materialize(user_cal, &self.inner.calendars)?;
+        let mut user_calendars = self
+            .inner
+            .user_calendars
+            .write()
+            .map_err(|_| MarketError::CacheFailure)?;
+        (*user_calendars).insert(user_cal.name.clone(), Arc::new(calendar));
+        Ok(())
+    }
+
+    /// Get calendar from market like `get_calendar`, but look up user defined calendars
+    /// unknown to the market in the database. Calendars loaded from the database are
+    /// added to the market.
+    pub async fn get_or_load_calendar<DB: ObjectHandler + Sync>(
+        &self,
+        db: &DB,
+        name: &str,
+    ) -> Result<Arc<Calendar>, MarketError> {
+        match self.get_calendar(name) {
+            Err(MarketError::CalendarNotFound) => {}
+            result => return result,
         }
+        let stored_names = calendar_store::list_user_calendars(db).await?;
+        if !stored_names.iter().any(|stored_name| stored_name == name) {
+            return Err(MarketError::CalendarNotFound);
+        }
+        let user_cal = calendar_store::load_user_calendar(db, name).await?;
+        self.add_user_calendar(&user_cal)?;
+        self.get_calendar(name)
+    }
+
+    /// Store user defined calendar in database and add it to market, replacing any previous
+    /// calendar of the same name
+    pub async fn save_user_calendar<DB: ObjectHandler + Sync>(
+        &self,
+        db: &DB,
+        user_cal: &UserCalendar,
+    ) -> Result<(), MarketError> {
+        // Check that the calendar is valid before it is stored
+        calendar_store::materialize(user_cal, &self.inner.calendars)?;
+        calendar_store::save_user_calendar(db, user_cal).await?;
+        self.add_user_calendar(user_cal)
+    }
+
+    /// Load all user defined calendars stored in database
+    pub async fn load_user_calendars<DB: ObjectHandler + Sync>(
+        &self,
+        db: &DB,
+    ) -> Result<(), MarketError> {
+        for name in calendar_store::list_user_calendars(db).await? {
+            let user_cal = calendar_store::load_user_calendar(db, &name).await?;
+            self.add_user_calendar(&user_cal)?;
+        }
+        Ok(())
     }
 
     /// Store currency in cache
@@ -219,10 +488,26 @@ impl Market {
         }
     }
 
+    /// Get the names of all market data providers added to the market
+    pub fn active_providers(&self) -> Vec<String> {
+        if let Ok(providers) = self.inner.providers.read() {
+            (*providers).keys().cloned().collect()
+        } else {
+            Vec::new()
+        }
+    }
+
     /// Fetch latest quotes for all active ticker
+    /// Tickers of the same asset are updated in order of their priority.
     /// Returns a list of ticker for which the update failed.
     pub async fn update_quotes(&self) -> Result<Vec<i32>, MarketError> {
-        let tickers = self.inner.db.get_all_ticker().await?;
+        let mut tickers = self.inner.db.get_all_ticker().await?;
+        if !self.inner.include_inactive_tickers.load(Ordering::Relaxed) {
+            tickers.retain(|ticker| ticker.active);
+        }
+        tickers.sort_by_key(|ticker| (ticker.asset, ticker.priority));
+        let skip_redundant = self.inner.skip_redundant_updates.load(Ordering::Relaxed);
+        let mut updated_assets = BTreeSet::new();
         let mut failed_ticker = Vec::new();
         let providers = self
             .inner
@@ -230,36 +515,322 @@ impl Market {
             .read()
             .map_err(|_| MarketError::CacheFailure)?;
         for ticker in tickers {
+            if skip_redundant && updated_assets.contains(&ticker.asset) {
+                continue;
+            }
             if let Some(provider) = (*providers).get(&ticker.source) {
-                if market_quotes::update_ticker((*provider).clone(), &ticker, self.inner.db.clone())
-                    .await
-                    .is_err()
+                if market_quotes::update_ticker(
+                    (*provider).clone(),
+                    &ticker,
+                    self.inner.db.clone(),
+                    None,
+                    StaleQuotePolicy::Skip,
+                )
+                .await
+                .is_err()
                 {
                     failed_ticker.push(ticker.id.unwrap());
+                } else {
+                    updated_assets.insert(ticker.asset);
                 }
             }
         }
         Ok(failed_ticker)
     }
 
+    /// Check if the latest quote of a ticker is stale, i.e. if it is older than the
+    /// previous business day before `now` with respect to the given calendar.
+    /// Dates are compared in the ticker's time zone, or in local time if the ticker has none.
+    /// Tickers without any quotes are considered as stale.
+    pub async fn is_quote_stale(
+        &self,
+        ticker_id: i32,
+        now: DateTime<Local>,
+        cal_name: &str,
+    ) -> Result<bool, MarketError> {
+        let calendar = self.get_calendar(cal_name)?;
+        let ticker = self.inner.db.get_ticker_by_id(ticker_id).await?;
+        let last_quote_time = self
+            .inner
+            .db
+            .get_last_quote_for_ticker(ticker_id)
+            .await?
+            .map(|quote| quote.time);
+        let tz: Option<Tz> = match &ticker.tz {
+            Some(zone) => Some(zone.parse().map_err(|_| DateTimeError::StringParseError)?),
+            None => None,
+        };
+        let ticker_date = |time: DateTime<Local>| match tz {
+            Some(tz) => time.with_timezone(&tz).date_naive(),
+            None => time.date_naive(),
+        };
+        let last_session = calendar.prev_bday(ticker_date(now));
+        Ok(match last_quote_time {
+            Some(time) => ticker_date(time) < last_session,
+            None => true,
+        })
+    }
+
+    /// Time range of the available quotes of an asset over all its tickers,
+    /// or `None` if there are no quotes for the asset
+    pub async fn asset_quote_range(
+        &self,
+        asset_id: i32,
+    ) -> Result<Option<(DateTime<Local>, DateTime<Local>)>, MarketError> {
+        Ok(self.inner.db.get_quote_range_for_asset(asset_id).await?)
+    }
+
+    /// Ids of all assets with tickers whose latest quote over all tickers is older than
+    /// `max_age` at time `now`, or which have no quotes at all. Like for `update_quotes`,
+    /// inactive tickers are ignored unless enabled by `set_include_inactive_tickers`.
+    pub async fn assets_needing_update(
+        &self,
+        max_age: Duration,
+        now: DateTime<Local>,
+    ) -> Result<Vec<i32>, MarketError> {
+        let include_inactive = self.inner.include_inactive_tickers.load(Ordering::Relaxed);
+        let mut last_quotes: BTreeMap<i32, Option<DateTime<Local>>> = BTreeMap::new();
+        for (ticker, time) in self.inner.db.tickers_with_last_quote_time().await? {
+            if !(ticker.active || include_inactive) {
+                continue;
+            }
+            let last = last_quotes.entry(ticker.asset).or_insert(None);
+            if time > *last {
+                *last = time;
+            }
+        }
+        Ok(last_quotes
+            .into_iter()
+            .filter(|(_, time)| match time {
+                Some(time) => now - *time > max_age,
+                None => true,
+            })
+            .map(|(asset_id, _)| asset_id)
+            .collect())
+    }
+
+    /// Check all stored quotes of a ticker for invalid prices, i.e. prices which are not positive
+    /// or not finite, and for relative changes
+    /// to the previous valid quote beyond the threshold set by `set_max_quote_jump`
+    /// (default 50%). Anomalies are returned in chronological order.
+    pub async fn validate_quotes(&self, ticker_id: i32) -> Result<Vec<QuoteAnomaly>, MarketError> {
+        let max_jump = *self
+            .inner
+            .max_quote_jump
+            .read()
+            .map_err(|_| MarketError::CacheFailure)?;
+        let quotes = self.inner.db.get_all_quotes_for_ticker(ticker_id).await?;
+        let mut anomalies = Vec::new();
+        let mut previous_price: Option<f64> = None;
+        for quote in quotes {
+            if !quote.price.is_finite() || quote.price <= 0.0 {
+                anomalies.push(QuoteAnomaly::InvalidPrice {
+                    time: quote.time,
+                    price: quote.price,
+                });
+                continue;
+            }
+            if let Some(previous_price) = previous_price {
+                if (quote.price / previous_price - 1.0).abs() > max_jump {
+                    anomalies.push(QuoteAnomaly::Jump {
+                        time: quote.time,
+                        price: quote.price,
+                        previous_price,
+                    });
+                }
+            }
+            previous_price = Some(quote.price);
+        }
+        Ok(anomalies)
+    }
+
+    /// Mark all active tickers whose latest quote is older than `max_age` at time `now`
+    /// as inactive, e.g. tickers of delisted securities, and return their ids.
+    /// Tickers without any quotes are kept active, since they may just have been added.
+    pub async fn deactivate_stale_tickers(
+        &self,
+        max_age: Duration,
+        now: DateTime<Local>,
+    ) -> Result<Vec<i32>, MarketError> {
+        let mut deactivated = Vec::new();
+        for (ticker, time) in self.inner.db.tickers_with_last_quote_time().await? {
+            if !ticker.active {
+                continue;
+            }
+            if let (Some(id), Some(time)) = (ticker.id, time) {
+                if now - time > max_age {
+                    self.inner.db.deactivate_ticker(id).await?;
+                    deactivated.push(id);
+                }
+            }
+        }
+        Ok(deactivated)
+    }
+
     /// Update latest quote for a specific ticker id
+    /// Fails with `MarketError::MissingProvider` if no provider has been added for the
+    /// ticker's source, which is always the case for manually maintained tickers.
     pub async fn update_quote_for_ticker(&self, ticker_id: i32) -> Result<(), MarketError> {
-        let ticker = self.inner.db.get_ticker_by_id(ticker_id).await?;
-        let provider = if let Ok(providers) = self.inner.providers.read() {
-            if let Some(provider) = (*providers).get(&ticker.source) {
-                Some(provider.clone())
+        let ticker = self
+            .inner
+            .db
+            .get_ticker_by_id(ticker_id)
+            .await
+            .map_err(|err| match err {
+                DataError::NotFound(_) => MarketError::UnknownTicker(ticker_id),
+                err => MarketError::DBError(err),
+            })?;
+        let provider = self
+            .inner
+            .providers
+            .read()
+            .map_err(|_| MarketError::CacheFailure)?
+            .get(&ticker.source)
+            .cloned()
+            .ok_or_else(|| MarketError::MissingProvider(ticker.source.clone()))?;
+        market_quotes::update_ticker(
+            provider,
+            &ticker,
+            self.inner.db.clone(),
+            None,
+            StaleQuotePolicy::Skip,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Fetch the option chain of the underlying ticker, derive the implied volatilities
+    /// and store the resulting volatility surface in the database.
+    /// Implied volatilities are solved for with the Black-Scholes model using the mid price
+    /// (or the last price, if no bid/ask is available) and zero interest rates.
+    pub async fn fetch_and_store_vol_surface<DB: ObjectHandler + Sync>(
+        &self,
+        db: &DB,
+        asset_id: i32,
+        underlying_ticker: &Ticker,
+    ) -> Result<VolatilitySurface, MarketError> {
+        let provider = self
+            .inner
+            .providers
+            .read()
+            .map_err(|_| MarketError::CacheFailure)?
+            .get(&underlying_ticker.source)
+            .cloned()
+            .ok_or_else(|| MarketError::MissingProvider(underlying_ticker.source.clone()))?;
+        let option_quotes = provider.fetch_option_chain(underlying_ticker, None).await?;
+        let generated_at = Local::now();
+        let spot = self
+            .get_asset_price(asset_id, underlying_ticker.currency, generated_at)
+            .await?;
+        let today = generated_at.naive_local().date();
+        let mut points = Vec::new();
+        for quote in option_quotes {
+            let expiry_years = DayCountConv::Act365
+                .year_fraction(today, quote.expiry, None, None)
+                .map_err(|_| MarketError::DateTimeError(DateTimeError::DateTimeConversionFailed))?;
+            if expiry_years <= 0. {
+                continue;
+            }
+            let price = if quote.bid > 0. && quote.ask > 0. {
+                0.5 * (quote.bid + quote.ask)
             } else {
-                None
+                quote.last
+            };
+            if let Some(implied_vol) = vol_surface::implied_vol(
+                quote.option_type,
+                price,
+                spot,
+                quote.strike,
+                expiry_years,
+                0.,
+            ) {
+                points.push(VolPoint {
+                    strike: quote.strike,
+                    expiry_years,
+                    implied_vol,
+                });
             }
-        } else {
-            None
+        }
+        let surface = VolatilitySurface {
+            asset_id,
+            generated_at,
+            points,
         };
-        if let Some(provider) = provider {
-            market_quotes::update_ticker(provider, &ticker, self.inner.db.clone()).await?;
+        vol_surface::save_vol_surface(db, &surface).await?;
+        Ok(surface)
+    }
+
+    /// Store snapshot of a portfolio valuation in the database, replacing any previous
+    /// snapshot of the same date
+    pub async fn store_position_snapshot<DB: ObjectHandler + Sync>(
+        &self,
+        db: &DB,
+        snapshot: &PortfolioSnapshot,
+    ) -> Result<(), MarketError> {
+        let id = position_snapshot_id(snapshot.date);
+        let ids = db.get_object_ids(POSITION_SNAPSHOT).await?;
+        if ids.contains(&id) {
+            db.update_object(&id, snapshot).await?;
+        } else {
+            db.store_object(&id, POSITION_SNAPSHOT, snapshot).await?;
+        }
+        Ok(())
+    }
+
+    /// Load snapshot of a portfolio valuation for the given date from the database
+    /// Since currencies are stored by their ISO code only, they are resolved via the market.
+    pub async fn load_position_snapshot<DB: ObjectHandler + Sync>(
+        &self,
+        db: &DB,
+        date: NaiveDate,
+    ) -> Result<PortfolioSnapshot, MarketError> {
+        let mut snapshot: PortfolioSnapshot = db.get_object(&position_snapshot_id(date)).await?;
+        let cash = &mut snapshot.position.cash;
+        cash.currency = self.get_currency(cash.currency.iso_code).await?;
+        for position in snapshot.position.assets.values_mut() {
+            position.currency = self.get_currency(position.currency.iso_code).await?;
+        }
+        Ok(snapshot)
+    }
+
+    /// Store the specification of the bond with the given asset id in the database,
+    /// replacing any previously stored specification
+    pub async fn store_bond_spec<DB: ObjectHandler + Sync>(
+        &self,
+        db: &DB,
+        bond_asset_id: i32,
+        spec: &BondSpec,
+    ) -> Result<(), MarketError> {
+        let id = bond_spec_id(bond_asset_id);
+        let ids = db.get_object_ids(BOND_SPEC).await?;
+        if ids.contains(&id) {
+            db.update_object(&id, spec).await?;
+        } else {
+            db.store_object(&id, BOND_SPEC, spec).await?;
         }
         Ok(())
     }
 
+    /// Calculate the present value at `valuation_date` of all future payments of the bond
+    /// with the given asset id for its face value, i.e. the dirty value. The bond specification
+    /// is loaded from the database and the payments are discounted with the given curve.
+    pub async fn value_bond<DB: ObjectHandler + Sync>(
+        &self,
+        db: &DB,
+        bond_asset_id: i32,
+        valuation_date: NaiveDate,
+        curve: &dyn Discounter,
+    ) -> Result<f64, MarketError> {
+        let mut spec: BondSpec = db.get_object(&bond_spec_id(bond_asset_id)).await?;
+        spec.currency = self.get_currency(spec.currency.iso_code).await?;
+        let cash_flows = spec.into_cash_flows(self)?;
+        let future_cash_flows = get_cash_flows_after(&cash_flows, valuation_date);
+        let value = curve
+            .discount_cash_flow_stream(&future_cash_flows, valuation_date)
+            .map_err(BondError::from)?;
+        Ok(value.amount)
+    }
+
     /// Fetch latest quotes for all active ticker
     pub async fn update_quote_history(
         &self,
@@ -267,7 +838,15 @@ impl Market {
         start: DateTime<Local>,
         end: DateTime<Local>,
     ) -> Result<(), MarketError> {
-        let ticker = self.inner.db.get_ticker_by_id(ticker_id).await?;
+        let ticker = self
+            .inner
+            .db
+            .get_ticker_by_id(ticker_id)
+            .await
+            .map_err(|err| match err {
+                DataError::NotFound(_) => MarketError::UnknownTicker(ticker_id),
+                err => MarketError::DBError(err),
+            })?;
         let provider = if let Ok(providers) = self.inner.providers.read() {
             if let Some(provider) = (*providers).get(&ticker.source) {
                 Some(provider.clone())
@@ -290,6 +869,46 @@ impl Market {
         Ok(())
     }
 
+    /// Fetch and store the quote history of all tickers of the given source,
+    /// e.g. for initial data loads. Like for `update_quotes`, inactive tickers are skipped
+    /// unless enabled by `set_include_inactive_tickers`.
+    /// Returns a list of ticker for which the update failed.
+    pub async fn backfill_source_history(
+        &self,
+        source: &str,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> Result<Vec<i32>, MarketError> {
+        let provider = self
+            .inner
+            .providers
+            .read()
+            .map_err(|_| MarketError::CacheFailure)?
+            .get(source)
+            .cloned()
+            .ok_or_else(|| MarketError::MissingProvider(source.to_string()))?;
+        let mut tickers = self.inner.db.get_all_ticker_for_source(source).await?;
+        if !self.inner.include_inactive_tickers.load(Ordering::Relaxed) {
+            tickers.retain(|ticker| ticker.active);
+        }
+        let mut failed_ticker = Vec::new();
+        for ticker in tickers {
+            if market_quotes::update_ticker_history(
+                provider.clone(),
+                &ticker,
+                self.inner.db.clone(),
+                start,
+                end,
+            )
+            .await
+            .is_err()
+            {
+                failed_ticker.push(ticker.id.unwrap());
+            }
+        }
+        Ok(failed_ticker)
+    }
+
     /// Update quote history using all tickers of given asset
     pub async fn update_quote_history_for_asset(
         &self,
@@ -315,10 +934,174 @@ impl Market {
         Ok(())
     }
 
+    /// Fetch the dividend history per share of an asset from the provider of its active ticker
+    /// with the highest priority and store the dividends paid on the shares held at each
+    /// dividend date, as given by `shares_held`, as dividend transactions.
+    /// Dividends on dates without holdings are skipped. Returns the ids of the new transactions.
+    pub async fn import_dividends_as_transactions<DB: TransactionHandler + Sync>(
+        &self,
+        db: &DB,
+        asset_id: i32,
+        shares_held: impl Fn(NaiveDate) -> f64,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> Result<Vec<i32>, MarketError> {
+        let mut tickers = self.inner.db.get_all_ticker_for_asset(asset_id).await?;
+        tickers.retain(|ticker| ticker.active);
+        tickers.sort_by_key(|ticker| ticker.priority);
+        let (ticker, provider) = {
+            let providers = self
+                .inner
+                .providers
+                .read()
+                .map_err(|_| MarketError::CacheFailure)?;
+            tickers
+                .iter()
+                .find_map(|ticker| {
+                    providers
+                        .get(&ticker.source)
+                        .map(|provider| (ticker, provider.clone()))
+                })
+                .ok_or_else(|| match tickers.first() {
+                    Some(ticker) => MarketError::MissingProvider(ticker.source.clone()),
+                    None => MarketError::DBError(DataError::NotFound(format!(
+                        "active ticker for asset with id {}",
+                        asset_id
+                    ))),
+                })?
+        };
+        let dividends = provider.fetch_dividend_history(ticker, start, end).await?;
+        let mut transaction_ids = Vec::new();
+        for dividend in dividends {
+            let shares = shares_held(dividend.date);
+            if shares == 0.0 {
+                continue;
+            }
+            let transaction = Transaction {
+                id: None,
+                transaction_type: TransactionType::Dividend { asset_id },
+                cash_flow: CashFlow::new(
+                    dividend.amount.amount * shares,
+                    dividend.amount.currency,
+                    dividend.date,
+                ),
+                note: None,
+                time_stamp: None,
+            };
+            transaction_ids.push(db.insert_transaction(&transaction).await?);
+        }
+        Ok(transaction_ids)
+    }
+
+    /// Adjust the positions of all transactions of the given asset stored before the given
+    /// stock splits, such that they are given in post-split shares and sum up to the current
+    /// quantity. Cash amounts are kept, i.e. the implied prices per share are adjusted as well.
+    /// Since adjustments are cumulative, splits already applied to the asset are skipped.
+    /// Returns the number of adjusted transactions.
+    pub async fn split_adjust_transactions<DB: TransactionHandler + Sync>(
+        &self,
+        db: &DB,
+        asset_id: i32,
+        splits: &[StockSplit],
+    ) -> Result<usize, MarketError> {
+        Ok(db.apply_stock_splits(asset_id, splits).await?)
+    }
+
+    /// Load all quotes of the given assets within the given time range into the price cache
+    /// All quotes are fetched with a single database query, which is much faster than fetching
+    /// quotes asset by asset when valuing larger portfolios.
+    pub async fn preload_quotes(
+        &self,
+        asset_ids: &[i32],
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> Result<(), MarketError> {
+        let quotes = self
+            .inner
+            .db
+            .get_quotes_in_range_for_assets(asset_ids, start, end)
+            .await?;
+        if let Ok(mut prices) = self.inner.prices.write() {
+            for asset_id in asset_ids {
+                (*prices).entry(*asset_id).or_default().add_range(
+                    TimeRange { start, end },
+                    quotes
+                        .iter()
+                        .filter(|(id, _, _)| id == asset_id)
+                        .map(|(_, quote, currency_id)| (quote.time, (quote.price, *currency_id))),
+                );
+            }
+            Ok(())
+        } else {
+            Err(MarketError::CacheFailure)
+        }
+    }
+
+    /// Remove all prices from cache
+    pub fn clear_price_cache(&self) -> Result<(), MarketError> {
+        let mut prices = self
+            .inner
+            .prices
+            .write()
+            .map_err(|_| MarketError::CacheFailure)?;
+        prices.clear();
+        Ok(())
+    }
+
+    /// Remove all prices of the given asset from cache
+    pub fn clear_asset_from_cache(&self, asset_id: i32) -> Result<(), MarketError> {
+        let mut prices = self
+            .inner
+            .prices
+            .write()
+            .map_err(|_| MarketError::CacheFailure)?;
+        prices.remove(&asset_id);
+        Ok(())
+    }
+
+    /// Get statistics on the content of the price cache
+    pub fn cache_stats(&self) -> CacheStats {
+        let mut stats = CacheStats {
+            asset_count: 0,
+            total_price_points: 0,
+            oldest_entry: None,
+            newest_entry: None,
+        };
+        if let Ok(prices) = self.inner.prices.read() {
+            for series in prices
+                .values()
+                .map(|cached| &cached.quotes)
+                .filter(|series| !series.is_empty())
+            {
+                stats.asset_count += 1;
+                stats.total_price_points += series.len();
+                let first = *series.keys().next().unwrap();
+                let last = *series.keys().next_back().unwrap();
+                stats.oldest_entry = Some(stats.oldest_entry.map_or(first, |t| t.min(first)));
+                stats.newest_entry = Some(stats.newest_entry.map_or(last, |t| t.max(last)));
+            }
+        }
+        stats
+    }
+
+    /// Share of asset price requests served from cache, 0 if there haven't been any requests
+    pub fn cache_hit_rate(&self) -> f64 {
+        let hits = self.inner.cache_hits.load(Ordering::Relaxed);
+        let misses = self.inner.cache_misses.load(Ordering::Relaxed);
+        if hits + misses == 0 {
+            0.0
+        } else {
+            hits as f64 / (hits + misses) as f64
+        }
+    }
+
+    /// Get price and quote currency id of the last quote of an asset before the given time
+    /// from the cache. Returns `None` if the time is outside of the time ranges loaded into
+    /// the cache or the cache does not contain the last quote before the given time.
     pub fn try_from_cache(&self, asset_id: i32, time: DateTime<Local>) -> Option<(f64, i32)> {
         if let Ok(prices) = self.inner.prices.read() {
-            if let Some(series) = (*prices).get(&asset_id) {
-                return series.range(..time).last().map(|entry| *entry.1);
+            if let Some(cached) = (*prices).get(&asset_id) {
+                return cached.last_before(time);
             }
         }
         None
@@ -330,66 +1113,437 @@ impl Market {
         currency: Currency,
         time: DateTime<Local>,
     ) -> Result<f64, MarketError> {
-        let (price, quote_currency_id) = if let Some((quote, curr)) =
-            self.try_from_cache(asset_id, time)
-        {
-            (quote, curr)
-        } else {
-            let cache_policy = if let Ok(cache_policy) = self.inner.cache_policy.read() {
-                (*cache_policy).clone()
+        self.try_get_asset_price(asset_id, currency, time)
+            .await?
+            .ok_or_else(|| {
+                MarketError::DBError(DataError::NotFound(format!(
+                    "quote for asset with id {} before {}",
+                    asset_id, time
+                )))
+            })
+    }
+
+    /// Get the price of an asset in the given currency at the given time
+    /// Returns `None` if there is no quote for the asset on or before the given time.
+    /// Stored quotes already include the ticker's factor (see `Ticker::factor`),
+    /// therefore prices are only converted into the requested currency here.
+    pub async fn try_get_asset_price(
+        &self,
+        asset_id: i32,
+        currency: Currency,
+        time: DateTime<Local>,
+    ) -> Result<Option<f64>, MarketError> {
+        let (price, quote_currency_id) =
+            if let Some((quote, curr)) = self.try_from_cache(asset_id, time) {
+                self.inner.cache_hits.fetch_add(1, Ordering::Relaxed);
+                (quote, curr)
             } else {
-                CachePolicy::None
-            };
-            match &cache_policy {
-                CachePolicy::None => {
-                    let (quote, currency) = self
-                        .inner
-                        .db
-                        .get_last_quote_before_by_id(asset_id, time)
-                        .await?;
-                    (quote.price, currency.id.unwrap())
-                }
-                CachePolicy::PredefinedPeriod(time_range) => {
-                    let date_start = time.date().and_hms(0, 0, 0);
-                    let date_end = time.date().and_hms_milli(23, 59, 59, 999);
-                    let start = std::cmp::min(time_range.start, date_start);
-                    let end = std::cmp::max(time_range.end, date_end);
-                    let quotes = self
-                        .inner
-                        .db
-                        .get_quotes_in_range_by_id(asset_id, start, end)
-                        .await?;
-                    if let Ok(mut prices) = self.inner.prices.write() {
-                        // add quotes to cache in this
-                        let asset_prices = (*prices).entry(asset_id).or_insert_with(BTreeMap::new);
-                        for quote in quotes {
-                            asset_prices.insert(quote.0.time, (quote.0.price, quote.1));
+                self.inner.cache_misses.fetch_add(1, Ordering::Relaxed);
+                let cache_policy = if let Ok(cache_policy) = self.inner.cache_policy.read() {
+                    (*cache_policy).clone()
+                } else {
+                    CachePolicy::None
+                };
+                let cached = match &cache_policy {
+                    CachePolicy::None => None,
+                    CachePolicy::PredefinedPeriod(time_range) => {
+                        let date_start = time.date().and_hms(0, 0, 0);
+                        let date_end = time.date().and_hms_milli(23, 59, 59, 999);
+                        let start = std::cmp::min(time_range.start, date_start);
+                        let end = std::cmp::max(time_range.end, date_end);
+                        let quotes = self
+                            .inner
+                            .db
+                            .get_quotes_in_range_by_id(asset_id, start, end)
+                            .await?;
+                        if let Ok(mut prices) = self.inner.prices.write() {
+                            // add quotes to cache in this
+                            let quotes = quotes
+                                .into_iter()
+                                .map(|(quote, curr)| (quote.time, (quote.price, curr)));
+                            (*prices)
+                                .entry(asset_id)
+                                .or_default()
+                                .add_range(TimeRange { start, end }, quotes);
                         }
+                        self.try_from_cache(asset_id, time)
                     }
-                    self.try_from_cache(asset_id, time)
-                        .ok_or(MarketError::CacheFailure)?
+                };
+                // Without a quote in the cached range, fetch the last quote before from database
+                match cached {
+                    Some(price) => price,
+                    None => match self
+                        .inner
+                        .db
+                        .try_get_last_quote_before_by_id(asset_id, time)
+                        .await?
+                    {
+                        Some((quote, currency)) => (quote.price, currency.id.unwrap()),
+                        None => return Ok(None),
+                    },
                 }
-            }
-        };
-        if currency.id == Some(quote_currency_id) {
-            Ok(price)
-        } else {
-            let quote_currency = self.get_currency_by_id(quote_currency_id).await?;
-            let fx_rate = self
-                .fx_rate(quote_currency, currency, time)
-                .await
-                .map_err(|_| MarketError::CurrencyConversionError)?;
-            Ok(price * fx_rate)
-        }
+            };
+        self.convert_price(price, quote_currency_id, currency, time)
+            .await
+            .map(Some)
     }
-}
 
-#[async_trait]
-impl CurrencyConverter for Market {
-    async fn fx_rate(
+    /// Get the price of an asset in the given currency at the given time, linearly
+    /// interpolated between the last quote before and the first quote after the given time
+    /// If there is no quote after the given time, the last quote before is used.
+    pub async fn get_interpolated_price(
         &self,
-        base_currency: Currency,
-        quote_currency: Currency,
+        asset_id: i32,
+        currency: Currency,
+        time: DateTime<Local>,
+    ) -> Result<f64, MarketError> {
+        let (before, before_currency) = self
+            .inner
+            .db
+            .try_get_last_quote_before_by_id(asset_id, time)
+            .await?
+            .ok_or_else(|| {
+                MarketError::DBError(DataError::NotFound(format!(
+                    "quote for asset with id {} before {}",
+                    asset_id, time
+                )))
+            })?;
+        let before_price = self
+            .convert_price(before.price, before_currency.id.unwrap(), currency, time)
+            .await?;
+        if before.time == time {
+            return Ok(before_price);
+        }
+        match self
+            .inner
+            .db
+            .try_get_first_quote_after_by_id(asset_id, time)
+            .await?
+        {
+            None => Ok(before_price),
+            Some((after, after_currency)) => {
+                let after_price = self
+                    .convert_price(after.price, after_currency.id.unwrap(), currency, time)
+                    .await?;
+                let weight = (time - before.time).num_milliseconds() as f64
+                    / (after.time - before.time).num_milliseconds() as f64;
+                Ok(before_price + weight * (after_price - before_price))
+            }
+        }
+    }
+
+    /// Get the quote of an asset closest in time to the given time, either before or after it.
+    /// Quotes more than `max_gap` away are ignored. If both neighbouring quotes have the
+    /// same distance, the earlier quote is returned.
+    pub async fn nearest_quote(
+        &self,
+        asset_id: i32,
+        time: DateTime<Local>,
+        max_gap: Duration,
+    ) -> Result<Option<Quote>, MarketError> {
+        let before = self
+            .inner
+            .db
+            .try_get_last_quote_before_by_id(asset_id, time)
+            .await?
+            .map(|(quote, _)| quote)
+            .filter(|quote| time - quote.time <= max_gap);
+        let after = self
+            .inner
+            .db
+            .try_get_first_quote_after_by_id(asset_id, time)
+            .await?
+            .map(|(quote, _)| quote)
+            .filter(|quote| quote.time - time <= max_gap);
+        Ok(match (before, after) {
+            (Some(before), Some(after)) => {
+                if after.time - time < time - before.time {
+                    Some(after)
+                } else {
+                    Some(before)
+                }
+            }
+            (before, after) => before.or(after),
+        })
+    }
+
+    /// Quotes of an asset between start and end in ascending order of time, converted into the
+    /// given currency at the time of each quote. Only the quotes of a single ticker are used,
+    /// namely the ticker with the highest priority (i.e. lowest priority number) among all
+    /// tickers of the asset with quotes in the given range, so that prices of different
+    /// sources are not mixed within one history.
+    pub async fn get_quote_history(
+        &self,
+        asset_id: i32,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+        currency: Currency,
+    ) -> Result<Vec<(DateTime<Local>, f64)>, MarketError> {
+        let quotes = self
+            .inner
+            .db
+            .get_quotes_in_range_by_id(asset_id, start, end)
+            .await?;
+        let priorities: BTreeMap<i32, i32> = self
+            .inner
+            .db
+            .get_all_ticker_for_asset(asset_id)
+            .await?
+            .into_iter()
+            .filter_map(|ticker| ticker.id.map(|id| (id, ticker.priority)))
+            .collect();
+        // ties in priority are resolved by the lower ticker id to keep the choice deterministic
+        let ticker_id = quotes
+            .iter()
+            .map(|(quote, _)| quote.ticker)
+            .min_by_key(|id| (priorities.get(id).copied().unwrap_or(i32::MAX), *id));
+        // quotes are sorted by descending time
+        let mut history: Vec<(DateTime<Local>, f64)> = Vec::new();
+        for (quote, currency_id) in quotes {
+            if Some(quote.ticker) != ticker_id {
+                continue;
+            }
+            let price = self
+                .convert_price(quote.price, currency_id, currency, quote.time)
+                .await?;
+            history.push((quote.time, price));
+        }
+        history.reverse();
+        Ok(history)
+    }
+
+    /// Trailing returns of an asset in the given currency over the standard windows
+    /// 1D, 1W, 1M, 3M, 1Y and YTD up to `as_of`. The start price of each window is the last
+    /// price available at the end of the window's start date, for YTD the end of the last
+    /// day of the previous year. The return of a window is `None` if there is no price
+    /// available at its start.
+    pub async fn trailing_returns(
+        &self,
+        asset_id: i32,
+        as_of: DateTime<Local>,
+        currency: Currency,
+    ) -> Result<BTreeMap<&'static str, Option<f64>>, MarketError> {
+        let end_price = self.get_asset_price(asset_id, currency, as_of).await?;
+        let as_of_date = as_of.naive_local().date();
+        let mut start_dates = Vec::new();
+        for (window, period) in [
+            ("1D", "1D"),
+            ("1W", "1W"),
+            ("1M", "1M"),
+            ("3M", "3M"),
+            ("1Y", "12M"),
+        ] {
+            let period: TimePeriod = period.parse().unwrap();
+            start_dates.push((window, period.sub_from(as_of_date, None)));
+        }
+        let last_year_end = NaiveDate::from_ymd_opt(as_of_date.year() - 1, 12, 31)
+            .ok_or(DateTimeError::DateTimeConversionFailed)?;
+        start_dates.push(("YTD", last_year_end));
+
+        let mut returns = BTreeMap::new();
+        for (window, start_date) in start_dates {
+            let start_time = naive_date_to_date_time(&start_date, 24, None)?;
+            let start_price = self
+                .try_get_asset_price(asset_id, currency, start_time)
+                .await?;
+            returns.insert(window, start_price.map(|price| end_price / price - 1.0));
+        }
+        Ok(returns)
+    }
+
+    /// Convert cash amount into the given currency at the given time,
+    /// rounded to the number of digits of the target currency
+    pub async fn convert(
+        &self,
+        amount: CashAmount,
+        to: Currency,
+        time: DateTime<Local>,
+    ) -> Result<CashAmount, MarketError> {
+        let fx_rate = self.fx_rate(amount.currency, to, time).await?;
+        Ok(CashAmount {
+            amount: amount.amount * fx_rate,
+            currency: to,
+        }
+        .round(to.rounding_digits()))
+    }
+
+    /// Convert cash flows into the given currency, each at the last fx rate available
+    /// at the end of the cash flow's date
+    pub async fn convert_cash_flows(
+        &self,
+        flows: &[CashFlow],
+        to: Currency,
+    ) -> Result<Vec<CashFlow>, MarketError> {
+        let mut converted = Vec::with_capacity(flows.len());
+        for flow in flows {
+            let time = naive_date_to_date_time(&flow.date, 24, None)?;
+            converted.push(CashFlow {
+                amount: self.convert(flow.amount, to, time).await?,
+                date: flow.date,
+            });
+        }
+        Ok(converted)
+    }
+
+    /// Forward fx rate for the given settlement date derived by covered interest rate parity
+    /// from the spot rate at `spot_time` and the simply compounded interest rates of both
+    /// currencies over the period from spot to settlement date
+    #[allow(clippy::too_many_arguments)]
+    pub async fn forward_fx_rate(
+        &self,
+        base: Currency,
+        quote: Currency,
+        spot_time: DateTime<Local>,
+        settlement: NaiveDate,
+        r_base: f64,
+        r_quote: f64,
+        dcc: DayCountConv,
+    ) -> Result<f64, MarketError> {
+        let spot = self.fx_rate(base, quote, spot_time).await?;
+        let t = dcc.year_fraction(spot_time.naive_local().date(), settlement, None, None)?;
+        Ok(spot * (1. + r_quote * t) / (1. + r_base * t))
+    }
+
+    /// Get the fx rate for each business day of the given calendar between start and end date
+    /// The rate of a day is the last rate available at the end of the day.
+    pub async fn fx_rate_series(
+        &self,
+        base: Currency,
+        quote: Currency,
+        start: NaiveDate,
+        end: NaiveDate,
+        cal_name: &str,
+    ) -> Result<Vec<(NaiveDate, f64)>, MarketError> {
+        let calendar = self.get_calendar(cal_name)?;
+        let mut series = Vec::new();
+        for date in start.iter_days().take_while(|date| *date <= end) {
+            if calendar.is_business_day(date) {
+                let time = naive_date_to_date_time(&date, 24, None)?;
+                series.push((date, self.fx_rate(base, quote, time).await?));
+            }
+        }
+        Ok(series)
+    }
+
+    /// Write the portfolio value and P&L since inception for each business day of the given
+    /// calendar between start and end date as csv, with one row per day and the columns
+    /// date, value, trading_pnl, unrealized_pnl, dividend, interest, fees and tax.
+    /// Each day is valued with the latest quotes available at the end of the day.
+    pub async fn export_portfolio_timeseries_csv<W: Write>(
+        &self,
+        writer: W,
+        transactions: &[Transaction],
+        currency: Currency,
+        start: NaiveDate,
+        end: NaiveDate,
+        cal_name: &str,
+    ) -> Result<(), MarketError> {
+        let calendar = self.get_calendar(cal_name)?;
+        let mut csv_writer = csv::Writer::from_writer(writer);
+        for date in start.iter_days().take_while(|date| *date <= end) {
+            if calendar.is_business_day(date) {
+                let next_day = date + Duration::days(1);
+                let (_, totals) =
+                    calculate_position_and_pnl(currency, transactions, Some(next_day), self)
+                        .await
+                        .map_err(|err| MarketError::PositionError(Box::new(err)))?;
+                csv_writer.serialize(PortfolioTimeSeriesRow {
+                    date,
+                    value: totals.value,
+                    trading_pnl: totals.trading_pnl,
+                    unrealized_pnl: totals.unrealized_pnl,
+                    dividend: totals.dividend,
+                    interest: totals.interest,
+                    fees: totals.fees,
+                    tax: totals.tax,
+                })?;
+            }
+        }
+        csv_writer
+            .flush()
+            .map_err(|err| MarketError::CsvError(err.into()))?;
+        Ok(())
+    }
+
+    /// Determine fx rate from the stored fx quotes before and after the given time
+    /// according to the given fill policy
+    async fn filled_fx_rate(
+        &self,
+        base_currency: Currency,
+        quote_currency: Currency,
+        time: DateTime<Local>,
+        fx_fill_policy: FxFillPolicy,
+    ) -> Result<f64, CurrencyError> {
+        let base_curr_id = base_currency
+            .id
+            .ok_or(CurrencyError::CurrencyNotInDatabase(
+                base_currency.to_string(),
+            ))?;
+        let db_error = |e: DataError| CurrencyError::DataBaseError(e.to_string());
+        let in_quote_currency = |(_, curr): &(Quote, Currency)| curr.id == quote_currency.id;
+        let before = self
+            .inner
+            .db
+            .try_get_last_quote_before_by_id(base_curr_id, time)
+            .await
+            .map_err(db_error)?
+            .filter(in_quote_currency);
+        let after = self
+            .inner
+            .db
+            .try_get_first_quote_after_by_id(base_curr_id, time)
+            .await
+            .map_err(db_error)?
+            .filter(in_quote_currency);
+        match (before, after, fx_fill_policy) {
+            (Some((before, _)), Some((after, _)), FxFillPolicy::Nearest) => {
+                if after.time - time < time - before.time {
+                    Ok(after.price)
+                } else {
+                    Ok(before.price)
+                }
+            }
+            (Some((before, _)), Some((after, _)), FxFillPolicy::Linear) => {
+                let weight = (time - before.time).num_milliseconds() as f64
+                    / (after.time - before.time).num_milliseconds() as f64;
+                Ok(before.price + weight * (after.price - before.price))
+            }
+            (Some((before, _)), _, _) => Ok(before.price),
+            (None, Some((after, _)), FxFillPolicy::Nearest) => Ok(after.price),
+            _ => Err(CurrencyError::MissingQuoteForCurrencyPair(
+                base_currency.to_string(),
+                quote_currency.to_string(),
+            )),
+        }
+    }
+
+    /// Convert price given in currency with id `quote_currency_id` to the given currency
+    pub(crate) async fn convert_price(
+        &self,
+        price: f64,
+        quote_currency_id: i32,
+        currency: Currency,
+        time: DateTime<Local>,
+    ) -> Result<f64, MarketError> {
+        if currency.id == Some(quote_currency_id) {
+            Ok(price)
+        } else {
+            let quote_currency = self.get_currency_by_id(quote_currency_id).await?;
+            let fx_rate = self
+                .fx_rate(quote_currency, currency, time)
+                .await
+                .map_err(|_| MarketError::CurrencyConversionError)?;
+            Ok(price * fx_rate)
+        }
+    }
+}
+
+#[async_trait]
+impl CurrencyConverter for Market {
+    async fn fx_rate(
+        &self,
+        base_currency: Currency,
+        quote_currency: Currency,
         time: DateTime<Local>,
     ) -> Result<f64, CurrencyError> {
         if base_currency == quote_currency {
@@ -400,6 +1554,17 @@ impl CurrencyConverter for Market {
                 .ok_or(CurrencyError::CurrencyNotInDatabase(
                     base_currency.to_string(),
                 ))?;
+            let fx_fill_policy = self
+                .inner
+                .fx_fill_policy
+                .read()
+                .map(|policy| *policy)
+                .unwrap_or(FxFillPolicy::LastBefore);
+            if fx_fill_policy != FxFillPolicy::LastBefore {
+                return self
+                    .filled_fx_rate(base_currency, quote_currency, time, fx_fill_policy)
+                    .await;
+            }
             let (fx_quote, quote_curr_id) =
                 if let Some((fx_quote, quote_curr_id)) = self.try_from_cache(base_curr_id, time) {
                     (fx_quote, quote_curr_id)
@@ -423,6 +1588,22 @@ impl CurrencyConverter for Market {
     }
 }
 
+/// Object type used to store portfolio snapshots
+pub const POSITION_SNAPSHOT: &str = "position_snapshot";
+
+/// Id of the object the portfolio snapshot of a given date is stored under
+fn position_snapshot_id(date: NaiveDate) -> String {
+    format!("{}_{}", POSITION_SNAPSHOT, date)
+}
+
+/// Object type of stored bond specifications
+pub const BOND_SPEC: &str = "bond_spec";
+
+/// Id of the object the specification of the bond with the given asset id is stored under
+fn bond_spec_id(bond_asset_id: i32) -> String {
+    format!("{}_{}", BOND_SPEC, bond_asset_id)
+}
+
 /// Generate fixed set of some calendars for testing purposes only
 pub fn generate_calendars() -> BTreeMap<String, Calendar> {
     use cal_calc::{target_holidays, uk_settlement_holidays};
@@ -437,3 +1618,1719 @@ pub fn generate_calendars() -> BTreeMap<String, Calendar> {
 
     calendars
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datatypes::{date_time_helper::make_time, AssetHandler, Index, Stock};
+    use crate::market_quotes::{comdirect::Comdirect, yahoo::Yahoo, MarketDataSource};
+    use crate::postgres::PostgresDB;
+
+    /// Connect to the test database and remove all data
+    async fn prepare_db() -> PostgresDB {
+        let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
+        assert!(
+            db_url.is_ok(),
+            "environment variable $FINQL_TEST_DATABASE_URL is not set"
+        );
+        let db = PostgresDB::new(&db_url.unwrap()).await.unwrap();
+        db.clean().await.unwrap();
+        db
+    }
+
+    /// Active ticker of a manually maintained quote with priority 1 and factor 1
+    fn make_ticker(asset: i32, name: &str, currency: Currency) -> Ticker {
+        Ticker {
+            id: None,
+            asset,
+            name: name.to_string(),
+            currency,
+            source: "manual".to_string(),
+            priority: 1,
+            factor: 1.0,
+            tz: None,
+            cal: None,
+            active: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn list_active_providers() {
+        let db = prepare_db().await;
+
+        let market = Market::new(Arc::new(db)).await;
+        assert!(market.active_providers().is_empty());
+        market.add_provider(MarketDataSource::Yahoo.to_string(), Arc::new(Yahoo::new()));
+        market.add_provider(
+            MarketDataSource::Comdirect.to_string(),
+            Arc::new(Comdirect::new()),
+        );
+        let providers = market.active_providers();
+        assert_eq!(providers.len(), 2);
+        assert!(providers.contains(&"yahoo".to_string()));
+        assert!(providers.contains(&"comdirect".to_string()));
+        assert!(!providers.contains(&"gurufocus".to_string()));
+    }
+
+    #[tokio::test]
+    async fn preload_quotes_for_assets() {
+        let db = prepare_db().await;
+
+        let eur = Currency::new(None, CurrencyISOCode::new("EUR").unwrap(), None);
+        let eur_id = db.insert_asset(&Asset::Currency(eur)).await.unwrap();
+        let eur = Currency::new(Some(eur_id), eur.iso_code, None);
+        let mut asset_ids = Vec::new();
+        for i in 0..5 {
+            let stock = Stock::new(None, format!("Stock {}", i), None, None, None);
+            let asset_id = db.insert_asset(&Asset::Stock(stock)).await.unwrap();
+            let ticker_id = db
+                .insert_ticker(&make_ticker(asset_id, &format!("STOCK{}", i), eur))
+                .await
+                .unwrap();
+            for day in 1..=10 {
+                db.insert_quote(&Quote {
+                    id: None,
+                    ticker: ticker_id,
+                    price: 100.0 * (i + 1) as f64 + day as f64,
+                    time: make_time(2021, 3, day, 20, 0, 0).unwrap(),
+                    volume: None,
+                    stale: false,
+                })
+                .await
+                .unwrap();
+            }
+            asset_ids.push(asset_id);
+        }
+
+        let db = Arc::new(db);
+        let market = Market::new(db.clone()).await;
+        let start = make_time(2021, 3, 1, 0, 0, 0).unwrap();
+        let end = make_time(2021, 3, 31, 0, 0, 0).unwrap();
+        market.preload_quotes(&asset_ids, start, end).await.unwrap();
+
+        // Remove all quotes from database, prices must now be served from cache
+        db.clean().await.unwrap();
+        for (i, asset_id) in asset_ids.iter().enumerate() {
+            for day in 2..=10 {
+                let time = make_time(2021, 3, day, 22, 0, 0).unwrap();
+                let price = market.get_asset_price(*asset_id, eur, time).await.unwrap();
+                assert_eq!(price, 100.0 * (i + 1) as f64 + day as f64);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn interpolate_asset_price() {
+        let tol = 1e-10;
+        let db = prepare_db().await;
+
+        let eur = Currency::new(None, CurrencyISOCode::new("EUR").unwrap(), None);
+        let eur_id = db.insert_asset(&Asset::Currency(eur)).await.unwrap();
+        let eur = Currency::new(Some(eur_id), eur.iso_code, None);
+        let stock = Stock::new(None, "Stock".to_string(), None, None, None);
+        let asset_id = db.insert_asset(&Asset::Stock(stock)).await.unwrap();
+        let ticker_id = db
+            .insert_ticker(&make_ticker(asset_id, "STOCK", eur))
+            .await
+            .unwrap();
+        for (day, price) in [(1, 100.0), (2, 110.0)].iter() {
+            db.insert_quote(&Quote {
+                id: None,
+                ticker: ticker_id,
+                price: *price,
+                time: make_time(2021, 3, *day, 20, 0, 0).unwrap(),
+                volume: None,
+                stale: false,
+            })
+            .await
+            .unwrap();
+        }
+        let market = Market::new(Arc::new(db)).await;
+
+        let halfway = make_time(2021, 3, 2, 8, 0, 0).unwrap();
+        let price = market
+            .get_interpolated_price(asset_id, eur, halfway)
+            .await
+            .unwrap();
+        assert_fuzzy_eq!(price, 105.0, tol);
+        // after last quote, the last quote is used
+        let after = make_time(2021, 3, 3, 8, 0, 0).unwrap();
+        let price = market
+            .get_interpolated_price(asset_id, eur, after)
+            .await
+            .unwrap();
+        assert_fuzzy_eq!(price, 110.0, tol);
+        // no quote before first quote
+        let before = make_time(2021, 2, 28, 8, 0, 0).unwrap();
+        assert!(market
+            .get_interpolated_price(asset_id, eur, before)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn store_and_load_position_snapshot() {
+        use crate::portfolio::{PortfolioPosition, Position};
+
+        let db = Arc::new(prepare_db().await);
+        let market = Market::new(db.clone()).await;
+        let eur = market.get_currency_from_str("EUR").await.unwrap();
+
+        let mut position = PortfolioPosition::new(eur);
+        position.cash.position = 1000.0;
+        let mut asset_position = Position::new(Some(1), eur);
+        asset_position.position = 10.0;
+        asset_position.purchase_value = -950.0;
+        asset_position.last_quote = Some(99.0);
+        position.assets.insert(1, asset_position);
+        let totals = position.calc_totals();
+        let date = NaiveDate::from_ymd(2021, 3, 1);
+        let mut snapshot = PortfolioSnapshot {
+            date,
+            position,
+            totals,
+        };
+        market
+            .store_position_snapshot(db.as_ref(), &snapshot)
+            .await
+            .unwrap();
+        let loaded = market
+            .load_position_snapshot(db.as_ref(), date)
+            .await
+            .unwrap();
+        assert_eq!(loaded, snapshot);
+
+        // storing a snapshot for the same date replaces the previous one
+        snapshot.position.cash.position = 500.0;
+        snapshot.totals = snapshot.position.calc_totals();
+        market
+            .store_position_snapshot(db.as_ref(), &snapshot)
+            .await
+            .unwrap();
+        let loaded = market
+            .load_position_snapshot(db.as_ref(), date)
+            .await
+            .unwrap();
+        assert_eq!(loaded, snapshot);
+        assert!(market
+            .load_position_snapshot(db.as_ref(), NaiveDate::from_ymd(2021, 3, 2))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn manage_price_cache() {
+        let tol = 1e-11;
+        let db = prepare_db().await;
+
+        let eur = Currency::new(None, CurrencyISOCode::new("EUR").unwrap(), None);
+        let eur_id = db.insert_asset(&Asset::Currency(eur)).await.unwrap();
+        let eur = Currency::new(Some(eur_id), eur.iso_code, None);
+        let mut asset_ids = Vec::new();
+        let mut quote_ids = Vec::new();
+        for i in 0..2 {
+            let stock = Stock::new(None, format!("Stock {}", i), None, None, None);
+            let asset_id = db.insert_asset(&Asset::Stock(stock)).await.unwrap();
+            let ticker_id = db
+                .insert_ticker(&make_ticker(asset_id, &format!("STOCK{}", i), eur))
+                .await
+                .unwrap();
+            for day in 1..=3 {
+                let quote_id = db
+                    .insert_quote(&Quote {
+                        id: None,
+                        ticker: ticker_id,
+                        price: 100.0,
+                        time: make_time(2021, 3, day, 20, 0, 0).unwrap(),
+                        volume: None,
+                        stale: false,
+                    })
+                    .await
+                    .unwrap();
+                quote_ids.push((quote_id, ticker_id, day));
+            }
+            asset_ids.push(asset_id);
+        }
+
+        let db = Arc::new(db);
+        let market = Market::new(db.clone()).await;
+        let start = make_time(2021, 3, 1, 0, 0, 0).unwrap();
+        let end = make_time(2021, 3, 31, 0, 0, 0).unwrap();
+        market.preload_quotes(&asset_ids, start, end).await.unwrap();
+        let stats = market.cache_stats();
+        assert_eq!(stats.asset_count, 2);
+        assert_eq!(stats.total_price_points, 6);
+        assert_eq!(
+            stats.oldest_entry,
+            Some(make_time(2021, 3, 1, 20, 0, 0).unwrap())
+        );
+        assert_eq!(
+            stats.newest_entry,
+            Some(make_time(2021, 3, 3, 20, 0, 0).unwrap())
+        );
+
+        // change quotes in database, cached prices are not affected
+        for (quote_id, ticker_id, day) in quote_ids {
+            db.update_quote(&Quote {
+                id: Some(quote_id),
+                ticker: ticker_id,
+                price: 110.0,
+                time: make_time(2021, 3, day, 20, 0, 0).unwrap(),
+                volume: None,
+                stale: false,
+            })
+            .await
+            .unwrap();
+        }
+        let time = make_time(2021, 3, 4, 10, 0, 0).unwrap();
+        for asset_id in &asset_ids {
+            let price = market.get_asset_price(*asset_id, eur, time).await.unwrap();
+            assert_fuzzy_eq!(price, 100.0, tol);
+        }
+        assert_fuzzy_eq!(market.cache_hit_rate(), 1.0, tol);
+
+        // cleared asset is fetched from database again
+        market.clear_asset_from_cache(asset_ids[0]).unwrap();
+        assert_eq!(market.cache_stats().asset_count, 1);
+        let price = market
+            .get_asset_price(asset_ids[0], eur, time)
+            .await
+            .unwrap();
+        assert_fuzzy_eq!(price, 110.0, tol);
+        let price = market
+            .get_asset_price(asset_ids[1], eur, time)
+            .await
+            .unwrap();
+        assert_fuzzy_eq!(price, 100.0, tol);
+        assert_fuzzy_eq!(market.cache_hit_rate(), 0.75, tol);
+
+        // times outside of the cached range are fetched from database
+        let time = make_time(2021, 4, 10, 10, 0, 0).unwrap();
+        assert!(market.try_from_cache(asset_ids[1], time).is_none());
+        let price = market
+            .get_asset_price(asset_ids[1], eur, time)
+            .await
+            .unwrap();
+        assert_fuzzy_eq!(price, 110.0, tol);
+        let time = make_time(2021, 2, 10, 10, 0, 0).unwrap();
+        assert!(market
+            .try_get_asset_price(asset_ids[1], eur, time)
+            .await
+            .unwrap()
+            .is_none());
+
+        market.clear_price_cache().unwrap();
+        let stats = market.cache_stats();
+        assert_eq!(stats.asset_count, 0);
+        assert_eq!(stats.total_price_points, 0);
+        assert_eq!(stats.oldest_entry, None);
+    }
+
+    #[tokio::test]
+    async fn update_quote_for_unknown_ticker() {
+        let db = prepare_db().await;
+
+        assert!(matches!(
+            db.get_ticker_by_id(1).await,
+            Err(DataError::NotFound(_))
+        ));
+        let market = Market::new(Arc::new(db)).await;
+        assert!(matches!(
+            market.update_quote_for_ticker(1).await,
+            Err(MarketError::UnknownTicker(1))
+        ));
+    }
+
+    #[tokio::test]
+    async fn update_quote_for_manual_ticker() {
+        let db = prepare_db().await;
+        let market = Market::new(Arc::new(db)).await;
+        market.add_provider(MarketDataSource::Yahoo.to_string(), Arc::new(Yahoo::new()));
+        let eur = market.get_currency_from_str("EUR").await.unwrap();
+        let stock = Stock::new(None, "Private Equity".to_string(), None, None, None);
+        let asset_id = market
+            .db()
+            .insert_asset(&Asset::Stock(stock))
+            .await
+            .unwrap();
+        let ticker_id = market
+            .db()
+            .insert_ticker(&make_ticker(asset_id, "PE", eur))
+            .await
+            .unwrap();
+
+        match market.update_quote_for_ticker(ticker_id).await {
+            Err(MarketError::MissingProvider(source)) => assert_eq!(source, "manual"),
+            other => panic!("expected missing provider error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn stale_quotes_with_respect_to_calendar() {
+        let db = prepare_db().await;
+
+        let market = Market::new(Arc::new(db)).await;
+        let eur = market.get_currency_from_str("EUR").await.unwrap();
+        let stock = Stock::new(None, "Apple".to_string(), None, None, None);
+        let asset_id = market
+            .db()
+            .insert_asset(&Asset::Stock(stock))
+            .await
+            .unwrap();
+        let ticker_id = market
+            .db()
+            .insert_ticker(&make_ticker(asset_id, "AAPL", eur))
+            .await
+            .unwrap();
+        let saturday = make_time(2021, 11, 6, 12, 0, 0).unwrap();
+        let tuesday = make_time(2021, 11, 9, 12, 0, 0).unwrap();
+        assert!(market
+            .is_quote_stale(ticker_id, saturday, "TARGET")
+            .await
+            .unwrap());
+
+        // quote at close of Friday, 5th of November 2021
+        market
+            .db()
+            .insert_quote(&Quote {
+                id: None,
+                ticker: ticker_id,
+                price: 100.0,
+                time: make_time(2021, 11, 5, 18, 0, 0).unwrap(),
+                volume: None,
+                stale: false,
+            })
+            .await
+            .unwrap();
+        // an older quote stored later does not hide the most recent quote
+        market
+            .db()
+            .insert_quote(&Quote {
+                id: None,
+                ticker: ticker_id,
+                price: 99.0,
+                time: make_time(2021, 11, 3, 18, 0, 0).unwrap(),
+                volume: None,
+                stale: false,
+            })
+            .await
+            .unwrap();
+        assert!(!market
+            .is_quote_stale(ticker_id, saturday, "TARGET")
+            .await
+            .unwrap());
+        assert!(market
+            .is_quote_stale(ticker_id, tuesday, "TARGET")
+            .await
+            .unwrap());
+        assert!(matches!(
+            market.is_quote_stale(ticker_id, tuesday, "unknown").await,
+            Err(MarketError::CalendarNotFound)
+        ));
+        assert!(market
+            .is_quote_stale(ticker_id + 100, tuesday, "TARGET")
+            .await
+            .is_err());
+
+        // quote late on Thursday in New York is stale on Saturday, whatever the local time zone
+        let ny_ticker_id = market
+            .db()
+            .insert_ticker(&Ticker {
+                priority: 2,
+                tz: Some("America/New_York".to_string()),
+                ..make_ticker(asset_id, "AAPL_NY", eur)
+            })
+            .await
+            .unwrap();
+        let thursday_night = naive_date_to_date_time(
+            &NaiveDate::from_ymd(2021, 11, 4),
+            23,
+            Some("America/New_York".to_string()),
+        )
+        .unwrap();
+        market
+            .db()
+            .insert_quote(&Quote {
+                id: None,
+                ticker: ny_ticker_id,
+                price: 100.0,
+                time: thursday_night,
+                volume: None,
+                stale: false,
+            })
+            .await
+            .unwrap();
+        assert!(market
+            .is_quote_stale(ny_ticker_id, saturday, "TARGET")
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn convert_cash_amount() {
+        let tol = 1e-10;
+        let db = prepare_db().await;
+
+        let market = Market::new(Arc::new(db)).await;
+        let eur = market.get_currency_from_str("EUR").await.unwrap();
+        let usd = market.get_currency_from_str("USD").await.unwrap();
+        let time = make_time(2021, 11, 5, 18, 0, 0).unwrap();
+        crate::fx_rates::insert_fx_quote(0.87654, usd, eur, time, market.db())
+            .await
+            .unwrap();
+
+        let amount = CashAmount {
+            amount: 100.0,
+            currency: usd,
+        };
+        let converted = market.convert(amount, eur, time).await.unwrap();
+        assert_eq!(converted.currency, eur);
+        assert_fuzzy_eq!(converted.amount, 87.65, tol);
+        let converted = market.convert(amount, usd, time).await.unwrap();
+        assert_fuzzy_eq!(converted.amount, 100.0, tol);
+        let before = make_time(2021, 11, 4, 18, 0, 0).unwrap();
+        assert!(market.convert(amount, eur, before).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn trailing_returns_of_asset() {
+        let tol = 1e-10;
+        let db = prepare_db().await;
+
+        let market = Market::new(Arc::new(db)).await;
+        let eur = market.get_currency_from_str("EUR").await.unwrap();
+        let stock = Stock::new(None, "Stock".to_string(), None, None, None);
+        let asset_id = market
+            .db()
+            .insert_asset(&Asset::Stock(stock))
+            .await
+            .unwrap();
+        let ticker = market
+            .db()
+            .insert_ticker(&make_ticker(asset_id, "STOCK", eur))
+            .await
+            .unwrap();
+        let prices = [
+            (10, 29, 100.0),
+            (11, 22, 110.0),
+            (11, 29, 120.0),
+            (11, 30, 125.0),
+        ];
+        for (month, day, price) in prices {
+            market
+                .db()
+                .insert_quote(&Quote {
+                    id: None,
+                    ticker,
+                    price,
+                    time: make_time(2021, month, day, 18, 0, 0).unwrap(),
+                    volume: None,
+                    stale: false,
+                })
+                .await
+                .unwrap();
+        }
+
+        let as_of = make_time(2021, 12, 1, 18, 0, 0).unwrap();
+        let returns = market.trailing_returns(asset_id, as_of, eur).await.unwrap();
+        assert_eq!(returns.len(), 6);
+        // latest price is from 30th of November, i.e. unchanged since end of last day
+        assert_fuzzy_eq!(returns["1D"].unwrap(), 0.0, tol);
+        assert_fuzzy_eq!(returns["1W"].unwrap(), 125.0 / 110.0 - 1.0, tol);
+        assert_fuzzy_eq!(returns["1M"].unwrap(), 0.25, tol);
+        assert_eq!(returns["3M"], None);
+        assert_eq!(returns["1Y"], None);
+        assert_eq!(returns["YTD"], None);
+    }
+
+    #[tokio::test]
+    async fn quote_history_in_foreign_currency() {
+        let tol = 1e-10;
+        let db = prepare_db().await;
+
+        let market = Market::new(Arc::new(db)).await;
+        let eur = market.get_currency_from_str("EUR").await.unwrap();
+        let usd = market.get_currency_from_str("USD").await.unwrap();
+        let stock = Stock::new(None, "Apple".to_string(), None, None, None);
+        let asset_id = market
+            .db()
+            .insert_asset(&Asset::Stock(stock))
+            .await
+            .unwrap();
+        let mut ticker_ids = Vec::new();
+        for (name, priority) in [("AAPL", 1), ("AAPL.F", 2)] {
+            let ticker_id = market
+                .db()
+                .insert_ticker(&Ticker {
+                    priority,
+                    ..make_ticker(asset_id, name, usd)
+                })
+                .await
+                .unwrap();
+            ticker_ids.push(ticker_id);
+        }
+        for (day, fx_rate, price) in [(1, 0.85, 150.0), (3, 0.9, 160.0)] {
+            let time = make_time(2021, 11, day, 18, 0, 0).unwrap();
+            crate::fx_rates::insert_fx_quote(fx_rate, usd, eur, time, market.db())
+                .await
+                .unwrap();
+            // the lower priority ticker quotes a different price at the same time
+            for (ticker, price) in [(ticker_ids[1], price + 1.0), (ticker_ids[0], price)] {
+                market
+                    .db()
+                    .insert_quote(&Quote {
+                        id: None,
+                        ticker,
+                        price,
+                        time,
+                        volume: None,
+                        stale: false,
+                    })
+                    .await
+                    .unwrap();
+            }
+        }
+
+        let start = make_time(2021, 11, 1, 0, 0, 0).unwrap();
+        let end = make_time(2021, 11, 5, 0, 0, 0).unwrap();
+        let history = market
+            .get_quote_history(asset_id, start, end, eur)
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].0, make_time(2021, 11, 1, 18, 0, 0).unwrap());
+        assert_fuzzy_eq!(history[0].1, 150.0 * 0.85, tol);
+        assert_eq!(history[1].0, make_time(2021, 11, 3, 18, 0, 0).unwrap());
+        assert_fuzzy_eq!(history[1].1, 160.0 * 0.9, tol);
+
+        let history = market
+            .get_quote_history(asset_id, start, end, usd)
+            .await
+            .unwrap();
+        assert_fuzzy_eq!(history[0].1, 150.0, tol);
+        assert_fuzzy_eq!(history[1].1, 160.0, tol);
+
+        // quotes only available for the lower priority ticker are not mixed into the history
+        for (ticker, day) in [(ticker_ids[1], 2), (ticker_ids[1], 8)] {
+            market
+                .db()
+                .insert_quote(&Quote {
+                    id: None,
+                    ticker,
+                    price: 170.0,
+                    time: make_time(2021, 11, day, 18, 0, 0).unwrap(),
+                    volume: None,
+                    stale: false,
+                })
+                .await
+                .unwrap();
+        }
+        let history = market
+            .get_quote_history(asset_id, start, end, usd)
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 2);
+        assert_fuzzy_eq!(history[0].1, 150.0, tol);
+        assert_fuzzy_eq!(history[1].1, 160.0, tol);
+
+        // without quotes of the preferred ticker in range, the next ticker is used
+        let start = make_time(2021, 11, 6, 0, 0, 0).unwrap();
+        let end = make_time(2021, 11, 10, 0, 0, 0).unwrap();
+        let history = market
+            .get_quote_history(asset_id, start, end, usd)
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 1);
+        assert_fuzzy_eq!(history[0].1, 170.0, tol);
+    }
+
+    #[tokio::test]
+    async fn convert_dated_cash_flows() {
+        let tol = 1e-10;
+        let db = prepare_db().await;
+
+        let market = Market::new(Arc::new(db)).await;
+        let eur = market.get_currency_from_str("EUR").await.unwrap();
+        let usd = market.get_currency_from_str("USD").await.unwrap();
+        for (day, rate) in [(1, 0.85), (3, 0.9)] {
+            crate::fx_rates::insert_fx_quote(
+                rate,
+                usd,
+                eur,
+                make_time(2021, 11, day, 18, 0, 0).unwrap(),
+                market.db(),
+            )
+            .await
+            .unwrap();
+        }
+
+        let flows = [
+            CashFlow::new(100.0, usd, NaiveDate::from_ymd(2021, 11, 2)),
+            CashFlow::new(200.0, usd, NaiveDate::from_ymd(2021, 11, 3)),
+        ];
+        let converted = market.convert_cash_flows(&flows, eur).await.unwrap();
+        assert_eq!(converted.len(), 2);
+        assert_eq!(converted[0].date, flows[0].date);
+        assert_eq!(converted[0].amount.currency, eur);
+        assert_fuzzy_eq!(converted[0].amount.amount, 85.0, tol);
+        assert_eq!(converted[1].date, flows[1].date);
+        assert_fuzzy_eq!(converted[1].amount.amount, 180.0, tol);
+
+        let early = [CashFlow::new(100.0, usd, NaiveDate::from_ymd(2021, 10, 29))];
+        assert!(market.convert_cash_flows(&early, eur).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn forward_fx_rate_by_interest_rate_parity() {
+        let tol = 1e-10;
+        let db = prepare_db().await;
+
+        let market = Market::new(Arc::new(db)).await;
+        let eur = market.get_currency_from_str("EUR").await.unwrap();
+        let usd = market.get_currency_from_str("USD").await.unwrap();
+        let spot_time = make_time(2021, 11, 1, 18, 0, 0).unwrap();
+        crate::fx_rates::insert_fx_quote(1.15, eur, usd, spot_time, market.db())
+            .await
+            .unwrap();
+
+        let settlement = NaiveDate::from_ymd(2022, 5, 1);
+        let (r_eur, r_usd) = (-0.005, 0.02);
+        let forward = market
+            .forward_fx_rate(
+                eur,
+                usd,
+                spot_time,
+                settlement,
+                r_eur,
+                r_usd,
+                DayCountConv::Act360,
+            )
+            .await
+            .unwrap();
+        let t = 181. / 360.;
+        assert_fuzzy_eq!(forward, 1.15 * (1. + r_usd * t) / (1. + r_eur * t), tol);
+
+        // forward for settlement at spot date equals spot
+        let forward = market
+            .forward_fx_rate(
+                eur,
+                usd,
+                spot_time,
+                NaiveDate::from_ymd(2021, 11, 1),
+                r_eur,
+                r_usd,
+                DayCountConv::Act360,
+            )
+            .await
+            .unwrap();
+        assert_fuzzy_eq!(forward, 1.15, tol);
+    }
+
+    #[tokio::test]
+    async fn export_portfolio_timeseries() {
+        let db = prepare_db().await;
+
+        let market = Market::new(Arc::new(db)).await;
+        let eur = market.get_currency_from_str("EUR").await.unwrap();
+        let transactions = [
+            Transaction {
+                id: Some(1),
+                transaction_type: TransactionType::Cash,
+                cash_flow: CashFlow::new(10000.0, eur, NaiveDate::from_ymd(2021, 11, 1)),
+                note: None,
+                time_stamp: None,
+            },
+            Transaction {
+                id: Some(2),
+                transaction_type: TransactionType::Cash,
+                cash_flow: CashFlow::new(10.0, eur, NaiveDate::from_ymd(2021, 11, 3)),
+                note: None,
+                time_stamp: None,
+            },
+        ];
+
+        let mut output = Vec::new();
+        market
+            .export_portfolio_timeseries_csv(
+                &mut output,
+                &transactions,
+                eur,
+                NaiveDate::from_ymd(2021, 11, 1),
+                NaiveDate::from_ymd(2021, 11, 7),
+                "TARGET",
+            )
+            .await
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines[0],
+            "date,value,trading_pnl,unrealized_pnl,dividend,interest,fees,tax"
+        );
+        // one row per business day from Monday to Friday
+        assert_eq!(lines.len(), 6);
+        assert!(lines[1].starts_with("2021-11-01,10000.0,"));
+        assert!(lines[5].starts_with("2021-11-05,10010.0,"));
+    }
+
+    #[tokio::test]
+    async fn fx_rate_series_on_business_days() {
+        let tol = 1e-10;
+        let db = prepare_db().await;
+
+        let market = Market::new(Arc::new(db)).await;
+        let eur = market.get_currency_from_str("EUR").await.unwrap();
+        let usd = market.get_currency_from_str("USD").await.unwrap();
+        // rates on Monday and Wednesday
+        for (day, rate) in [(1, 1.15), (3, 1.17)] {
+            crate::fx_rates::insert_fx_quote(
+                rate,
+                eur,
+                usd,
+                make_time(2021, 11, day, 18, 0, 0).unwrap(),
+                market.db(),
+            )
+            .await
+            .unwrap();
+        }
+
+        let series = market
+            .fx_rate_series(
+                eur,
+                usd,
+                NaiveDate::from_ymd(2021, 11, 1),
+                NaiveDate::from_ymd(2021, 11, 7),
+                "TARGET",
+            )
+            .await
+            .unwrap();
+        let expected = [(1, 1.15), (2, 1.15), (3, 1.17), (4, 1.17), (5, 1.17)];
+        assert_eq!(series.len(), expected.len());
+        for ((date, rate), (day, expected_rate)) in series.iter().zip(expected.iter()) {
+            assert_eq!(*date, NaiveDate::from_ymd(2021, 11, *day));
+            assert_fuzzy_eq!(*rate, *expected_rate, tol);
+        }
+
+        // no rate available before first quote
+        assert!(market
+            .fx_rate_series(
+                eur,
+                usd,
+                NaiveDate::from_ymd(2021, 10, 29),
+                NaiveDate::from_ymd(2021, 11, 5),
+                "TARGET",
+            )
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn fx_rates_on_weekend() {
+        let tol = 1e-10;
+        let db = prepare_db().await;
+
+        let market = Market::new(Arc::new(db)).await;
+        let eur = market.get_currency_from_str("EUR").await.unwrap();
+        let usd = market.get_currency_from_str("USD").await.unwrap();
+        // rates at Friday and Monday close
+        for (day, rate) in [(5, 1.15), (8, 1.18)] {
+            crate::fx_rates::insert_fx_quote(
+                rate,
+                eur,
+                usd,
+                make_time(2021, 11, day, 18, 0, 0).unwrap(),
+                market.db(),
+            )
+            .await
+            .unwrap();
+        }
+        let saturday = make_time(2021, 11, 6, 18, 0, 0).unwrap();
+        let sunday = make_time(2021, 11, 7, 20, 0, 0).unwrap();
+        let position = CashAmount {
+            amount: 1000.0,
+            currency: eur,
+        };
+
+        let value = market.convert(position, usd, saturday).await.unwrap();
+        assert_fuzzy_eq!(value.amount, 1150.0, tol);
+        assert_fuzzy_eq!(market.fx_rate(eur, usd, sunday).await.unwrap(), 1.15, tol);
+
+        market.set_fx_fill_policy(FxFillPolicy::Linear).unwrap();
+        let value = market.convert(position, usd, saturday).await.unwrap();
+        assert_fuzzy_eq!(value.amount, 1160.0, tol);
+
+        market.set_fx_fill_policy(FxFillPolicy::Nearest).unwrap();
+        assert_fuzzy_eq!(market.fx_rate(eur, usd, saturday).await.unwrap(), 1.15, tol);
+        assert_fuzzy_eq!(market.fx_rate(eur, usd, sunday).await.unwrap(), 1.18, tol);
+        let thursday = make_time(2021, 11, 4, 18, 0, 0).unwrap();
+        assert_fuzzy_eq!(market.fx_rate(eur, usd, thursday).await.unwrap(), 1.15, tol);
+
+        market.set_fx_fill_policy(FxFillPolicy::Linear).unwrap();
+        assert!(market.fx_rate(eur, usd, thursday).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn periods_per_year_of_calendar() {
+        let db = prepare_db().await;
+        let market = Market::new(Arc::new(db)).await;
+        // 261 weekdays minus New Year, Good Friday and Easter Monday
+        assert_eq!(market.periods_per_year("TARGET", 2021).unwrap(), 258);
+        assert!(market.periods_per_year("unknown", 2021).is_err());
+    }
+
+    #[tokio::test]
+    async fn quote_range_of_asset() {
+        let db = prepare_db().await;
+
+        let market = Market::new(Arc::new(db)).await;
+        let eur = market.get_currency_from_str("EUR").await.unwrap();
+        let stock = Stock::new(None, "Apple".to_string(), None, None, None);
+        let asset_id = market
+            .db()
+            .insert_asset(&Asset::Stock(stock))
+            .await
+            .unwrap();
+        assert_eq!(market.asset_quote_range(asset_id).await.unwrap(), None);
+
+        let first = make_time(2021, 11, 1, 18, 0, 0).unwrap();
+        let last = make_time(2021, 11, 5, 18, 0, 0).unwrap();
+        let quote_times = [
+            ("yahoo", [first, make_time(2021, 11, 3, 18, 0, 0).unwrap()]),
+            ("manual", [make_time(2021, 11, 2, 18, 0, 0).unwrap(), last]),
+        ];
+        for (source, times) in quote_times {
+            let ticker_id = market
+                .db()
+                .insert_ticker(&Ticker {
+                    source: source.to_string(),
+                    ..make_ticker(asset_id, "AAPL", eur)
+                })
+                .await
+                .unwrap();
+            for time in times {
+                market
+                    .db()
+                    .insert_quote(&Quote {
+                        id: None,
+                        ticker: ticker_id,
+                        price: 100.0,
+                        time,
+                        volume: None,
+                        stale: false,
+                    })
+                    .await
+                    .unwrap();
+            }
+        }
+        assert_eq!(
+            market.asset_quote_range(asset_id).await.unwrap(),
+            Some((first, last))
+        );
+    }
+
+    #[tokio::test]
+    async fn benchmark_index_levels() {
+        let tol = 1e-10;
+        let db = prepare_db().await;
+
+        let market = Market::new(Arc::new(db)).await;
+        let usd = market.get_currency_from_str("USD").await.unwrap();
+        let index = Index::new(None, "S&P 500".to_string(), None);
+        let asset_id = market
+            .db()
+            .insert_asset(&Asset::Index(index))
+            .await
+            .unwrap();
+        let asset = market.db().get_asset_by_id(asset_id).await.unwrap();
+        assert_eq!(asset.class(), "index");
+        assert_eq!(asset.name(), "S&P 500");
+        assert_eq!(
+            market
+                .db()
+                .get_asset_id(&Asset::Index(Index::new(None, "S&P 500".to_string(), None)))
+                .await,
+            Some(asset_id)
+        );
+
+        // index levels are maintained manually
+        let ticker_id = market
+            .db()
+            .insert_ticker(&make_ticker(asset_id, "SPX", usd))
+            .await
+            .unwrap();
+        for (day, level) in [(4, 4680.06), (5, 4697.53)] {
+            market
+                .db()
+                .insert_quote(&Quote {
+                    id: None,
+                    ticker: ticker_id,
+                    price: level,
+                    time: make_time(2021, 11, day, 22, 0, 0).unwrap(),
+                    volume: None,
+                    stale: false,
+                })
+                .await
+                .unwrap();
+        }
+        let level = market
+            .get_asset_price(asset_id, usd, make_time(2021, 11, 5, 23, 0, 0).unwrap())
+            .await
+            .unwrap();
+        assert_fuzzy_eq!(level, 4697.53, tol);
+        let level = market
+            .get_asset_price(asset_id, usd, make_time(2021, 11, 5, 12, 0, 0).unwrap())
+            .await
+            .unwrap();
+        assert_fuzzy_eq!(level, 4680.06, tol);
+    }
+
+    /// Provider returning a daily quote history with constant prices
+    struct HistoryProvider {}
+
+    #[async_trait]
+    impl MarketQuoteProvider for HistoryProvider {
+        async fn fetch_latest_quote(
+            &self,
+            _ticker: &Ticker,
+        ) -> Result<Quote, market_quotes::MarketQuoteError> {
+            Err(market_quotes::MarketQuoteError::UnexpectedError(
+                "latest quotes are not supported".to_string(),
+            ))
+        }
+
+        async fn fetch_quote_history(
+            &self,
+            ticker: &Ticker,
+            start: DateTime<Local>,
+            end: DateTime<Local>,
+        ) -> Result<Vec<Quote>, market_quotes::MarketQuoteError> {
+            let mut quotes = Vec::new();
+            let mut time = start;
+            while time <= end {
+                quotes.push(Quote {
+                    id: None,
+                    ticker: ticker.id.unwrap(),
+                    price: 100.0,
+                    time,
+                    volume: None,
+                    stale: false,
+                });
+                time += chrono::Duration::days(1);
+            }
+            Ok(quotes)
+        }
+
+        async fn fetch_dividend_history(
+            &self,
+            _ticker: &Ticker,
+            _start: DateTime<Local>,
+            _end: DateTime<Local>,
+        ) -> Result<Vec<crate::datatypes::CashFlow>, market_quotes::MarketQuoteError> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn backfill_history_of_source() {
+        let db = prepare_db().await;
+
+        let market = Market::new(Arc::new(db)).await;
+        market.add_provider("eodhistdata".to_string(), Arc::new(HistoryProvider {}));
+        let eur = market.get_currency_from_str("EUR").await.unwrap();
+        let mut ticker_ids = Vec::new();
+        for name in ["AAPL", "MSFT"] {
+            let stock = Stock::new(None, name.to_string(), None, None, None);
+            let asset_id = market
+                .db()
+                .insert_asset(&Asset::Stock(stock))
+                .await
+                .unwrap();
+            let ticker_id = market
+                .db()
+                .insert_ticker(&Ticker {
+                    source: "eodhistdata".to_string(),
+                    ..make_ticker(asset_id, name, eur)
+                })
+                .await
+                .unwrap();
+            ticker_ids.push(ticker_id);
+        }
+
+        let start = make_time(2021, 11, 1, 18, 0, 0).unwrap();
+        let end = make_time(2021, 11, 5, 18, 0, 0).unwrap();
+        let failed = market
+            .backfill_source_history("eodhistdata", start, end)
+            .await
+            .unwrap();
+        assert!(failed.is_empty());
+        for ticker_id in ticker_ids {
+            let quotes = market
+                .db()
+                .get_all_quotes_for_ticker(ticker_id)
+                .await
+                .unwrap();
+            assert_eq!(quotes.len(), 5);
+        }
+        assert!(matches!(
+            market.backfill_source_history("yahoo", start, end).await,
+            Err(MarketError::MissingProvider(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn assets_with_stale_quotes() {
+        let db = prepare_db().await;
+        let market = Market::new(Arc::new(db)).await;
+        let eur = market.get_currency_from_str("EUR").await.unwrap();
+        let now = make_time(2021, 11, 5, 18, 0, 0).unwrap();
+        let mut asset_ids = Vec::new();
+        for (name, age) in [("Fresh", Duration::hours(1)), ("Stale", Duration::days(3))] {
+            let stock = Stock::new(None, name.to_string(), None, None, None);
+            let asset_id = market
+                .db()
+                .insert_asset(&Asset::Stock(stock))
+                .await
+                .unwrap();
+            let ticker_id = market
+                .db()
+                .insert_ticker(&make_ticker(asset_id, name, eur))
+                .await
+                .unwrap();
+            market
+                .db()
+                .insert_quote(&Quote {
+                    id: None,
+                    ticker: ticker_id,
+                    price: 100.0,
+                    time: now - age,
+                    volume: None,
+                    stale: false,
+                })
+                .await
+                .unwrap();
+            asset_ids.push(asset_id);
+        }
+
+        let assets = market
+            .assets_needing_update(Duration::days(1), now)
+            .await
+            .unwrap();
+        assert_eq!(assets, vec![asset_ids[1]]);
+    }
+
+    #[tokio::test]
+    async fn detect_quote_anomalies() {
+        let db = prepare_db().await;
+        let market = Market::new(Arc::new(db)).await;
+        let eur = market.get_currency_from_str("EUR").await.unwrap();
+        let stock = Stock::new(None, "Stock".to_string(), None, None, None);
+        let asset_id = market
+            .db()
+            .insert_asset(&Asset::Stock(stock))
+            .await
+            .unwrap();
+        let ticker_id = market
+            .db()
+            .insert_ticker(&make_ticker(asset_id, "STOCK", eur))
+            .await
+            .unwrap();
+        // spike on the 3rd, bad ticks on the 5th and 7th, jump on the 8th
+        let prices = [100.0, 102.0, 250.0, 104.0, 0.0, 105.0, f64::NAN, 200.0];
+        for (day, price) in prices.iter().enumerate() {
+            market
+                .db()
+                .insert_quote(&Quote {
+                    id: None,
+                    ticker: ticker_id,
+                    price: *price,
+                    time: make_time(2021, 11, day as u32 + 1, 18, 0, 0).unwrap(),
+                    volume: None,
+                    stale: false,
+                })
+                .await
+                .unwrap();
+        }
+
+        let time = |day| make_time(2021, 11, day, 18, 0, 0).unwrap();
+        let anomalies = market.validate_quotes(ticker_id).await.unwrap();
+        assert_eq!(
+            anomalies[..3],
+            vec![
+                QuoteAnomaly::Jump {
+                    time: time(3),
+                    price: 250.0,
+                    previous_price: 102.0
+                },
+                QuoteAnomaly::Jump {
+                    time: time(4),
+                    price: 104.0,
+                    previous_price: 250.0
+                },
+                QuoteAnomaly::InvalidPrice {
+                    time: time(5),
+                    price: 0.0
+                },
+            ]
+        );
+        // NaN is never equal to itself, so it can not be compared with assert_eq
+        assert!(matches!(
+            anomalies[3],
+            QuoteAnomaly::InvalidPrice { time: t, price } if t == time(7) && price.is_nan()
+        ));
+        assert_eq!(
+            anomalies[4],
+            QuoteAnomaly::Jump {
+                time: time(8),
+                price: 200.0,
+                previous_price: 105.0
+            }
+        );
+        assert_eq!(anomalies.len(), 5);
+
+        market.set_max_quote_jump(2.0).unwrap();
+        let anomalies = market.validate_quotes(ticker_id).await.unwrap();
+        assert_eq!(anomalies.len(), 2);
+        for max_jump in [-0.1, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                market.set_max_quote_jump(max_jump),
+                Err(MarketError::InvalidQuoteJump(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn deactivate_tickers_without_recent_quotes() {
+        let db = prepare_db().await;
+        let market = Market::new(Arc::new(db)).await;
+        let eur = market.get_currency_from_str("EUR").await.unwrap();
+        let now = make_time(2021, 11, 5, 18, 0, 0).unwrap();
+        let mut ticker_ids = Vec::new();
+        for (name, age) in [
+            ("Fresh", Some(Duration::hours(1))),
+            ("Stale", Some(Duration::days(30))),
+            ("New", None),
+        ] {
+            let stock = Stock::new(None, name.to_string(), None, None, None);
+            let asset_id = market
+                .db()
+                .insert_asset(&Asset::Stock(stock))
+                .await
+                .unwrap();
+            let ticker_id = market
+                .db()
+                .insert_ticker(&make_ticker(asset_id, name, eur))
+                .await
+                .unwrap();
+            if let Some(age) = age {
+                market
+                    .db()
+                    .insert_quote(&Quote {
+                        id: None,
+                        ticker: ticker_id,
+                        price: 100.0,
+                        time: now - age,
+                        volume: None,
+                        stale: false,
+                    })
+                    .await
+                    .unwrap();
+            }
+            ticker_ids.push(ticker_id);
+        }
+
+        let deactivated = market
+            .deactivate_stale_tickers(Duration::days(14), now)
+            .await
+            .unwrap();
+        assert_eq!(deactivated, vec![ticker_ids[1]]);
+        for (ticker_id, active) in ticker_ids.iter().zip([true, false, true]) {
+            let ticker = market.db().get_ticker_by_id(*ticker_id).await.unwrap();
+            assert_eq!(ticker.active, active);
+        }
+        // already inactive tickers are not reported again
+        let deactivated = market
+            .deactivate_stale_tickers(Duration::days(14), now)
+            .await
+            .unwrap();
+        assert!(deactivated.is_empty());
+    }
+
+    /// Provider delivering a single dividend of 0.5 EUR per share
+    struct DividendProvider {
+        currency: Currency,
+    }
+
+    #[async_trait]
+    impl MarketQuoteProvider for DividendProvider {
+        async fn fetch_latest_quote(
+            &self,
+            _ticker: &Ticker,
+        ) -> Result<Quote, market_quotes::MarketQuoteError> {
+            Err(market_quotes::MarketQuoteError::UnexpectedError(
+                "no quotes available".to_string(),
+            ))
+        }
+
+        async fn fetch_quote_history(
+            &self,
+            _ticker: &Ticker,
+            _start: DateTime<Local>,
+            _end: DateTime<Local>,
+        ) -> Result<Vec<Quote>, market_quotes::MarketQuoteError> {
+            Ok(Vec::new())
+        }
+
+        async fn fetch_dividend_history(
+            &self,
+            _ticker: &Ticker,
+            _start: DateTime<Local>,
+            _end: DateTime<Local>,
+        ) -> Result<Vec<CashFlow>, market_quotes::MarketQuoteError> {
+            Ok(vec![CashFlow::new(
+                0.5,
+                self.currency,
+                NaiveDate::from_ymd(2021, 5, 12),
+            )])
+        }
+    }
+
+    #[tokio::test]
+    async fn import_dividends() {
+        let tol = 1e-10;
+        let db = prepare_db().await;
+        let db = Arc::new(db);
+        let market = Market::new(db.clone()).await;
+        let eur = market.get_currency_from_str("EUR").await.unwrap();
+        let stock = Stock::new(None, "BASF".to_string(), None, None, None);
+        let asset_id = db.insert_asset(&Asset::Stock(stock)).await.unwrap();
+        db.insert_ticker(&Ticker {
+            source: "yahoo".to_string(),
+            ..make_ticker(asset_id, "BAS.DE", eur)
+        })
+        .await
+        .unwrap();
+        let start = make_time(2021, 1, 1, 0, 0, 0).unwrap();
+        let end = make_time(2021, 12, 31, 0, 0, 0).unwrap();
+
+        assert!(matches!(
+            market
+                .import_dividends_as_transactions(db.as_ref(), asset_id, |_| 100.0, start, end)
+                .await,
+            Err(MarketError::MissingProvider(_))
+        ));
+
+        market.add_provider(
+            "yahoo".to_string(),
+            Arc::new(DividendProvider { currency: eur }),
+        );
+        let ids = market
+            .import_dividends_as_transactions(db.as_ref(), asset_id, |_| 100.0, start, end)
+            .await
+            .unwrap();
+        assert_eq!(ids.len(), 1);
+        let transaction = db.get_transaction_by_id(ids[0]).await.unwrap();
+        assert!(matches!(
+            transaction.transaction_type,
+            TransactionType::Dividend { asset_id: id } if id == asset_id
+        ));
+        assert_eq!(transaction.cash_flow.date, NaiveDate::from_ymd(2021, 5, 12));
+        assert_eq!(transaction.cash_flow.amount.currency, eur);
+        assert_fuzzy_eq!(transaction.cash_flow.amount.amount, 50.0, tol);
+
+        // no dividend without holdings
+        let ids = market
+            .import_dividends_as_transactions(db.as_ref(), asset_id, |_| 0.0, start, end)
+            .await
+            .unwrap();
+        assert!(ids.is_empty());
+    }
+
+    #[tokio::test]
+    async fn split_adjusted_transactions() {
+        let tol = 1e-10;
+        let db = prepare_db().await;
+        let db = Arc::new(db);
+        let market = Market::new(db.clone()).await;
+        let eur = market.get_currency_from_str("EUR").await.unwrap();
+        let mut asset_ids = Vec::new();
+        for name in ["Apple", "Microsoft"] {
+            let stock = Stock::new(None, name.to_string(), None, None, None);
+            asset_ids.push(db.insert_asset(&Asset::Stock(stock)).await.unwrap());
+        }
+        let mut transaction_ids = Vec::new();
+        for (asset_id, position, amount, day) in [
+            (asset_ids[0], 10.0, -1000.0, 1),
+            (asset_ids[0], -4.0, 420.0, 10),
+            (asset_ids[0], 6.0, -330.0, 20),
+            (asset_ids[1], 10.0, -2000.0, 1),
+        ] {
+            let transaction = Transaction {
+                id: None,
+                transaction_type: TransactionType::Asset { asset_id, position },
+                cash_flow: CashFlow::new(amount, eur, NaiveDate::from_ymd(2021, 6, day)),
+                note: None,
+                time_stamp: None,
+            };
+            transaction_ids.push(db.insert_transaction(&transaction).await.unwrap());
+        }
+
+        let split = StockSplit {
+            date: NaiveDate::from_ymd(2021, 6, 15),
+            ratio: 2.0,
+        };
+        let adjusted = market
+            .split_adjust_transactions(db.as_ref(), asset_ids[0], &[split])
+            .await
+            .unwrap();
+        assert_eq!(adjusted, 2);
+        for (transaction_id, expected_position, expected_amount) in [
+            (transaction_ids[0], 20.0, -1000.0),
+            (transaction_ids[1], -8.0, 420.0),
+            (transaction_ids[2], 6.0, -330.0),
+            (transaction_ids[3], 10.0, -2000.0),
+        ] {
+            let transaction = db.get_transaction_by_id(transaction_id).await.unwrap();
+            match transaction.transaction_type {
+                TransactionType::Asset { position, .. } => {
+                    assert_fuzzy_eq!(position, expected_position, tol)
+                }
+                _ => panic!("expected asset transaction"),
+            }
+            assert_fuzzy_eq!(transaction.cash_flow.amount.amount, expected_amount, tol);
+        }
+
+        // applying the same split again does not change any transaction, while a new split
+        // passed along with it is still applied
+        let adjusted = market
+            .split_adjust_transactions(db.as_ref(), asset_ids[0], &[split])
+            .await
+            .unwrap();
+        assert_eq!(adjusted, 0);
+        let reverse_split = StockSplit {
+            date: NaiveDate::from_ymd_opt(2021, 6, 5).unwrap(),
+            ratio: 0.5,
+        };
+        let adjusted = market
+            .split_adjust_transactions(db.as_ref(), asset_ids[0], &[split, reverse_split])
+            .await
+            .unwrap();
+        assert_eq!(adjusted, 1);
+
+        // splits with invalid ratios are rejected without applying or recording any split
+        for ratio in [0.0, -2.0, f64::NAN, f64::INFINITY] {
+            let invalid_split = StockSplit {
+                date: NaiveDate::from_ymd_opt(2021, 6, 25).unwrap(),
+                ratio,
+            };
+            let result = market
+                .split_adjust_transactions(db.as_ref(), asset_ids[0], &[invalid_split])
+                .await;
+            assert!(matches!(
+                result,
+                Err(MarketError::DBError(DataError::InvalidTransaction(_)))
+            ));
+        }
+        let valid_split = StockSplit {
+            date: NaiveDate::from_ymd_opt(2021, 6, 25).unwrap(),
+            ratio: 3.0,
+        };
+        for (transaction_id, expected_position) in [
+            (transaction_ids[0], 10.0),
+            (transaction_ids[1], -8.0),
+            (transaction_ids[2], 6.0),
+            (transaction_ids[3], 10.0),
+        ] {
+            let transaction = db.get_transaction_by_id(transaction_id).await.unwrap();
+            match transaction.transaction_type {
+                TransactionType::Asset { position, .. } => {
+                    assert_fuzzy_eq!(position, expected_position, tol)
+                }
+                _ => panic!("expected asset transaction"),
+            }
+        }
+        let adjusted = market
+            .split_adjust_transactions(db.as_ref(), asset_ids[0], &[valid_split])
+            .await
+            .unwrap();
+        assert_eq!(adjusted, 3);
+    }
+
+    /// Provider recording the tickers for which latest quotes are requested
+    struct RecordingProvider {
+        requested: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl MarketQuoteProvider for RecordingProvider {
+        async fn fetch_latest_quote(
+            &self,
+            ticker: &Ticker,
+        ) -> Result<Quote, market_quotes::MarketQuoteError> {
+            self.requested.lock().unwrap().push(ticker.name.clone());
+            if ticker.name.starts_with("broken") {
+                return Err(market_quotes::MarketQuoteError::UnexpectedError(
+                    "ticker not available".to_string(),
+                ));
+            }
+            Ok(Quote {
+                id: None,
+                ticker: ticker.id.unwrap(),
+                price: 100.0,
+                time: make_time(2021, 11, 5, 18, 0, 0).unwrap(),
+                volume: None,
+                stale: false,
+            })
+        }
+
+        async fn fetch_quote_history(
+            &self,
+            ticker: &Ticker,
+            _start: DateTime<Local>,
+            _end: DateTime<Local>,
+        ) -> Result<Vec<Quote>, market_quotes::MarketQuoteError> {
+            self.requested.lock().unwrap().push(ticker.name.clone());
+            Ok(Vec::new())
+        }
+
+        async fn fetch_dividend_history(
+            &self,
+            _ticker: &Ticker,
+            _start: DateTime<Local>,
+            _end: DateTime<Local>,
+        ) -> Result<Vec<crate::datatypes::CashFlow>, market_quotes::MarketQuoteError> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn update_quotes_by_ticker_priority() {
+        let db = prepare_db().await;
+        let market = Market::new(Arc::new(db)).await;
+        let provider = Arc::new(RecordingProvider {
+            requested: std::sync::Mutex::new(Vec::new()),
+        });
+        market.add_provider("yahoo".to_string(), provider.clone());
+        let eur = market.get_currency_from_str("EUR").await.unwrap();
+        // tickers are inserted in reverse order of priority
+        for (asset_name, tickers) in [
+            ("Apple", [("AAPL.F", 2), ("AAPL", 1)]),
+            ("Microsoft", [("MSFT.F", 2), ("broken MSFT", 1)]),
+        ] {
+            let stock = Stock::new(None, asset_name.to_string(), None, None, None);
+            let asset_id = market
+                .db()
+                .insert_asset(&Asset::Stock(stock))
+                .await
+                .unwrap();
+            for (name, priority) in tickers {
+                market
+                    .db()
+                    .insert_ticker(&Ticker {
+                        source: "yahoo".to_string(),
+                        priority,
+                        ..make_ticker(asset_id, name, eur)
+                    })
+                    .await
+                    .unwrap();
+            }
+        }
+
+        let failed = market.update_quotes().await.unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(
+            *provider.requested.lock().unwrap(),
+            vec!["AAPL", "AAPL.F", "broken MSFT", "MSFT.F"]
+        );
+
+        // after a successful update, lower priority tickers are skipped
+        provider.requested.lock().unwrap().clear();
+        market.set_skip_redundant_updates(true);
+        market.update_quotes().await.unwrap();
+        assert_eq!(
+            *provider.requested.lock().unwrap(),
+            vec!["AAPL", "broken MSFT", "MSFT.F"]
+        );
+    }
+
+    #[tokio::test]
+    async fn inactive_tickers_are_not_updated() {
+        let db = prepare_db().await;
+        let market = Market::new(Arc::new(db)).await;
+        let provider = Arc::new(RecordingProvider {
+            requested: std::sync::Mutex::new(Vec::new()),
+        });
+        market.add_provider("yahoo".to_string(), provider.clone());
+        let eur = market.get_currency_from_str("EUR").await.unwrap();
+        let mut asset_ids = Vec::new();
+        let mut ticker_ids = Vec::new();
+        for name in ["AAPL", "delisted"] {
+            let stock = Stock::new(None, name.to_string(), None, None, None);
+            let asset_id = market
+                .db()
+                .insert_asset(&Asset::Stock(stock))
+                .await
+                .unwrap();
+            asset_ids.push(asset_id);
+            let ticker_id = market
+                .db()
+                .insert_ticker(&Ticker {
+                    source: "yahoo".to_string(),
+                    ..make_ticker(asset_id, name, eur)
+                })
+                .await
+                .unwrap();
+            ticker_ids.push(ticker_id);
+        }
+
+        market.db().deactivate_ticker(ticker_ids[1]).await.unwrap();
+        let delisted = market.db().get_ticker_by_id(ticker_ids[1]).await.unwrap();
+        assert!(!delisted.active);
+        let failed = market.update_quotes().await.unwrap();
+        assert!(failed.is_empty());
+        assert_eq!(*provider.requested.lock().unwrap(), vec!["AAPL"]);
+
+        let start = make_time(2021, 11, 1, 0, 0, 0).unwrap();
+        let end = make_time(2021, 11, 5, 0, 0, 0).unwrap();
+        let now = make_time(2021, 11, 10, 0, 0, 0).unwrap();
+        provider.requested.lock().unwrap().clear();
+        let failed = market
+            .backfill_source_history("yahoo", start, end)
+            .await
+            .unwrap();
+        assert!(failed.is_empty());
+        assert_eq!(*provider.requested.lock().unwrap(), vec!["AAPL"]);
+        let assets = market
+            .assets_needing_update(Duration::days(1), now)
+            .await
+            .unwrap();
+        assert_eq!(assets, vec![asset_ids[0]]);
+
+        provider.requested.lock().unwrap().clear();
+        market.set_include_inactive_tickers(true);
+        market.update_quotes().await.unwrap();
+        assert_eq!(
+            *provider.requested.lock().unwrap(),
+            vec!["AAPL", "delisted"]
+        );
+        provider.requested.lock().unwrap().clear();
+        market
+            .backfill_source_history("yahoo", start, end)
+            .await
+            .unwrap();
+        assert_eq!(
+            *provider.requested.lock().unwrap(),
+            vec!["AAPL", "delisted"]
+        );
+        let assets = market
+            .assets_needing_update(Duration::days(1), now)
+            .await
+            .unwrap();
+        assert_eq!(assets, asset_ids);
+    }
+
+    #[tokio::test]
+    async fn value_stored_bond() {
+        use crate::rates::{Compounding, FlatRate};
+
+        let db = Arc::new(prepare_db().await);
+        let market = Market::new(db.clone()).await;
+        let eur = market.get_currency_from_str("EUR").await.unwrap();
+
+        let data = r#"{
+            "isin": "DE0001234567",
+            "currency": "EUR",
+            "face": 1000,
+            "coupon_rate": 4,
+            "frequency": "6M",
+            "issue": "2021-04-15",
+            "maturity": "2023-04-15",
+            "dcc": "icma",
+            "first_coupon": null,
+            "business_day_adjustment": "modified",
+            "calendar": "TARGET"
+        }"#;
+        let spec: BondSpec = serde_json::from_str(data).unwrap();
+        market
+            .store_bond_spec(db.as_ref(), 42, &spec)
+            .await
+            .unwrap();
+
+        let valuation_date = NaiveDate::from_ymd(2022, 1, 3);
+        let curve = FlatRate::new(0.03, DayCountConv::Act365, Compounding::Annual, eur);
+        let value = market
+            .value_bond(db.as_ref(), 42, valuation_date, &curve)
+            .await
+            .unwrap();
+        // remaining payments after the first coupon on 2021-10-15
+        let payments = [
+            (NaiveDate::from_ymd(2022, 4, 19), 20.),
+            (NaiveDate::from_ymd(2022, 10, 17), 20.),
+            (NaiveDate::from_ymd(2023, 4, 17), 1020.),
+        ];
+        let reference: f64 = payments
+            .iter()
+            .map(|(date, amount)| {
+                let t = (*date - valuation_date).num_days() as f64 / 365.;
+                amount / 1.03_f64.powf(t)
+            })
+            .sum();
+        assert_fuzzy_eq!(value, reference, 1e-8);
+
+        // valuation fails if no specification is stored for the asset
+        assert!(market
+            .value_bond(db.as_ref(), 43, valuation_date, &curve)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn nearest_quote_in_either_direction() {
+        let db = prepare_db().await;
+
+        let eur = Currency::new(None, CurrencyISOCode::new("EUR").unwrap(), None);
+        let eur_id = db.insert_asset(&Asset::Currency(eur)).await.unwrap();
+        let eur = Currency::new(Some(eur_id), eur.iso_code, None);
+        let stock = Stock::new(None, "Stock".to_string(), None, None, None);
+        let asset_id = db.insert_asset(&Asset::Stock(stock)).await.unwrap();
+        let ticker_id = db
+            .insert_ticker(&make_ticker(asset_id, "STOCK", eur))
+            .await
+            .unwrap();
+        // a week old quote before and a quote on the next day after the holiday
+        for (day, price) in [(1, 100.0), (9, 110.0)].iter() {
+            db.insert_quote(&Quote {
+                id: None,
+                ticker: ticker_id,
+                price: *price,
+                time: make_time(2021, 4, *day, 20, 0, 0).unwrap(),
+                volume: None,
+                stale: false,
+            })
+            .await
+            .unwrap();
+        }
+        let market = Market::new(Arc::new(db)).await;
+
+        let holiday = make_time(2021, 4, 8, 20, 0, 0).unwrap();
+        let quote = market
+            .nearest_quote(asset_id, holiday, Duration::days(10))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(quote.price, 110.0);
+        // quotes outside of the window are ignored
+        let quote = market
+            .nearest_quote(asset_id, holiday, Duration::hours(12))
+            .await
+            .unwrap();
+        assert!(quote.is_none());
+        // the earlier quote is used if it is the only one within the window
+        let time = make_time(2021, 4, 2, 8, 0, 0).unwrap();
+        let quote = market
+            .nearest_quote(asset_id, time, Duration::days(3))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(quote.price, 100.0);
+    }
+}
@@ -31,6 +31,7 @@ impl MarketQuoteProvider for AlphaVantage {
             price: alpha_quote.price(),
             time,
             volume: Some(alpha_quote.volume() as f64),
+            stale: false,
         })
     }
     /// Fetch historic quotes between start and end date
@@ -56,6 +57,7 @@ impl MarketQuoteProvider for AlphaVantage {
                     price: quote.close(),
                     time,
                     volume: Some(quote.volume() as f64),
+                    stale: false,
                 })
             }
         }
@@ -99,6 +101,7 @@ mod tests {
             factor: 1.0,
             tz: None,
             cal: None,
+            active: true,
         };
         let quote = alpha.fetch_latest_quote(&ticker).await.unwrap();
         assert!(quote.price != 0.0);
@@ -118,6 +121,7 @@ mod tests {
             factor: 1.0,
             tz: None,
             cal: None,
+            active: true,
         };
         let start = Local.ymd(2020, 1, 1).and_hms_milli(0, 0, 0, 0);
         let end = Local.ymd(3000, 1, 31).and_hms_milli(23, 59, 59, 999);
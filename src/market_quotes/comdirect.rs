@@ -1,5 +1,5 @@
 /// A tool to fetch prices by parsing comdirect web page
-use super::{MarketQuoteError, MarketQuoteProvider};
+use super::{log_response_body, MarketQuoteError, MarketQuoteProvider};
 use crate::datatypes::{date_time_helper::date_time_from_str, CashFlow, Quote, Ticker};
 use async_trait::async_trait;
 use chrono::{DateTime, Local};
@@ -41,6 +41,7 @@ impl Comdirect {
         }
 
         let body = resp.text().await?;
+        log_response_body("comdirect", &body);
         // parses string of HTML as a document
         let fragment = Html::parse_document(&body);
         // parses based on a CSS selector
@@ -81,6 +82,7 @@ impl Comdirect {
         }
 
         let body = resp.text().await?;
+        log_response_body("comdirect", &body);
 
         Self::parse_csv(&body)
     }
@@ -152,6 +154,7 @@ impl MarketQuoteProvider for Comdirect {
             price,
             time,
             volume: None,
+            stale: false,
         })
     }
     /// Fetch historic quotes between start and end date
@@ -172,6 +175,7 @@ impl MarketQuoteProvider for Comdirect {
                 price: quote.close,
                 time: quote.date,
                 volume: quote.volume,
+                stale: false,
             })
         }
         Ok(quotes)
@@ -212,6 +216,7 @@ mod tests {
             factor: 1.0,
             tz: None,
             cal: None,
+            active: true,
         };
         let quote = codi.fetch_latest_quote(&ticker).await.unwrap();
         assert!(quote.price != 0.0);
@@ -231,6 +236,7 @@ mod tests {
             factor: 1.0,
             tz: None,
             cal: None,
+            active: true,
         };
         let start = Local.ymd(2020, 1, 1).and_hms_milli(0, 0, 0, 0);
         let end = Local.ymd(2020, 1, 31).and_hms_milli(23, 59, 59, 999);
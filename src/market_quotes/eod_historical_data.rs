@@ -37,6 +37,7 @@ impl MarketQuoteProvider for EODHistData {
             price: eod_quote.close,
             time,
             volume: Some(eod_quote.volume as f64),
+            stale: false,
         })
     }
 
@@ -67,6 +68,7 @@ impl MarketQuoteProvider for EODHistData {
                     price,
                     time,
                     volume,
+                    stale: false,
                 })
             }
         }
@@ -121,6 +123,7 @@ mod tests {
             factor: 1.0,
             tz: None,
             cal: None,
+            active: true,
         };
         let quote = eod.fetch_latest_quote(&ticker).await.unwrap();
         assert!(quote.price != 0.0);
@@ -140,6 +143,7 @@ mod tests {
             factor: 1.0,
             tz: None,
             cal: None,
+            active: true,
         };
         let start = Local.ymd(2020, 1, 1).and_hms_milli(0, 0, 0, 0);
         let end = Local.ymd(2020, 1, 31).and_hms_milli(23, 59, 59, 999);
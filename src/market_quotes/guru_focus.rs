@@ -41,6 +41,7 @@ impl MarketQuoteProvider for GuruFocus {
             price: quote.price.into(),
             time,
             volume: Some(quote.todays_volume.into()),
+            stale: false,
         })
     }
     /// Fetch historic quotes between start and end date
@@ -66,6 +67,7 @@ impl MarketQuoteProvider for GuruFocus {
                 price: *price,
                 time,
                 volume: None,
+                stale: false,
             })
         }
         Ok(quotes)
@@ -116,6 +118,7 @@ mod tests {
             factor: 1.0,
             tz: None,
             cal: None,
+            active: true,
         };
         let quote = gf.fetch_latest_quote(&ticker).await.unwrap();
         assert!(quote.price != 0.0);
@@ -135,6 +138,7 @@ mod tests {
             factor: 1.0,
             tz: None,
             cal: None,
+            active: true,
         };
         let start = Local.ymd(2020, 1, 1).and_hms_milli(0, 0, 0, 0);
         let end = Local.ymd(2020, 1, 31).and_hms_milli(23, 59, 59, 999);
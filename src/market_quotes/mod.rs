@@ -1,12 +1,14 @@
 use std::fmt;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use crate::datatypes::{CashFlow, Quote, QuoteHandler, Ticker};
 use alpha_vantage;
 use async_trait::async_trait;
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Duration, Local, NaiveDate};
 use gurufocus_api;
+use log::{debug, trace, warn};
 use serde::{Deserialize, Serialize};
 use serde_json;
 use thiserror::Error;
@@ -15,8 +17,25 @@ pub mod alpha_vantage_wrapper;
 pub mod comdirect;
 pub mod eod_historical_data;
 pub mod guru_focus;
+pub mod stats;
 pub mod yahoo;
 
+/// Flag to enable logging of raw response bodies received from market data providers
+static LOG_RESPONSE_BODIES: AtomicBool = AtomicBool::new(false);
+
+/// Enable or disable logging (at trace level) of raw response bodies received from market
+/// data providers. This is only supported by providers that parse the responses themselves.
+pub fn set_log_response_bodies(enabled: bool) {
+    LOG_RESPONSE_BODIES.store(enabled, Ordering::Relaxed);
+}
+
+/// Log response body received from a market data provider, if enabled
+pub(crate) fn log_response_body(source: &str, body: &str) {
+    if LOG_RESPONSE_BODIES.load(Ordering::Relaxed) {
+        trace!("response body received from {}: {}", source, body);
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum MarketQuoteError {
     #[error("Storing quote in database failed")]
@@ -41,10 +60,66 @@ pub enum MarketQuoteError {
     GuruFocusError(#[from] gurufocus_api::GuruFocusError),
     #[error("JSON parsing error")]
     JSONError(#[from] serde_json::Error),
+    #[error("Quotes in currency '{0}' requested, but provider delivers quotes in '{1}'")]
+    CurrencyMismatch(String, String),
     #[error("Unexpected error: '{0}'")]
     UnexpectedError(String),
 }
 
+/// Check that the currency reported by a market data provider matches the ticker's currency.
+/// Some providers report quotes in pence as `GBp` or `GBX`, which are accepted for tickers
+/// in GBP with a factor of 0.01 only.
+pub(crate) fn check_quote_currency(
+    ticker: &Ticker,
+    currency: &str,
+) -> Result<(), MarketQuoteError> {
+    let expected = ticker.currency.to_string();
+    let is_pence = (currency == "GBp" || currency == "GBX") && ticker.factor == 0.01;
+    if currency == expected || (expected == "GBP" && is_pence) {
+        Ok(())
+    } else {
+        Err(MarketQuoteError::CurrencyMismatch(
+            expected,
+            currency.to_string(),
+        ))
+    }
+}
+
+/// Type of an option contract
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum OptionType {
+    Call,
+    Put,
+}
+
+/// Quote of a single option contract of an option chain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OptionQuote {
+    /// Id of the ticker of the underlying
+    pub ticker_id: i32,
+    /// Symbol of the underlying as used by the market data provider
+    pub underlying_ticker: String,
+    pub option_type: OptionType,
+    pub strike: f64,
+    pub expiry: NaiveDate,
+    pub bid: f64,
+    pub ask: f64,
+    pub last: f64,
+    pub open_interest: f64,
+    pub implied_vol: Option<f64>,
+}
+
+/// Field of daily OHLC data that is used as quote price by providers delivering full OHLC bars
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PriceField {
+    Open,
+    High,
+    Low,
+    #[default]
+    Close,
+    AdjClose,
+}
+
 /// General interface for market data quotes provider
 #[async_trait]
 pub trait MarketQuoteProvider: Send + Sync {
@@ -65,15 +140,66 @@ pub trait MarketQuoteProvider: Send + Sync {
         start: DateTime<Local>,
         end: DateTime<Local>,
     ) -> Result<Vec<CashFlow>, MarketQuoteError>;
+
+    /// Fetch option chain (all strikes for all or only the given expiry) of the underlying
+    async fn fetch_option_chain(
+        &self,
+        _underlying_ticker: &Ticker,
+        _expiry: Option<NaiveDate>,
+    ) -> Result<Vec<OptionQuote>, MarketQuoteError> {
+        Err(MarketQuoteError::UnexpectedError(
+            "option chain not supported".to_string(),
+        ))
+    }
+}
+
+/// Handling of stale quotes in `update_ticker`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StaleQuotePolicy {
+    /// Stale quotes are not stored
+    Skip,
+    /// Stale quotes are stored with the `stale` flag set
+    Flag,
 }
 
+/// Fetch latest quote of ticker and store it in the database
+/// If `max_age` is given, quotes older than `max_age` are considered to be stale and
+/// are handled according to the given policy.
 pub async fn update_ticker<'a>(
     provider: Arc<dyn MarketQuoteProvider + Send + Sync + 'a>,
     ticker: &Ticker,
     db: Arc<dyn QuoteHandler + Send + Sync + 'a>,
+    max_age: Option<Duration>,
+    stale_policy: StaleQuotePolicy,
 ) -> Result<(), MarketQuoteError> {
-    let mut quote = provider.fetch_latest_quote(ticker).await?;
-    quote.price *= ticker.factor;
+    debug!(
+        "fetching latest quote for ticker {} from {}",
+        ticker.name, ticker.source
+    );
+    let mut quote = provider.fetch_latest_quote(ticker).await.map_err(|err| {
+        warn!(
+            "fetching latest quote for ticker {} from {} failed: {}",
+            ticker.name, ticker.source, err
+        );
+        err
+    })?;
+    debug!(
+        "received quote for ticker {} from {}: {} at {}",
+        ticker.name, ticker.source, quote.price, quote.time
+    );
+    if let Some(max_age) = max_age {
+        if Local::now() - quote.time > max_age {
+            if stale_policy == StaleQuotePolicy::Skip {
+                debug!(
+                    "skipping stale quote for ticker {} from {} at {}",
+                    ticker.name, ticker.source, quote.time
+                );
+                return Ok(());
+            }
+            quote.stale = true;
+        }
+    }
+    quote.price = ticker.apply_factor(quote.price);
     db.insert_quote(&quote).await?;
     Ok(())
 }
@@ -85,9 +211,30 @@ pub async fn update_ticker_history<'a>(
     start: DateTime<Local>,
     end: DateTime<Local>,
 ) -> Result<(), MarketQuoteError> {
-    let mut quotes = provider.fetch_quote_history(ticker, start, end).await?;
+    debug!(
+        "fetching quote history for ticker {} from {} between {} and {}",
+        ticker.name, ticker.source, start, end
+    );
+    let mut quotes = provider
+        .fetch_quote_history(ticker, start, end)
+        .await
+        .map_err(|err| {
+            warn!(
+                "fetching quote history for ticker {} from {} between {} and {} failed: {}",
+                ticker.name, ticker.source, start, end, err
+            );
+            err
+        })?;
+    debug!(
+        "received {} quotes for ticker {} from {} between {} and {}",
+        quotes.len(),
+        ticker.name,
+        ticker.source,
+        start,
+        end
+    );
     for mut quote in &mut quotes {
-        quote.price *= ticker.factor;
+        quote.price = ticker.apply_factor(quote.price);
         db.insert_quote(quote).await?;
     }
     Ok(())
@@ -144,7 +291,7 @@ impl MarketDataSource {
         token: String,
     ) -> Option<Arc<dyn MarketQuoteProvider + Send + Sync>> {
         match self {
-            Self::Yahoo => Some(Arc::new(yahoo::Yahoo {})),
+            Self::Yahoo => Some(Arc::new(yahoo::Yahoo::new())),
             Self::GuruFocus => Some(Arc::new(guru_focus::GuruFocus::new(token))),
             Self::EodHistData => Some(Arc::new(eod_historical_data::EODHistData::new(token))),
             Self::AlphaVantage => Some(Arc::new(alpha_vantage_wrapper::AlphaVantage::new(token))),
@@ -173,10 +320,10 @@ mod tests {
     use super::*;
 
     use chrono::offset::TimeZone;
-    use chrono::{Duration, Local};
     use rand::Rng;
 
-    use crate::datatypes::{Asset, CurrencyISOCode, QuoteHandler, Stock};
+    use crate::datatypes::{Asset, AssetHandler, CurrencyISOCode, QuoteHandler, Stock};
+    use crate::market::Market;
     use crate::postgres::PostgresDB;
 
     struct DummyProvider {}
@@ -190,6 +337,7 @@ mod tests {
                 price: 1.23,
                 time: Local.ymd(2020, 1, 1).and_hms_milli(0, 0, 0, 0),
                 volume: None,
+                stale: false,
             })
         }
 
@@ -210,6 +358,7 @@ mod tests {
                     price,
                     time: date,
                     volume: None,
+                    stale: false,
                 });
                 date = date + Duration::days(1);
                 price *= (0.0001 + 0.2 * rng.gen::<f64>()).exp();
@@ -253,6 +402,7 @@ mod tests {
             factor: 1.0,
             tz: None,
             cal: None,
+            active: true,
         };
         let ticker_id = db.insert_ticker(&ticker).await.unwrap();
         ticker.id = Some(ticker_id);
@@ -273,8 +423,10 @@ mod tests {
 
         let db = Arc::new(db);
         let ticker = prepare_db(db.clone()).await;
-        let provider = DummyProvider {};
-        update_ticker(&provider, &ticker, db.clone()).await.unwrap();
+        let provider = Arc::new(DummyProvider {});
+        update_ticker(provider, &ticker, db.clone(), None, StaleQuotePolicy::Skip)
+            .await
+            .unwrap();
         let quotes = db
             .get_all_quotes_for_ticker(ticker.id.unwrap())
             .await
@@ -297,10 +449,10 @@ mod tests {
 
         let db = Arc::new(db);
         let ticker = prepare_db(db.clone()).await;
-        let provider = DummyProvider {};
+        let provider = Arc::new(DummyProvider {});
         let start = Local.ymd(2020, 1, 1).and_hms_milli(0, 0, 0, 0);
         let end = Local.ymd(2020, 1, 31).and_hms_milli(23, 59, 59, 999);
-        update_ticker_history(&provider, &ticker, db.clone(), start, end)
+        update_ticker_history(provider, &ticker, db.clone(), start, end)
             .await
             .unwrap();
         let quotes = db
@@ -310,4 +462,395 @@ mod tests {
         assert_eq!(quotes.len(), 31);
         assert_fuzzy_eq!(quotes[0].price, 1.23, tol);
     }
+
+    #[tokio::test]
+    async fn test_ticker_factor() {
+        let tol = 1.0e-10;
+
+        let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
+        assert!(
+            db_url.is_ok(),
+            "environment variable $FINQL_TEST_DATABASE_URL is not set"
+        );
+        let db = PostgresDB::new(&db_url.unwrap()).await.unwrap();
+        db.clean().await.unwrap();
+
+        let db = Arc::new(db);
+        let mut ticker = prepare_db(db.clone()).await;
+        // Provider delivers prices in cent
+        ticker.factor = 0.01;
+        db.update_ticker(&ticker).await.unwrap();
+        let provider = Arc::new(DummyProvider {});
+        update_ticker(provider, &ticker, db.clone(), None, StaleQuotePolicy::Skip)
+            .await
+            .unwrap();
+        let quotes = db
+            .get_all_quotes_for_ticker(ticker.id.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(quotes.len(), 1);
+        assert_fuzzy_eq!(quotes[0].price, 0.0123, tol);
+        assert_fuzzy_eq!(ticker.remove_factor(quotes[0].price), 1.23, tol);
+
+        // valuation uses the stored price without scaling it again
+        let market = Market::new(db.clone()).await;
+        let price = market
+            .get_asset_price(ticker.asset, ticker.currency, Local::now())
+            .await
+            .unwrap();
+        assert_fuzzy_eq!(price, quotes[0].price, tol);
+    }
+
+    /// Logger collecting all log messages for inspection in tests
+    struct TestLogger {
+        records: std::sync::Mutex<Vec<(log::Level, String)>>,
+    }
+
+    impl log::Log for TestLogger {
+        fn enabled(&self, _metadata: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            if let Ok(mut records) = self.records.lock() {
+                records.push((record.level(), record.args().to_string()));
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static TEST_LOGGER: TestLogger = TestLogger {
+        records: std::sync::Mutex::new(Vec::new()),
+    };
+
+    fn logged(level: log::Level, pattern: &str) -> bool {
+        TEST_LOGGER
+            .records
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|(l, msg)| *l == level && msg.contains(pattern))
+    }
+
+    #[tokio::test]
+    async fn test_logging_of_fetches() {
+        let _ = log::set_logger(&TEST_LOGGER);
+        log::set_max_level(log::LevelFilter::Trace);
+
+        let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
+        assert!(
+            db_url.is_ok(),
+            "environment variable $FINQL_TEST_DATABASE_URL is not set"
+        );
+        let db = PostgresDB::new(&db_url.unwrap()).await.unwrap();
+        db.clean().await.unwrap();
+
+        let db = Arc::new(db);
+        let mut ticker = prepare_db(db.clone()).await;
+        ticker.name = "LoggedTicker".to_string();
+        let provider = Arc::new(DummyProvider {});
+        update_ticker(
+            provider.clone(),
+            &ticker,
+            db.clone(),
+            None,
+            StaleQuotePolicy::Skip,
+        )
+        .await
+        .unwrap();
+        assert!(logged(
+            log::Level::Debug,
+            "fetching latest quote for ticker LoggedTicker from manual"
+        ));
+        assert!(logged(
+            log::Level::Debug,
+            "received quote for ticker LoggedTicker from manual: 1.23"
+        ));
+
+        let start = Local.ymd(2020, 1, 1).and_hms_milli(0, 0, 0, 0);
+        let end = Local.ymd(2020, 1, 31).and_hms_milli(23, 59, 59, 999);
+        update_ticker_history(provider, &ticker, db.clone(), start, end)
+            .await
+            .unwrap();
+        assert!(logged(
+            log::Level::Debug,
+            "fetching quote history for ticker LoggedTicker from manual"
+        ));
+        assert!(logged(
+            log::Level::Debug,
+            "quotes for ticker LoggedTicker from manual"
+        ));
+
+        log_response_body("manual", "hidden body");
+        assert!(!logged(log::Level::Trace, "hidden body"));
+        set_log_response_bodies(true);
+        log_response_body("manual", "visible body");
+        set_log_response_bodies(false);
+        assert!(logged(
+            log::Level::Trace,
+            "response body received from manual: visible body"
+        ));
+    }
+
+    /// Provider returning quotes of a given age
+    struct AgedQuoteProvider {
+        age: Duration,
+    }
+
+    #[async_trait]
+    impl MarketQuoteProvider for AgedQuoteProvider {
+        async fn fetch_latest_quote(&self, ticker: &Ticker) -> Result<Quote, MarketQuoteError> {
+            Ok(Quote {
+                id: None,
+                ticker: ticker.id.unwrap(),
+                price: 1.23,
+                time: Local::now() - self.age,
+                volume: None,
+                stale: false,
+            })
+        }
+
+        async fn fetch_quote_history(
+            &self,
+            _ticker: &Ticker,
+            _start: DateTime<Local>,
+            _end: DateTime<Local>,
+        ) -> Result<Vec<Quote>, MarketQuoteError> {
+            Ok(Vec::new())
+        }
+
+        async fn fetch_dividend_history(
+            &self,
+            _ticker: &Ticker,
+            _start: DateTime<Local>,
+            _end: DateTime<Local>,
+        ) -> Result<Vec<CashFlow>, MarketQuoteError> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_skip_stale_quotes() {
+        let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
+        assert!(
+            db_url.is_ok(),
+            "environment variable $FINQL_TEST_DATABASE_URL is not set"
+        );
+        let db = PostgresDB::new(&db_url.unwrap()).await.unwrap();
+        db.clean().await.unwrap();
+
+        let db = Arc::new(db);
+        let ticker = prepare_db(db.clone()).await;
+        let stale_provider = Arc::new(AgedQuoteProvider {
+            age: Duration::days(3),
+        });
+        update_ticker(
+            stale_provider.clone(),
+            &ticker,
+            db.clone(),
+            Some(Duration::days(1)),
+            StaleQuotePolicy::Skip,
+        )
+        .await
+        .unwrap();
+        let quotes = db
+            .get_all_quotes_for_ticker(ticker.id.unwrap())
+            .await
+            .unwrap();
+        assert!(quotes.is_empty());
+
+        let fresh_provider = Arc::new(AgedQuoteProvider {
+            age: Duration::hours(1),
+        });
+        update_ticker(
+            fresh_provider,
+            &ticker,
+            db.clone(),
+            Some(Duration::days(1)),
+            StaleQuotePolicy::Skip,
+        )
+        .await
+        .unwrap();
+        // without freshness window, stale quotes are stored as well
+        update_ticker(
+            stale_provider,
+            &ticker,
+            db.clone(),
+            None,
+            StaleQuotePolicy::Skip,
+        )
+        .await
+        .unwrap();
+        let quotes = db
+            .get_all_quotes_for_ticker(ticker.id.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(quotes.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_flag_stale_quotes() {
+        let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
+        assert!(
+            db_url.is_ok(),
+            "environment variable $FINQL_TEST_DATABASE_URL is not set"
+        );
+        let db = PostgresDB::new(&db_url.unwrap()).await.unwrap();
+        db.clean().await.unwrap();
+
+        let db = Arc::new(db);
+        let ticker = prepare_db(db.clone()).await;
+        for age in [Duration::days(3), Duration::hours(1)] {
+            update_ticker(
+                Arc::new(AgedQuoteProvider { age }),
+                &ticker,
+                db.clone(),
+                Some(Duration::days(1)),
+                StaleQuotePolicy::Flag,
+            )
+            .await
+            .unwrap();
+        }
+        let quotes = db
+            .get_all_quotes_for_ticker(ticker.id.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(quotes.len(), 2);
+        // quotes are sorted by time, i.e. the backfilled quote comes first
+        assert!(quotes[0].stale);
+        assert!(!quotes[1].stale);
+    }
+
+    /// Crypto exchange mock quoting BTC in USD and EUR only
+    struct CryptoProvider {}
+
+    #[async_trait]
+    impl MarketQuoteProvider for CryptoProvider {
+        async fn fetch_latest_quote(&self, ticker: &Ticker) -> Result<Quote, MarketQuoteError> {
+            let price = match ticker.currency.to_string().as_str() {
+                "USD" => 48000.0,
+                "EUR" => 41000.0,
+                other => {
+                    return Err(MarketQuoteError::CurrencyMismatch(
+                        other.to_string(),
+                        "USD".to_string(),
+                    ))
+                }
+            };
+            Ok(Quote {
+                id: None,
+                ticker: ticker.id.unwrap(),
+                price,
+                time: Local.ymd(2021, 11, 1).and_hms_milli(12, 0, 0, 0),
+                volume: None,
+                stale: false,
+            })
+        }
+
+        async fn fetch_quote_history(
+            &self,
+            ticker: &Ticker,
+            _start: DateTime<Local>,
+            _end: DateTime<Local>,
+        ) -> Result<Vec<Quote>, MarketQuoteError> {
+            Ok(vec![self.fetch_latest_quote(ticker).await?])
+        }
+
+        async fn fetch_dividend_history(
+            &self,
+            _ticker: &Ticker,
+            _start: DateTime<Local>,
+            _end: DateTime<Local>,
+        ) -> Result<Vec<CashFlow>, MarketQuoteError> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_crypto_quote_currency() {
+        let tol = 1.0e-10;
+
+        let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
+        assert!(
+            db_url.is_ok(),
+            "environment variable $FINQL_TEST_DATABASE_URL is not set"
+        );
+        let db = PostgresDB::new(&db_url.unwrap()).await.unwrap();
+        db.clean().await.unwrap();
+
+        let db = Arc::new(db);
+        let btc_id = db
+            .insert_asset(&Asset::Stock(Stock::new(
+                None,
+                "Bitcoin".to_string(),
+                None,
+                None,
+                None,
+            )))
+            .await
+            .unwrap();
+        let eur = db
+            .get_or_new_currency(CurrencyISOCode::new("EUR").unwrap())
+            .await
+            .unwrap();
+        let mut ticker = Ticker {
+            id: None,
+            asset: btc_id,
+            name: "BTC-EUR".to_string(),
+            currency: eur,
+            source: "manual".to_string(),
+            priority: 1,
+            factor: 1.0,
+            tz: None,
+            cal: None,
+            active: true,
+        };
+        ticker.id = Some(db.insert_ticker(&ticker).await.unwrap());
+        let provider = Arc::new(CryptoProvider {});
+        update_ticker(
+            provider.clone(),
+            &ticker,
+            db.clone(),
+            None,
+            StaleQuotePolicy::Skip,
+        )
+        .await
+        .unwrap();
+        let market = Market::new(db.clone()).await;
+        let price = market
+            .get_asset_price(btc_id, eur, Local.ymd(2021, 11, 2).and_hms(0, 0, 0))
+            .await
+            .unwrap();
+        assert_fuzzy_eq!(price, 41000.0, tol);
+
+        // currencies not offered by the provider are rejected
+        let jpy = db
+            .get_or_new_currency(CurrencyISOCode::new("JPY").unwrap())
+            .await
+            .unwrap();
+        ticker.currency = jpy;
+        let result =
+            update_ticker(provider, &ticker, db.clone(), None, StaleQuotePolicy::Skip).await;
+        assert!(matches!(
+            result,
+            Err(MarketQuoteError::CurrencyMismatch(_, _))
+        ));
+
+        assert!(check_quote_currency(&ticker, "JPY").is_ok());
+        assert!(check_quote_currency(&ticker, "USD").is_err());
+        assert!(check_quote_currency(&ticker, "jpy").is_err());
+
+        // quotes in pence are accepted for GBP tickers with factor 0.01 only
+        ticker.currency = db
+            .get_or_new_currency(CurrencyISOCode::new("GBP").unwrap())
+            .await
+            .unwrap();
+        assert!(check_quote_currency(&ticker, "GBP").is_ok());
+        assert!(check_quote_currency(&ticker, "GBp").is_err());
+        ticker.factor = 0.01;
+        assert!(check_quote_currency(&ticker, "GBp").is_ok());
+        assert!(check_quote_currency(&ticker, "GBX").is_ok());
+        assert!(check_quote_currency(&ticker, "gbp").is_err());
+    }
 }
@@ -0,0 +1,136 @@
+//! Statistics on the quotes stored in the database
+//! Provides a quick overview on the coverage of the quote history per ticker.
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::datatypes::{DataError, QuoteHandler};
+
+/// Number and time range of quotes stored for a ticker
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TickerQuoteCoverage {
+    pub ticker_id: i32,
+    pub ticker_name: String,
+    pub quote_count: usize,
+    pub first_quote: Option<DateTime<Local>>,
+    pub last_quote: Option<DateTime<Local>>,
+    /// Average gap between subsequent quotes in days, reduced by one day,
+    /// i.e. zero for daily quotes without gaps. Zero if there are less than two quotes.
+    pub gap_days: f64,
+}
+
+impl TickerQuoteCoverage {
+    /// Construct quote coverage and calculate the average gap between quotes
+    pub fn new(
+        ticker_id: i32,
+        ticker_name: String,
+        quote_count: usize,
+        first_quote: Option<DateTime<Local>>,
+        last_quote: Option<DateTime<Local>>,
+    ) -> TickerQuoteCoverage {
+        let gap_days = match (first_quote, last_quote) {
+            (Some(first), Some(last)) if quote_count > 1 => {
+                (last - first).num_days() as f64 / (quote_count - 1) as f64 - 1.0
+            }
+            _ => 0.0,
+        };
+        TickerQuoteCoverage {
+            ticker_id,
+            ticker_name,
+            quote_count,
+            first_quote,
+            last_quote,
+            gap_days,
+        }
+    }
+}
+
+/// Get quote coverage statistics for all tickers
+pub async fn get_quote_coverage_stats(
+    db: Arc<dyn QuoteHandler + Send + Sync>,
+) -> Result<Vec<TickerQuoteCoverage>, DataError> {
+    db.get_quote_coverage_for_all_tickers().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datatypes::{
+        date_time_helper::make_time, Asset, AssetHandler, Currency, CurrencyISOCode, Quote, Stock,
+        Ticker,
+    };
+    use crate::postgres::PostgresDB;
+
+    #[tokio::test]
+    async fn quote_coverage_stats() {
+        let tol = 1e-11;
+        let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
+        assert!(
+            db_url.is_ok(),
+            "environment variable $FINQL_TEST_DATABASE_URL is not set"
+        );
+        let db = PostgresDB::new(&db_url.unwrap()).await.unwrap();
+        db.clean().await.unwrap();
+
+        let eur = Currency::new(None, CurrencyISOCode::new("EUR").unwrap(), None);
+        let eur_id = db.insert_asset(&Asset::Currency(eur)).await.unwrap();
+        let eur = Currency::new(Some(eur_id), eur.iso_code, None);
+        let stock = Stock::new(None, "Stock".to_string(), None, None, None);
+        let asset_id = db.insert_asset(&Asset::Stock(stock)).await.unwrap();
+        // daily quotes, no quotes, and quotes every other day
+        for (i, (count, step)) in [(10, 1), (0, 1), (5, 2)].iter().enumerate() {
+            let ticker_id = db
+                .insert_ticker(&Ticker {
+                    id: None,
+                    asset: asset_id,
+                    name: format!("TICKER{}", i),
+                    currency: eur,
+                    source: "manual".to_string(),
+                    priority: 1,
+                    factor: 1.0,
+                    tz: None,
+                    cal: None,
+                    active: true,
+                })
+                .await
+                .unwrap();
+            for j in 0..*count {
+                db.insert_quote(&Quote {
+                    id: None,
+                    ticker: ticker_id,
+                    price: 100.0,
+                    time: make_time(2021, 3, 1 + j * step, 20, 0, 0).unwrap(),
+                    volume: None,
+                    stale: false,
+                })
+                .await
+                .unwrap();
+            }
+        }
+
+        let stats = get_quote_coverage_stats(Arc::new(db)).await.unwrap();
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats[0].ticker_name, "TICKER0");
+        assert_eq!(stats[0].quote_count, 10);
+        assert_eq!(
+            stats[0].first_quote,
+            Some(make_time(2021, 3, 1, 20, 0, 0).unwrap())
+        );
+        assert_eq!(
+            stats[0].last_quote,
+            Some(make_time(2021, 3, 10, 20, 0, 0).unwrap())
+        );
+        assert_fuzzy_eq!(stats[0].gap_days, 0.0, tol);
+        assert_eq!(stats[1].quote_count, 0);
+        assert_eq!(stats[1].first_quote, None);
+        assert_eq!(stats[1].last_quote, None);
+        assert_eq!(stats[2].quote_count, 5);
+        assert_eq!(
+            stats[2].last_quote,
+            Some(make_time(2021, 3, 9, 20, 0, 0).unwrap())
+        );
+        assert_fuzzy_eq!(stats[2].gap_days, 1.0, tol);
+    }
+}
@@ -1,10 +1,174 @@
-use super::{MarketQuoteError, MarketQuoteProvider};
+use super::{
+    check_quote_currency, MarketQuoteError, MarketQuoteProvider, OptionQuote, OptionType,
+    PriceField,
+};
 use crate::datatypes::{date_time_helper::unix_to_date_time, CashFlow, Quote, Ticker};
 use async_trait::async_trait;
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, Utc};
+use serde::Deserialize;
+use tokio_compat_02::FutureExt;
 use yahoo_finance_api as yahoo;
 
-pub struct Yahoo {}
+const YAHOO_OPTIONS_URL: &str = "https://query1.finance.yahoo.com/v7/finance/options/";
+
+pub struct Yahoo {
+    price_field: PriceField,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct YahooOptionResponse {
+    option_chain: YahooOptionChain,
+}
+
+#[derive(Deserialize, Debug)]
+struct YahooOptionChain {
+    result: Vec<YahooOptionResult>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct YahooOptionResult {
+    underlying_symbol: String,
+    #[serde(default)]
+    expiration_dates: Vec<i64>,
+    #[serde(default)]
+    options: Vec<YahooOptions>,
+}
+
+#[derive(Deserialize, Debug)]
+struct YahooOptions {
+    #[serde(default)]
+    calls: Vec<YahooOptionContract>,
+    #[serde(default)]
+    puts: Vec<YahooOptionContract>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct YahooOptionContract {
+    strike: f64,
+    expiration: i64,
+    #[serde(default)]
+    bid: f64,
+    #[serde(default)]
+    ask: f64,
+    #[serde(default)]
+    last_price: f64,
+    #[serde(default)]
+    open_interest: f64,
+    implied_volatility: Option<f64>,
+}
+
+impl Yahoo {
+    /// Create new Yahoo provider using close prices as quotes
+    pub fn new() -> Yahoo {
+        Yahoo {
+            price_field: PriceField::Close,
+        }
+    }
+
+    /// Create new Yahoo provider using the given OHLC field as quote price
+    pub fn with_price_field(price_field: PriceField) -> Yahoo {
+        Yahoo { price_field }
+    }
+
+    /// Select the configured price field of a Yahoo quote
+    fn price(&self, quote: &yahoo::Quote) -> f64 {
+        match self.price_field {
+            PriceField::Open => quote.open,
+            PriceField::High => quote.high,
+            PriceField::Low => quote.low,
+            PriceField::Close => quote.close,
+            PriceField::AdjClose => quote.adjclose,
+        }
+    }
+
+    async fn get_option_chain(
+        &self,
+        symbol: &str,
+        expiry: Option<i64>,
+    ) -> Result<YahooOptionResult, MarketQuoteError> {
+        let url = match expiry {
+            Some(expiry) => format!("{}{}?date={}", YAHOO_OPTIONS_URL, symbol, expiry),
+            None => format!("{}{}", YAHOO_OPTIONS_URL, symbol),
+        };
+        let resp = reqwest::get(&url).compat().await?;
+        if !resp.status().is_success() {
+            return Err(MarketQuoteError::UnexpectedError(
+                "unexpected server response".to_string(),
+            ));
+        }
+        let body = resp.text().await?;
+        parse_option_chain(&body)
+    }
+}
+
+impl Default for Yahoo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Yahoo quotes a symbol in a fixed currency (e.g. `BTC-USD` vs. `BTC-EUR`), which must match
+/// the ticker's currency
+fn check_response_currency(
+    ticker: &Ticker,
+    response: &yahoo::YResponse,
+) -> Result<(), MarketQuoteError> {
+    match response.chart.result.first() {
+        Some(block) => check_quote_currency(ticker, &block.meta.currency),
+        None => Ok(()),
+    }
+}
+
+/// Parse the response of the Yahoo options endpoint
+fn parse_option_chain(body: &str) -> Result<YahooOptionResult, MarketQuoteError> {
+    let response: YahooOptionResponse = serde_json::from_str(body)?;
+    response
+        .option_chain
+        .result
+        .into_iter()
+        .next()
+        .ok_or_else(|| MarketQuoteError::UnexpectedError("empty option chain".to_string()))
+}
+
+/// Convert Yahoo option chain to list of option quotes
+fn to_option_quotes(
+    ticker_id: i32,
+    chain: YahooOptionResult,
+) -> Result<Vec<OptionQuote>, MarketQuoteError> {
+    let mut quotes = Vec::new();
+    for options in chain.options {
+        let contracts = options
+            .calls
+            .into_iter()
+            .map(|c| (OptionType::Call, c))
+            .chain(options.puts.into_iter().map(|p| (OptionType::Put, p)));
+        for (option_type, contract) in contracts {
+            quotes.push(OptionQuote {
+                ticker_id,
+                underlying_ticker: chain.underlying_symbol.clone(),
+                option_type,
+                strike: contract.strike,
+                expiry: unix_to_date(contract.expiration)?,
+                bid: contract.bid,
+                ask: contract.ask,
+                last: contract.last_price,
+                open_interest: contract.open_interest,
+                implied_vol: contract.implied_volatility,
+            });
+        }
+    }
+    Ok(quotes)
+}
+
+/// Yahoo gives option expiries as UNIX timestamps at midnight UTC
+fn unix_to_date(seconds: i64) -> Result<NaiveDate, MarketQuoteError> {
+    DateTime::<Utc>::from_timestamp(seconds, 0)
+        .map(|time| time.date_naive())
+        .ok_or_else(|| MarketQuoteError::UnexpectedError("invalid expiry".to_string()))
+}
 
 #[async_trait]
 impl MarketQuoteProvider for Yahoo {
@@ -12,13 +176,15 @@ impl MarketQuoteProvider for Yahoo {
     async fn fetch_latest_quote(&self, ticker: &Ticker) -> Result<Quote, MarketQuoteError> {
         let yahoo = yahoo::YahooConnector::new();
         let response = yahoo.get_latest_quotes(&ticker.name, "1d").await?;
+        check_response_currency(ticker, &response)?;
         let quote = response.last_quote()?;
         Ok(Quote {
             id: None,
             ticker: ticker.id.unwrap(),
-            price: quote.close,
+            price: self.price(&quote),
             time: unix_to_date_time(quote.timestamp),
             volume: Some(quote.volume as f64),
+            stale: false,
         })
     }
     /// Fetch historic quotes between start and end date
@@ -32,6 +198,7 @@ impl MarketQuoteProvider for Yahoo {
         let response = yahoo
             .get_quote_history(&ticker.name, start.into(), end.into())
             .await?;
+        check_response_currency(ticker, &response)?;
         let yahoo_quotes = response.quotes()?;
         let mut quotes = Vec::new();
         for quote in &yahoo_quotes {
@@ -40,9 +207,10 @@ impl MarketQuoteProvider for Yahoo {
             quotes.push(Quote {
                 id: None,
                 ticker: ticker.id.unwrap(),
-                price: quote.close,
+                price: self.price(quote),
                 time,
                 volume,
+                stale: false,
             })
         }
         Ok(quotes)
@@ -72,6 +240,39 @@ impl MarketQuoteProvider for Yahoo {
         }
         Ok(dividends)
     }
+
+    /// Fetch option chain, if no expiry is given the chains of all available expiries are fetched
+    async fn fetch_option_chain(
+        &self,
+        underlying_ticker: &Ticker,
+        expiry: Option<NaiveDate>,
+    ) -> Result<Vec<OptionQuote>, MarketQuoteError> {
+        let ticker_id = underlying_ticker
+            .id
+            .ok_or_else(|| MarketQuoteError::UnexpectedError("ticker has no id".to_string()))?;
+        match expiry {
+            Some(expiry) => {
+                let expiry = expiry.and_time(NaiveTime::MIN).and_utc().timestamp();
+                let chain = self
+                    .get_option_chain(&underlying_ticker.name, Some(expiry))
+                    .await?;
+                to_option_quotes(ticker_id, chain)
+            }
+            None => {
+                // Without a given date, Yahoo returns only the chain of the nearest expiry
+                let chain = self.get_option_chain(&underlying_ticker.name, None).await?;
+                let expiries = chain.expiration_dates.clone();
+                let mut quotes = to_option_quotes(ticker_id, chain)?;
+                for expiry in expiries.into_iter().skip(1) {
+                    let chain = self
+                        .get_option_chain(&underlying_ticker.name, Some(expiry))
+                        .await?;
+                    quotes.append(&mut to_option_quotes(ticker_id, chain)?);
+                }
+                Ok(quotes)
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -87,17 +288,18 @@ mod tests {
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
     async fn test_yahoo_fetch_quote() {
-        let yahoo = Yahoo {};
+        let yahoo = Yahoo::new();
         let ticker = Ticker {
             id: Some(1),
             asset: 1,
             name: "AAPL".to_string(),
-            currency: Currency::from_str("EUR").unwrap(),
+            currency: Currency::from_str("USD").unwrap(),
             source: MarketDataSource::Yahoo.to_string(),
             priority: 1,
             factor: 1.0,
             tz: None,
             cal: None,
+            active: true,
         };
         let quote = yahoo.fetch_latest_quote(&ticker).await.unwrap();
         assert!(quote.price != 0.0);
@@ -105,17 +307,18 @@ mod tests {
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
     async fn test_yahoo_fetch_history() {
-        let yahoo = Yahoo {};
+        let yahoo = Yahoo::new();
         let ticker = Ticker {
             id: Some(1),
             asset: 1,
             name: "AAPL".to_string(),
-            currency: Currency::from_str("EUR").unwrap(),
+            currency: Currency::from_str("USD").unwrap(),
             source: MarketDataSource::Yahoo.to_string(),
             priority: 1,
             factor: 1.0,
             tz: None,
             cal: None,
+            active: true,
         };
         let start = New_York
             .ymd(2020, 1, 1)
@@ -132,4 +335,85 @@ mod tests {
         assert_eq!(quotes.len(), 21);
         assert!(quotes[0].price != 0.0);
     }
+
+    #[test]
+    fn test_parse_option_chain() {
+        let body = r#"{"optionChain":{"result":[{"underlyingSymbol":"AAPL",
+            "expirationDates":[1637280000,1639699200],"strikes":[150.0,155.0],
+            "hasMiniOptions":false,"quote":{"symbol":"AAPL"},
+            "options":[{"expirationDate":1637280000,"hasMiniOptions":false,
+            "calls":[{"contractSymbol":"AAPL211119C00150000","strike":150.0,"currency":"USD",
+                "lastPrice":2.5,"change":0.1,"volume":1000,"openInterest":12000,"bid":2.45,
+                "ask":2.55,"contractSize":"REGULAR","expiration":1637280000,
+                "lastTradeDate":1637009999,"impliedVolatility":0.2512,"inTheMoney":true}],
+            "puts":[{"contractSymbol":"AAPL211119P00155000","strike":155.0,"currency":"USD",
+                "lastPrice":3.1,"contractSize":"REGULAR","expiration":1637280000,
+                "lastTradeDate":1637009999,"inTheMoney":true}]}]}],"error":null}}"#;
+        let chain = parse_option_chain(body).unwrap();
+        assert_eq!(chain.expiration_dates.len(), 2);
+        let quotes = to_option_quotes(1, chain).unwrap();
+        assert_eq!(quotes.len(), 2);
+        let expiry = NaiveDate::from_ymd(2021, 11, 19);
+
+        let call = &quotes[0];
+        assert_eq!(call.ticker_id, 1);
+        assert_eq!(call.underlying_ticker, "AAPL");
+        assert_eq!(call.option_type, OptionType::Call);
+        assert_eq!(call.strike, 150.0);
+        assert_eq!(call.expiry, expiry);
+        assert_eq!(call.bid, 2.45);
+        assert_eq!(call.ask, 2.55);
+        assert_eq!(call.last, 2.5);
+        assert_eq!(call.open_interest, 12000.0);
+        assert_eq!(call.implied_vol, Some(0.2512));
+
+        let put = &quotes[1];
+        assert_eq!(put.option_type, OptionType::Put);
+        assert_eq!(put.strike, 155.0);
+        assert_eq!(put.expiry, expiry);
+        assert_eq!(put.bid, 0.0);
+        assert_eq!(put.implied_vol, None);
+    }
+
+    #[test]
+    fn test_select_price_field() {
+        let quote = yahoo::Quote {
+            timestamp: 1609772400,
+            open: 133.52,
+            high: 133.61,
+            low: 126.76,
+            volume: 143301900,
+            close: 129.41,
+            adjclose: 128.62,
+        };
+        let open = Yahoo::with_price_field(PriceField::Open).price(&quote);
+        let close = Yahoo::new().price(&quote);
+        assert_eq!(open, 133.52);
+        assert_eq!(close, 129.41);
+        assert!(open != close);
+        assert_eq!(
+            Yahoo::with_price_field(PriceField::AdjClose).price(&quote),
+            128.62
+        );
+    }
+
+    #[tokio::test]
+    async fn option_chain_requires_ticker_id() {
+        let ticker = Ticker {
+            id: None,
+            asset: 1,
+            name: "AAPL".to_string(),
+            currency: Currency::from_str("USD").unwrap(),
+            source: MarketDataSource::Yahoo.to_string(),
+            priority: 1,
+            factor: 1.0,
+            tz: None,
+            cal: None,
+            active: true,
+        };
+        assert!(Yahoo::new()
+            .fetch_option_chain(&ticker, None)
+            .await
+            .is_err());
+    }
 }
@@ -1,5 +1,5 @@
 use futures::future::join_all;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::Arc;
 use std::vec::Vec;
 use thiserror::Error;
@@ -9,12 +9,14 @@ use chrono::{DateTime, Local, NaiveDate};
 use serde::{Deserialize, Serialize};
 
 use crate::datatypes::{
+    cash_flow::round2digits,
     currency::CurrencyConverter,
     date_time_helper::{naive_date_to_date_time, DateTimeError},
-    Asset, AssetHandler, Currency, CurrencyError, DataError, Transaction,
+    AssetHandler, Currency, CurrencyError, CurrencyISOCode, DataError, Transaction,
     TransactionType,
 };
 
+use crate::market::MarketError;
 use crate::period_date::PeriodDateError;
 use crate::Market;
 
@@ -30,11 +32,37 @@ pub enum PositionError {
     #[error("Failed to convert currency")]
     CurrencyError(#[from] CurrencyError),
     #[error("Failed to access market data")]
-    MarketDataError(#[from] crate::market::MarketError),
+    MarketDataError(#[from] MarketError),
+    #[error("No quote found for asset with id {asset_id}")]
+    NoQuote { asset_id: i32, source: MarketError },
+    #[error("Neither quote nor purchase value available for asset with id {0}")]
+    NoPrice(i32),
+    #[error("No fx rate found for currency pair {from}/{to}")]
+    NoFxRate {
+        from: CurrencyISOCode,
+        to: CurrencyISOCode,
+        source: CurrencyError,
+    },
+}
+
+impl PositionError {
+    /// Return the underlying database error, if the position calculation failed due to
+    /// a failed database access
+    pub fn as_data_error(&self) -> Option<&DataError> {
+        match self {
+            PositionError::PositionDataError(err) => Some(err),
+            PositionError::MarketDataError(MarketError::DBError(err)) => Some(err),
+            PositionError::NoQuote {
+                source: MarketError::DBError(err),
+                ..
+            } => Some(err),
+            _ => None,
+        }
+    }
 }
 
 /// Calculate the total position as of a given date by applying a specified set of filters
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Position {
     pub asset_id: Option<i32>,
     pub name: String,
@@ -49,18 +77,47 @@ pub struct Position {
     pub currency: Currency,
     pub last_quote: Option<f64>,
     pub last_quote_time: Option<DateTime<Local>>,
+    /// Internal technical account, which is excluded from asset reports but not from totals
+    #[serde(default)]
+    pub technical: bool,
+    /// Warning on problems with the valuation of the position, e.g. a replaced invalid quote
+    #[serde(default)]
+    pub warning: Option<String>,
 }
 
 /// Calculate the total position as of a given date by applying a specified set of filters
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
 pub struct PositionTotals {
     pub value: f64,
-    trading_pnl: f64,
-    unrealized_pnl: f64,
-    dividend: f64,
-    interest: f64,
-    tax: f64,
-    fees: f64,
+    pub trading_pnl: f64,
+    pub unrealized_pnl: f64,
+    pub dividend: f64,
+    pub interest: f64,
+    pub tax: f64,
+    pub fees: f64,
+}
+
+impl PositionTotals {
+    /// Total costs by fees and taxes relative to the absolute total value.
+    /// Since fees and taxes are stored as (typically negative) cash flows, costs are
+    /// returned as positive figure. If total value is zero, 0.0 is returned.
+    pub fn cost_drag(&self) -> f64 {
+        if self.value == 0.0 {
+            0.0
+        } else {
+            -(self.fees + self.tax) / self.value.abs()
+        }
+    }
+
+    /// Fees (without taxes) relative to the absolute total value, following the
+    /// same conventions as `cost_drag`.
+    pub fn fee_ratio(&self) -> f64 {
+        if self.value == 0.0 {
+            0.0
+        } else {
+            -self.fees / self.value.abs()
+        }
+    }
 }
 
 impl Position {
@@ -78,6 +135,38 @@ impl Position {
             tax: 0.0,
             last_quote: None,
             last_quote_time: None,
+            technical: false,
+            warning: None,
+        }
+    }
+
+    /// Positions without asset id or explicitly marked as technical are treated
+    /// as technical accounts
+    pub fn is_technical(&self) -> bool {
+        self.technical || self.asset_id.is_none()
+    }
+
+    /// Value of the position based on the last quote, or the purchase value if there is no quote
+    pub fn value(&self) -> f64 {
+        if let Some(quote) = self.last_quote {
+            self.position * quote
+        } else {
+            -self.purchase_value
+        }
+    }
+
+    /// Replace a last quote which is NaN or infinite (e.g. due to a zero fx rate) by the price
+    /// derived from the purchase value and record a warning
+    fn replace_invalid_quote(&mut self) {
+        if let Some(quote) = self.last_quote {
+            if !quote.is_finite() {
+                self.last_quote = self.quote_from_purchase().filter(|q| q.is_finite());
+                self.last_quote_time = None;
+                self.warning = Some(format!(
+                    "invalid quote {} replaced by price derived from purchase value",
+                    quote
+                ));
+            }
         }
     }
 
@@ -89,33 +178,119 @@ impl Position {
         }
     }
 
+    /// Get latest quote of the position's asset before the given time in position currency
+    /// Returns `None` for positions without asset id.
+    pub async fn get_quote(
+        &self,
+        time: DateTime<Local>,
+        market: &Market,
+    ) -> Result<Option<f64>, PositionError> {
+        match self.asset_id {
+            Some(asset_id) => market
+                .get_asset_price(asset_id, self.currency, time)
+                .await
+                .map(Some)
+                .map_err(|source| PositionError::NoQuote { asset_id, source }),
+            None => Ok(None),
+        }
+    }
+
     /// Add quote information to position
     /// If no quote is available (or no conversion to position currency), calculate
     /// from purchase value.
     pub async fn add_quote(&mut self, time: DateTime<Local>, market: Market) {
-        if let Some(asset_id) = self.asset_id {
-            if let Ok(price) = market.get_asset_price(asset_id, self.currency, time).await {
-                self.last_quote = Some(price);
-                self.last_quote_time = Some(time);
-            } else {
-                // No price found
-                self.last_quote = self.quote_from_purchase();
-                self.last_quote_time = None;
+        // Failures are reflected by a missing last quote
+        let _ = self.try_add_quote(time, &market).await;
+    }
+
+    /// Add quote information to position like `add_quote`, but return an error
+    /// if neither a quote nor a price derived from the purchase value is available.
+    pub async fn try_add_quote(
+        &mut self,
+        time: DateTime<Local>,
+        market: &Market,
+    ) -> Result<(), PositionError> {
+        // Warnings refer to the previous valuation only
+        self.warning = None;
+        let asset_id = match self.asset_id {
+            Some(asset_id) => asset_id,
+            None => {
+                // No asset ID, must be some technical account, set price to 1.0
+                self.last_quote = Some(1.0);
+                self.last_quote_time = Some(Local::now());
+                return Ok(());
             }
-        } else {
-            // No asset ID, must be some technical account, set price to 1.0
-            self.last_quote = Some(1.0);
-            self.last_quote_time = Some(Local::now());
         };
+        let price = market
+            .try_get_asset_price(asset_id, self.currency, time)
+            .await;
+        if let Ok(Some(price)) = price {
+            self.last_quote = Some(price);
+            self.last_quote_time = Some(time);
+            self.replace_invalid_quote();
+            return match self.last_quote {
+                Some(_) => Ok(()),
+                None => Err(PositionError::NoPrice(asset_id)),
+            };
+        }
+        // No price found or price not available in position currency
+        self.last_quote = self.quote_from_purchase();
+        self.last_quote_time = None;
+        match (self.last_quote, price) {
+            (Some(_), _) => Ok(()),
+            (None, Err(source)) => Err(PositionError::NoQuote { asset_id, source }),
+            (None, Ok(_)) => Err(PositionError::NoPrice(asset_id)),
+        }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct PortfolioPosition {
     pub cash: Position,
     pub assets: BTreeMap<i32, Position>,
 }
 
+/// Portfolio position and totals valued as of a given date
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PortfolioSnapshot {
+    pub date: NaiveDate,
+    pub position: PortfolioPosition,
+    pub totals: PositionTotals,
+}
+
+/// Change of a single asset position between two portfolio positions
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PositionDelta {
+    pub quantity: f64,
+    pub value: f64,
+}
+
+/// Differences between two portfolio positions, e.g. of two consecutive days
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct PortfolioDiff {
+    /// Change in quantity and value per asset id, including new and closed positions
+    pub assets: BTreeMap<i32, PositionDelta>,
+    /// Ids of assets held only in the later position
+    pub new_positions: Vec<i32>,
+    /// Ids of assets held only in the earlier position
+    pub closed_positions: Vec<i32>,
+}
+
+/// Difference between computed and externally reported asset holdings
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum ReconDiff {
+    /// Asset is held in both, but with different quantities
+    QuantityMismatch {
+        asset_id: i32,
+        computed: f64,
+        reported: f64,
+    },
+    /// Asset is held according to the computed position, but not reported
+    Missing { asset_id: i32, computed: f64 },
+    /// Asset is reported, but not held according to the computed position
+    Extra { asset_id: i32, reported: f64 },
+}
+
 impl PortfolioPosition {
     pub fn new(base_currency: Currency) -> PortfolioPosition {
         PortfolioPosition {
@@ -124,20 +299,29 @@ impl PortfolioPosition {
         }
     }
 
+    /// Set names of all asset positions, technical positions are skipped
     pub async fn get_asset_names(
         &mut self,
         db: Arc<dyn AssetHandler + Send + Sync>,
     ) -> Result<(), DataError> {
         for (id, mut pos) in &mut self.assets {
+            if pos.is_technical() {
+                continue;
+            }
             let asset = db.get_asset_by_id(*id).await?;
-            pos.name = match asset {
-                Asset::Currency(c) => c.iso_code.to_string(),
-                Asset::Stock(s) => s.name.clone(),
-            };
+            pos.name = asset.name();
         }
         Ok(())
     }
 
+    /// Asset positions to be shown in reports, i.e. all positions except technical accounts
+    pub fn report_positions(&self) -> Vec<&Position> {
+        self.assets
+            .values()
+            .filter(|pos| !pos.is_technical())
+            .collect()
+    }
+
     pub async fn add_quote(&mut self, time: DateTime<Local>, market: &Market) {
         let mut get_quote_futures = Vec::new();
         for pos in self.assets.values_mut() {
@@ -156,12 +340,9 @@ impl PortfolioPosition {
             tax: self.cash.tax,
             fees: self.cash.fees,
         };
-        for pos in self.assets.values() {
-            let pos_value = if let Some(quote) = pos.last_quote {
-                pos.position * quote
-            } else {
-                -pos.purchase_value
-            };
+        for pos in self.assets.values_mut() {
+            pos.replace_invalid_quote();
+            let pos_value = pos.value();
             totals.value += pos_value;
             totals.trading_pnl += pos.trading_pnl;
             totals.unrealized_pnl += pos_value + pos.purchase_value;
@@ -173,6 +354,19 @@ impl PortfolioPosition {
         totals
     }
 
+    /// Total value of cash and asset positions converted from the base currency into the
+    /// given reporting currency with the fx rate at the given time
+    pub async fn value_in(
+        &self,
+        currency: Currency,
+        market: &Market,
+        time: DateTime<Local>,
+    ) -> Result<f64, MarketError> {
+        let value = self.cash.position + self.assets.values().map(|pos| pos.value()).sum::<f64>();
+        let fx_rate = market.fx_rate(self.cash.currency, currency, time).await?;
+        Ok(value * fx_rate)
+    }
+
     /// Reset all pnl relevant figures, i.e. set purchase value to position * price and
     /// realized p&l, dividends, interest, tax, fee to 0 and eliminate 0 positions
     fn reset_pnl(&mut self) {
@@ -192,6 +386,35 @@ impl PortfolioPosition {
         }
     }
 
+    /// Differences from this position to the later position `other`. Assets with
+    /// a position of zero are regarded as not being held.
+    pub fn diff(&self, other: &PortfolioPosition) -> PortfolioDiff {
+        let is_held = |pos: Option<&Position>| matches!(pos, Some(pos) if pos.position != 0.0);
+        let mut diff = PortfolioDiff::default();
+        let asset_ids: BTreeSet<i32> = self
+            .assets
+            .keys()
+            .chain(other.assets.keys())
+            .copied()
+            .collect();
+        for asset_id in asset_ids {
+            let before = self.assets.get(&asset_id);
+            let after = other.assets.get(&asset_id);
+            let quantity =
+                after.map_or(0.0, |pos| pos.position) - before.map_or(0.0, |pos| pos.position);
+            let value =
+                after.map_or(0.0, |pos| pos.value()) - before.map_or(0.0, |pos| pos.value());
+            match (is_held(before), is_held(after)) {
+                (false, true) => diff.new_positions.push(asset_id),
+                (true, false) => diff.closed_positions.push(asset_id),
+                _ => {}
+            }
+            diff.assets
+                .insert(asset_id, PositionDelta { quantity, value });
+        }
+        diff
+    }
+
     fn remove_zero_positions(&mut self) {
         let mut zero_positions = Vec::new();
         for pos in self.assets.iter() {
@@ -236,7 +459,105 @@ pub async fn calc_position(
     Ok(positions)
 }
 
+/// Sort transactions into the canonical processing order, i.e. by cash flow date,
+/// time of storage in the database and transaction id
+pub fn sort_transactions_for_processing(transactions: &mut [Transaction]) {
+    transactions.sort_by(|a, b| {
+        (a.cash_flow.date, a.time_stamp, a.id).cmp(&(b.cash_flow.date, b.time_stamp, b.id))
+    });
+}
+
+/// Count transactions and sum up their cash amounts per transaction type code
+/// (see `TransactionType::code`). Amounts are summed up as given, regardless of their currency.
+pub fn transaction_summary(transactions: &[Transaction]) -> BTreeMap<String, (usize, f64)> {
+    let mut summary: BTreeMap<String, (usize, f64)> = BTreeMap::new();
+    for transaction in transactions {
+        let entry = summary
+            .entry(transaction.transaction_type.code().to_string())
+            .or_default();
+        entry.0 += 1;
+        entry.1 += transaction.cash_flow.amount.amount;
+    }
+    summary
+}
+
+/// Contribution of each asset to the total return of a portfolio over a period, i.e. the asset's
+/// change in value plus income (realized p&l, dividends and interest, net of taxes and fees)
+/// relative to the portfolio value at the start of the period. The position is expected to be
+/// valued at the end of the period, e.g. as calculated by `calculate_position_for_period`.
+/// The change in value is measured against the purchase value of the position, which for
+/// positions calculated by `calculate_position_for_period` is the value at the start of the
+/// period plus purchases within the period. For other positions, e.g. from
+/// `calculate_position_and_pnl`, the change in value since purchase is used instead.
+/// Returns an empty map if the start value is zero.
+pub fn return_contributions(position: &PortfolioPosition, start_value: f64) -> BTreeMap<i32, f64> {
+    if start_value == 0.0 {
+        return BTreeMap::new();
+    }
+    position
+        .assets
+        .iter()
+        .map(|(asset_id, pos)| {
+            let value_change = pos.value() + pos.purchase_value + pos.trading_pnl;
+            let income = pos.dividend + pos.interest + pos.tax + pos.fees;
+            (*asset_id, (value_change + income) / start_value)
+        })
+        .collect()
+}
+
+/// Annualize a cumulative return achieved over a period of `years` years.
+/// For periods of zero or negative length, the cumulative return is returned unchanged.
+pub fn annualize_return(cumulative: f64, years: f64) -> f64 {
+    if years <= 0.0 {
+        cumulative
+    } else {
+        (1.0 + cumulative).powf(1.0 / years) - 1.0
+    }
+}
+
+/// Reconcile the computed asset positions against reported holdings by asset id, e.g. from
+/// a broker statement. Quantities with an absolute value not exceeding `tol` are regarded
+/// as not being held, and quantities differing by no more than `tol` are regarded as equal.
+pub fn reconcile_positions(
+    computed: &PortfolioPosition,
+    reported: &BTreeMap<i32, f64>,
+    tol: f64,
+) -> Vec<ReconDiff> {
+    let asset_ids: BTreeSet<i32> = computed
+        .assets
+        .keys()
+        .chain(reported.keys())
+        .copied()
+        .collect();
+    let mut diffs = Vec::new();
+    for asset_id in asset_ids {
+        let computed = computed
+            .assets
+            .get(&asset_id)
+            .map_or(0.0, |pos| pos.position);
+        let reported = reported.get(&asset_id).copied().unwrap_or(0.0);
+        if (computed - reported).abs() <= tol {
+            continue;
+        }
+        let diff = if reported.abs() <= tol {
+            ReconDiff::Missing { asset_id, computed }
+        } else if computed.abs() <= tol {
+            ReconDiff::Extra { asset_id, reported }
+        } else {
+            ReconDiff::QuantityMismatch {
+                asset_id,
+                computed,
+                reported,
+            }
+        };
+        diffs.push(diff);
+    }
+    diffs
+}
+
 /// Given a PortfolioPosition, calculate changes to position by a given set of transactions.
+/// Since the realized profit and loss depends on the order of transactions, transactions
+/// should be sorted with `sort_transactions_for_processing` before to get reproducible results.
 pub async fn calc_delta_position(
     positions: &mut PortfolioPosition,
     transactions: &[Transaction],
@@ -252,19 +573,29 @@ pub async fn calc_delta_position(
         if end.is_some() && trans.cash_flow.date >= end.unwrap() {
             continue;
         }
-        let curr_factor = if trans.cash_flow.amount.currency != base_currency {
-            market
+        let cash_amount = if trans.cash_flow.amount.currency != base_currency {
+            let curr_factor = market
                 .fx_rate(
                     trans.cash_flow.amount.currency,
                     base_currency,
                     naive_date_to_date_time(&trans.cash_flow.date, 20, None)?,
                 )
-                .await?
+                .await
+                .map_err(|source| PositionError::NoFxRate {
+                    from: trans.cash_flow.amount.currency.iso_code,
+                    to: base_currency.iso_code,
+                    source,
+                })?;
+            // avoid spurious precision of converted amounts
+            round2digits(
+                trans.cash_flow.amount.amount * curr_factor,
+                base_currency.rounding_digits,
+            )
         } else {
-            1.0
+            trans.cash_flow.amount.amount
         };
         // adjust cash balance
-        positions.cash.position += trans.cash_flow.amount.amount * curr_factor;
+        positions.cash.position += cash_amount;
 
         match trans.transaction_type {
             TransactionType::Cash => {
@@ -410,6 +741,95 @@ pub async fn calculate_position_for_period(
     Ok((position, totals))
 }
 
+/// Errors preventing the validation of a set of transactions
+#[derive(Error, Debug)]
+pub enum ValidationError {
+    #[error("Transaction cash flow in {found} instead of {expected}")]
+    CurrencyMismatch {
+        expected: CurrencyISOCode,
+        found: CurrencyISOCode,
+    },
+}
+
+/// Result of the consistency validation of a set of transactions
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationReport {
+    /// Sum of all cash flows
+    pub cash_balance: f64,
+    /// Number of assets with non-zero position
+    pub position_count: usize,
+    /// Number of fees referring to a non-existing transaction
+    pub unmatched_fees: usize,
+    /// Number of taxes referring to a non-existing transaction
+    pub unmatched_taxes: usize,
+    /// Indices of fees and taxes referring to a non-existing transaction
+    pub orphan_transactions: Vec<usize>,
+    /// Indices of asset transactions without change in position
+    pub zero_position_transactions: Vec<usize>,
+    /// Warning: the cash balance is negative
+    pub negative_cash_balance: bool,
+}
+
+impl ValidationReport {
+    /// True, if no inconsistencies have been found; warnings are ignored
+    pub fn is_consistent(&self) -> bool {
+        self.orphan_transactions.is_empty() && self.zero_position_transactions.is_empty()
+    }
+}
+
+/// Check a set of transactions in the given currency for accounting errors
+/// Fees and taxes must refer to existing transactions (if they refer to any transaction at all)
+/// and asset transactions must change the position by more than the given tolerance.
+/// A negative cash balance is reported as warning only.
+pub fn validate_portfolio_consistency(
+    transactions: &[Transaction],
+    currency: Currency,
+    tolerance: f64,
+) -> Result<ValidationReport, ValidationError> {
+    let mut report = ValidationReport {
+        cash_balance: 0.0,
+        position_count: 0,
+        unmatched_fees: 0,
+        unmatched_taxes: 0,
+        orphan_transactions: Vec::new(),
+        zero_position_transactions: Vec::new(),
+        negative_cash_balance: false,
+    };
+    let mut positions: BTreeMap<i32, f64> = BTreeMap::new();
+    let is_orphan = |trans_ref: Option<i32>| {
+        trans_ref.is_some_and(|trans_ref| !transactions.iter().any(|t| t.id == Some(trans_ref)))
+    };
+    for (idx, trans) in transactions.iter().enumerate() {
+        if trans.cash_flow.amount.currency.iso_code != currency.iso_code {
+            return Err(ValidationError::CurrencyMismatch {
+                expected: currency.iso_code,
+                found: trans.cash_flow.amount.currency.iso_code,
+            });
+        }
+        report.cash_balance += trans.cash_flow.amount.amount;
+        match trans.transaction_type {
+            TransactionType::Asset { asset_id, position } => {
+                if position.abs() <= tolerance {
+                    report.zero_position_transactions.push(idx);
+                }
+                *positions.entry(asset_id).or_insert(0.0) += position;
+            }
+            TransactionType::Fee { transaction_ref } if is_orphan(transaction_ref) => {
+                report.unmatched_fees += 1;
+                report.orphan_transactions.push(idx);
+            }
+            TransactionType::Tax { transaction_ref } if is_orphan(transaction_ref) => {
+                report.unmatched_taxes += 1;
+                report.orphan_transactions.push(idx);
+            }
+            _ => {}
+        }
+    }
+    report.position_count = positions.values().filter(|p| p.abs() > tolerance).count();
+    report.negative_cash_balance = report.cash_balance < -tolerance;
+    Ok(report)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -423,12 +843,8 @@ mod tests {
         CurrencyISOCode, Quote, Stock, Ticker,
     };
     use crate::postgres::PostgresDB;
-    use crate::market::CachePolicy;
 
-    #[tokio::test]
-    async fn test_portfolio_position() {
-        let tol = 1e-4;
-        // Setup database connection
+    async fn prepare_db() -> PostgresDB {
         let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
         assert!(
             db_url.is_ok(),
@@ -436,6 +852,13 @@ mod tests {
         );
         let db = PostgresDB::new(&db_url.unwrap()).await.unwrap();
         db.clean().await.unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn test_portfolio_position() {
+        let tol = 1e-4;
+        let db = prepare_db().await;
 
         let market = Market::new(Arc::new(db)).await;
         let eur = market.get_currency_from_str("EUR").await.unwrap();
@@ -454,6 +877,7 @@ mod tests {
                 date: NaiveDate::from_ymd(2020, 1, 1),
             },
             note: None,
+            time_stamp: None,
         });
         let positions = calc_position(eur, &transactions, None, market.clone()).await.unwrap();
         assert_fuzzy_eq!(positions.cash.position, 10000.0, tol);
@@ -473,6 +897,7 @@ mod tests {
                 date: NaiveDate::from_ymd(2020, 1, 2),
             },
             note: None,
+            time_stamp: None,
         });
         transactions.push(Transaction {
             id: Some(3),
@@ -487,6 +912,7 @@ mod tests {
                 date: NaiveDate::from_ymd(2020, 1, 2),
             },
             note: None,
+            time_stamp: None,
         });
         let positions = calc_position(eur, &transactions, None, market.clone()).await.unwrap();
         assert_fuzzy_eq!(positions.cash.position, 10000.0 - 104.0 - 5.0, tol);
@@ -511,6 +937,7 @@ mod tests {
                 date: NaiveDate::from_ymd(2020, 1, 31),
             },
             note: None,
+            time_stamp: None,
         });
         transactions.push(Transaction {
             id: Some(5),
@@ -525,6 +952,7 @@ mod tests {
                 date: NaiveDate::from_ymd(2020, 1, 31),
             },
             note: None,
+            time_stamp: None,
         });
         transactions.push(Transaction {
             id: Some(6),
@@ -539,6 +967,7 @@ mod tests {
                 date: NaiveDate::from_ymd(2020, 1, 31),
             },
             note: None,
+            time_stamp: None,
         });
         let positions = calc_position(eur, &transactions, None, market.clone()).await.unwrap();
         assert_fuzzy_eq!(
@@ -568,6 +997,7 @@ mod tests {
                 date: NaiveDate::from_ymd(2020, 2, 15),
             },
             note: None,
+            time_stamp: None,
         });
         transactions.push(Transaction {
             id: Some(8),
@@ -582,6 +1012,7 @@ mod tests {
                 date: NaiveDate::from_ymd(2020, 2, 25),
             },
             note: None,
+            time_stamp: None,
         });
         transactions.push(Transaction {
             id: Some(9),
@@ -596,6 +1027,7 @@ mod tests {
                 date: NaiveDate::from_ymd(2020, 2, 26),
             },
             note: None,
+            time_stamp: None,
         });
         transactions.push(Transaction {
             id: Some(10),
@@ -608,6 +1040,7 @@ mod tests {
                 date: NaiveDate::from_ymd(2020, 2, 27),
             },
             note: None,
+            time_stamp: None,
         });
         transactions.push(Transaction {
             id: Some(11),
@@ -620,6 +1053,7 @@ mod tests {
                 date: NaiveDate::from_ymd(2020, 2, 28),
             },
             note: None,
+            time_stamp: None,
         });
         let positions = calc_position(eur, &transactions, None, market.clone()).await.unwrap();
         assert_fuzzy_eq!(
@@ -645,19 +1079,29 @@ mod tests {
         assert_fuzzy_eq!(asset_pos_3.interest, 6.6, tol);
     }
 
+    #[test]
+    fn test_cost_drag() {
+        let tol = 1e-10;
+        let totals = PositionTotals {
+            value: 10000.0,
+            fees: -25.0,
+            tax: -75.0,
+            ..Default::default()
+        };
+        assert_fuzzy_eq!(totals.cost_drag(), 0.01, tol);
+        assert_fuzzy_eq!(totals.fee_ratio(), 0.0025, tol);
+
+        let empty = PositionTotals::default();
+        assert_fuzzy_eq!(empty.cost_drag(), 0.0, tol);
+        assert_fuzzy_eq!(empty.fee_ratio(), 0.0, tol);
+    }
+
     #[tokio::test]
     async fn test_add_quote_to_position() {
         use crate::datatypes::DataItem;
 
         let tol = 1e-4;
-        // Setup database connection
-        let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
-        assert!(
-            db_url.is_ok(),
-            "environment variable $FINQL_TEST_DATABASE_URL is not set"
-        );
-        let db = PostgresDB::new(&db_url.unwrap()).await.unwrap();
-        db.clean().await.unwrap();
+        let db = prepare_db().await;
 
         // first add some assets and currencies
         let eur_stock_id = db
@@ -700,6 +1144,7 @@ mod tests {
                 factor: 1.0,
                 tz: None,
                 cal: None,
+                active: true,
             })
             .await
             .unwrap();
@@ -714,6 +1159,7 @@ mod tests {
                 factor: 1.0,
                 tz: None,
                 cal: None,
+                active: true,
             })
             .await
             .unwrap();
@@ -726,6 +1172,7 @@ mod tests {
                 price: 12.34,
                 time,
                 volume: None,
+                stale: false,
             })
             .await
             .unwrap();
@@ -736,6 +1183,7 @@ mod tests {
                 price: 43.21,
                 time,
                 volume: None,
+                stale: false,
             })
             .await
             .unwrap();
@@ -776,4 +1224,601 @@ mod tests {
             "2019-12-30 10:00:00"
         );
     }
+
+    #[tokio::test]
+    async fn test_position_error_context() {
+        use crate::datatypes::DataItem;
+
+        let db = prepare_db().await;
+
+        let stock_id = db
+            .insert_asset(&Asset::Stock(Stock::new(
+                None,
+                "No Quote Stock".to_string(),
+                Some("NOQUOTE".to_string()),
+                None,
+                None,
+            )))
+            .await
+            .unwrap();
+        let mut eur = Currency::new(None, CurrencyISOCode::new("EUR").unwrap(), Some(2));
+        let eur_id = db.insert_asset(&Asset::Currency(eur)).await.unwrap();
+        eur.set_id(eur_id).unwrap();
+        let mut usd = Currency::new(None, CurrencyISOCode::new("USD").unwrap(), Some(2));
+        let usd_id = db.insert_asset(&Asset::Currency(usd)).await.unwrap();
+        usd.set_id(usd_id).unwrap();
+        let market = Market::new(Arc::new(db)).await;
+
+        let position = Position::new(Some(stock_id), eur);
+        let time = make_time(2020, 1, 2, 10, 0, 0).unwrap();
+        let err = position.get_quote(time, &market).await.unwrap_err();
+        assert!(matches!(err, PositionError::NoQuote { .. }));
+        assert!(err.to_string().contains(&stock_id.to_string()));
+        assert!(std::error::Error::source(&err).is_some());
+        assert!(matches!(err.as_data_error(), Some(DataError::NotFound(_))));
+
+        let transactions = vec![Transaction {
+            id: Some(1),
+            transaction_type: TransactionType::Cash,
+            cash_flow: CashFlow {
+                amount: CashAmount {
+                    amount: 10000.0,
+                    currency: usd,
+                },
+                date: NaiveDate::from_ymd(2020, 1, 1),
+            },
+            note: None,
+            time_stamp: None,
+        }];
+        let err = calc_position(eur, &transactions, None, market.clone())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, PositionError::NoFxRate { .. }));
+        assert!(err.to_string().contains("USD/EUR"));
+        assert!(std::error::Error::source(&err).is_some());
+        assert!(err.as_data_error().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rounding_of_converted_cash() {
+        use crate::datatypes::DataItem;
+
+        let db = prepare_db().await;
+
+        let mut jpy = Currency::new(None, CurrencyISOCode::new("JPY").unwrap(), Some(0));
+        let jpy_id = db.insert_asset(&Asset::Currency(jpy)).await.unwrap();
+        jpy.set_id(jpy_id).unwrap();
+        let mut usd = Currency::new(None, CurrencyISOCode::new("USD").unwrap(), Some(2));
+        let usd_id = db.insert_asset(&Asset::Currency(usd)).await.unwrap();
+        usd.set_id(usd_id).unwrap();
+
+        let qh: Arc<dyn QuoteHandler + Sync + Send> = Arc::new(db);
+        let time = make_time(2019, 12, 30, 10, 0, 0).unwrap();
+        crate::fx_rates::insert_fx_quote(109.876, usd, jpy, time, qh.clone())
+            .await
+            .unwrap();
+        let market = Market::new(qh).await;
+
+        let transactions = vec![Transaction {
+            id: Some(1),
+            transaction_type: TransactionType::Cash,
+            cash_flow: CashFlow {
+                amount: CashAmount {
+                    amount: 123.45,
+                    currency: usd,
+                },
+                date: NaiveDate::from_ymd(2020, 1, 1),
+            },
+            note: None,
+            time_stamp: None,
+        }];
+        let positions = calc_position(jpy, &transactions, None, market)
+            .await
+            .unwrap();
+        let cash = positions.cash.position;
+        assert_eq!(cash, cash.round());
+        assert_eq!(cash, 13564.0);
+    }
+
+    #[tokio::test]
+    async fn test_canonical_transaction_order() {
+        let tol = 1e-11;
+        let db = prepare_db().await;
+        let market = Market::new(Arc::new(db)).await;
+        let eur = market.get_currency_from_str("EUR").await.unwrap();
+
+        let transaction = |id: i32, transaction_type, amount, date| Transaction {
+            id: Some(id),
+            transaction_type,
+            cash_flow: CashFlow {
+                amount: CashAmount {
+                    amount,
+                    currency: eur,
+                },
+                date,
+            },
+            note: None,
+            time_stamp: None,
+        };
+        let first_day = NaiveDate::from_ymd(2020, 1, 2);
+        let second_day = NaiveDate::from_ymd(2020, 1, 3);
+        let transactions = vec![
+            transaction(1, TransactionType::Cash, 10000.0, first_day),
+            transaction(
+                2,
+                TransactionType::Asset {
+                    asset_id: 1,
+                    position: 10.0,
+                },
+                -1000.0,
+                first_day,
+            ),
+            // sell and buy on the same day, realized pnl depends on the order
+            transaction(
+                3,
+                TransactionType::Asset {
+                    asset_id: 1,
+                    position: -5.0,
+                },
+                600.0,
+                second_day,
+            ),
+            transaction(
+                4,
+                TransactionType::Asset {
+                    asset_id: 1,
+                    position: 5.0,
+                },
+                -400.0,
+                second_day,
+            ),
+        ];
+        let mut shuffled = vec![
+            transactions[3].clone(),
+            transactions[0].clone(),
+            transactions[2].clone(),
+            transactions[1].clone(),
+        ];
+        let mut transactions = transactions;
+        sort_transactions_for_processing(&mut transactions);
+        sort_transactions_for_processing(&mut shuffled);
+
+        let mut positions = PortfolioPosition::new(eur);
+        calc_delta_position(&mut positions, &transactions, None, None, market.clone())
+            .await
+            .unwrap();
+        let mut shuffled_positions = PortfolioPosition::new(eur);
+        calc_delta_position(&mut shuffled_positions, &shuffled, None, None, market)
+            .await
+            .unwrap();
+
+        assert_fuzzy_eq!(
+            positions.cash.position,
+            shuffled_positions.cash.position,
+            tol
+        );
+        let asset_pos = positions.assets.get(&1).unwrap();
+        let shuffled_asset_pos = shuffled_positions.assets.get(&1).unwrap();
+        assert_fuzzy_eq!(asset_pos.position, shuffled_asset_pos.position, tol);
+        assert_fuzzy_eq!(asset_pos.trading_pnl, shuffled_asset_pos.trading_pnl, tol);
+        assert_fuzzy_eq!(asset_pos.trading_pnl, 100.0, tol);
+        assert_fuzzy_eq!(
+            asset_pos.purchase_value,
+            shuffled_asset_pos.purchase_value,
+            tol
+        );
+    }
+
+    #[test]
+    fn test_validate_portfolio_consistency() {
+        let tol = 1e-11;
+        let eur = Currency::new(None, CurrencyISOCode::new("EUR").unwrap(), None);
+        let date = NaiveDate::from_ymd(2020, 1, 2);
+        let transaction = |id: i32, transaction_type, amount| Transaction {
+            id: Some(id),
+            transaction_type,
+            cash_flow: CashFlow::new(amount, eur, date),
+            note: None,
+            time_stamp: None,
+        };
+        let mut transactions = vec![
+            transaction(1, TransactionType::Cash, 1000.0),
+            transaction(
+                2,
+                TransactionType::Asset {
+                    asset_id: 1,
+                    position: 10.0,
+                },
+                -900.0,
+            ),
+            transaction(
+                3,
+                TransactionType::Fee {
+                    transaction_ref: Some(2),
+                },
+                -5.0,
+            ),
+            transaction(
+                4,
+                TransactionType::Tax {
+                    transaction_ref: None,
+                },
+                -2.0,
+            ),
+        ];
+        let report = validate_portfolio_consistency(&transactions, eur, tol).unwrap();
+        assert!(report.is_consistent());
+        assert_fuzzy_eq!(report.cash_balance, 93.0, tol);
+        assert_eq!(report.position_count, 1);
+        assert!(!report.negative_cash_balance);
+
+        // fee referring to a non-existing transaction
+        transactions.push(transaction(
+            5,
+            TransactionType::Fee {
+                transaction_ref: Some(42),
+            },
+            -100.0,
+        ));
+        let report = validate_portfolio_consistency(&transactions, eur, tol).unwrap();
+        assert!(!report.is_consistent());
+        assert_eq!(report.unmatched_fees, 1);
+        assert_eq!(report.unmatched_taxes, 0);
+        assert_eq!(report.orphan_transactions, vec![4]);
+        assert!(report.negative_cash_balance);
+
+        let usd = Currency::new(None, CurrencyISOCode::new("USD").unwrap(), None);
+        assert!(matches!(
+            validate_portfolio_consistency(&transactions, usd, tol),
+            Err(ValidationError::CurrencyMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_return_contributions() {
+        let tol = 1e-11;
+        let eur = Currency::new(None, CurrencyISOCode::new("EUR").unwrap(), None);
+        let mut portfolio = PortfolioPosition::new(eur);
+        portfolio.cash.position = 500.0;
+        let mut first = Position::new(Some(1), eur);
+        first.position = 10.0;
+        first.purchase_value = -1000.0;
+        first.last_quote = Some(110.0);
+        first.dividend = 20.0;
+        portfolio.assets.insert(1, first);
+        let mut second = Position::new(Some(2), eur);
+        second.position = 5.0;
+        second.purchase_value = -500.0;
+        second.last_quote = Some(90.0);
+        second.fees = -5.0;
+        portfolio.assets.insert(2, second);
+
+        let start_value = 2000.0;
+        let contributions = return_contributions(&portfolio, start_value);
+        assert_eq!(contributions.len(), 2);
+        assert_fuzzy_eq!(contributions[&1], 120.0 / start_value, tol);
+        assert_fuzzy_eq!(contributions[&2], -55.0 / start_value, tol);
+
+        let totals = portfolio.calc_totals();
+        let total_return = (totals.unrealized_pnl
+            + totals.trading_pnl
+            + totals.dividend
+            + totals.interest
+            + totals.tax
+            + totals.fees)
+            / start_value;
+        assert_fuzzy_eq!(contributions.values().sum::<f64>(), total_return, tol);
+
+        assert!(return_contributions(&portfolio, 0.0).is_empty());
+    }
+
+    #[test]
+    fn test_portfolio_diff() {
+        let tol = 1e-11;
+        let eur = Currency::new(None, CurrencyISOCode::new("EUR").unwrap(), None);
+        let holding = |asset_id: i32, position: f64, quote: f64| {
+            let mut pos = Position::new(Some(asset_id), eur);
+            pos.position = position;
+            pos.purchase_value = -position * 100.0;
+            pos.last_quote = Some(quote);
+            pos
+        };
+        let mut before = PortfolioPosition::new(eur);
+        before.assets.insert(1, holding(1, 10.0, 100.0));
+        before.assets.insert(2, holding(2, 5.0, 50.0));
+        let mut after = PortfolioPosition::new(eur);
+        after.assets.insert(1, holding(1, 15.0, 110.0));
+        // closed positions may still be kept with zero quantity
+        after.assets.insert(2, holding(2, 0.0, 55.0));
+        after.assets.insert(3, holding(3, 20.0, 10.0));
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.new_positions, vec![3]);
+        assert_eq!(diff.closed_positions, vec![2]);
+        assert_eq!(diff.assets.len(), 3);
+        assert_fuzzy_eq!(diff.assets[&1].quantity, 5.0, tol);
+        assert_fuzzy_eq!(diff.assets[&1].value, 650.0, tol);
+        assert_fuzzy_eq!(diff.assets[&2].quantity, -5.0, tol);
+        assert_fuzzy_eq!(diff.assets[&2].value, -250.0, tol);
+        assert_fuzzy_eq!(diff.assets[&3].quantity, 20.0, tol);
+        assert_fuzzy_eq!(diff.assets[&3].value, 200.0, tol);
+
+        // closed position removed entirely
+        after.assets.remove(&2);
+        assert_eq!(before.diff(&after).closed_positions, vec![2]);
+        let unchanged = before.diff(&before);
+        assert!(unchanged.assets.values().all(|d| d.quantity == 0.0));
+    }
+
+    #[test]
+    fn test_reconcile_positions() {
+        let eur = Currency::new(None, CurrencyISOCode::new("EUR").unwrap(), None);
+        let mut computed = PortfolioPosition::new(eur);
+        for (asset_id, quantity) in [(1, 10.0), (2, 5.0), (3, 20.0), (4, 0.0)] {
+            let mut pos = Position::new(Some(asset_id), eur);
+            pos.position = quantity;
+            computed.assets.insert(asset_id, pos);
+        }
+        let reported = BTreeMap::from([(1, 10.0 + 1e-9), (2, 4.0), (5, 7.0)]);
+
+        let diffs = reconcile_positions(&computed, &reported, 1e-6);
+        assert_eq!(
+            diffs,
+            vec![
+                ReconDiff::QuantityMismatch {
+                    asset_id: 2,
+                    computed: 5.0,
+                    reported: 4.0
+                },
+                ReconDiff::Missing {
+                    asset_id: 3,
+                    computed: 20.0
+                },
+                ReconDiff::Extra {
+                    asset_id: 5,
+                    reported: 7.0
+                },
+            ]
+        );
+        let reported = BTreeMap::from([(1, 10.0), (2, 5.0), (3, 20.0)]);
+        assert!(reconcile_positions(&computed, &reported, 1e-6).is_empty());
+    }
+
+    #[test]
+    fn test_invalid_quotes_in_totals() {
+        let tol = 1e-11;
+        let eur = Currency::new(None, CurrencyISOCode::new("EUR").unwrap(), None);
+        let mut portfolio = PortfolioPosition::new(eur);
+        let mut valid = Position::new(Some(1), eur);
+        valid.position = 10.0;
+        valid.purchase_value = -1000.0;
+        valid.last_quote = Some(110.0);
+        portfolio.assets.insert(1, valid);
+        let mut invalid = Position::new(Some(2), eur);
+        invalid.position = 5.0;
+        invalid.purchase_value = -500.0;
+        invalid.last_quote = Some(f64::NAN);
+        invalid.last_quote_time = Some(Local::now());
+        portfolio.assets.insert(2, invalid);
+
+        let totals = portfolio.calc_totals();
+        assert!(totals.value.is_finite());
+        assert_fuzzy_eq!(totals.value, 1600.0, tol);
+        assert_fuzzy_eq!(totals.unrealized_pnl, 100.0, tol);
+        let invalid = &portfolio.assets[&2];
+        assert_eq!(invalid.last_quote, Some(100.0));
+        assert_eq!(invalid.last_quote_time, None);
+        assert!(invalid.warning.is_some());
+        assert!(portfolio.assets[&1].warning.is_none());
+
+        // infinite quote without any purchase value to fall back to
+        let mut zero = Position::new(Some(3), eur);
+        zero.last_quote = Some(f64::INFINITY);
+        portfolio.assets.insert(3, zero);
+        assert!(portfolio.calc_totals().value.is_finite());
+        assert_eq!(portfolio.assets[&3].last_quote, None);
+    }
+
+    #[test]
+    fn test_annualize_return() {
+        let tol = 1e-10;
+        assert_fuzzy_eq!(annualize_return(0.1, 0.5), 0.21, tol);
+        assert_fuzzy_eq!(annualize_return(0.1, 1.0), 0.1, tol);
+        assert_fuzzy_eq!(annualize_return(0.21, 2.0), 0.1, tol);
+        assert_fuzzy_eq!(annualize_return(0.1, 0.0), 0.1, tol);
+        assert_fuzzy_eq!(annualize_return(0.1, -1.0), 0.1, tol);
+    }
+
+    #[test]
+    fn test_technical_positions() {
+        let tol = 1e-11;
+        let eur = Currency::new(None, CurrencyISOCode::new("EUR").unwrap(), None);
+        let mut portfolio = PortfolioPosition::new(eur);
+        let mut stock = Position::new(Some(1), eur);
+        stock.position = 10.0;
+        stock.last_quote = Some(100.0);
+        portfolio.assets.insert(1, stock);
+        let mut account = Position::new(Some(2), eur);
+        account.position = 250.0;
+        account.last_quote = Some(1.0);
+        account.technical = true;
+        portfolio.assets.insert(2, account);
+
+        let report = portfolio.report_positions();
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].asset_id, Some(1));
+        assert!(Position::new(None, eur).is_technical());
+
+        let totals = portfolio.calc_totals();
+        assert_fuzzy_eq!(totals.value, 1250.0, tol);
+    }
+
+    #[test]
+    fn test_transaction_summary() {
+        let tol = 1e-11;
+        let eur = Currency::new(None, CurrencyISOCode::new("EUR").unwrap(), None);
+        let transaction = |transaction_type, amount| Transaction {
+            id: None,
+            transaction_type,
+            cash_flow: CashFlow::new(amount, eur, NaiveDate::from_ymd(2020, 1, 2)),
+            note: None,
+            time_stamp: None,
+        };
+        let transactions = vec![
+            transaction(TransactionType::Cash, 10000.0),
+            transaction(TransactionType::Cash, -2000.0),
+            transaction(
+                TransactionType::Asset {
+                    asset_id: 1,
+                    position: 10.0,
+                },
+                -1000.0,
+            ),
+            transaction(
+                TransactionType::Fee {
+                    transaction_ref: None,
+                },
+                -10.0,
+            ),
+            transaction(
+                TransactionType::Fee {
+                    transaction_ref: None,
+                },
+                -5.5,
+            ),
+            transaction(TransactionType::Dividend { asset_id: 1 }, 25.0),
+        ];
+        let summary = transaction_summary(&transactions);
+        assert_eq!(summary.len(), 4);
+        assert_eq!(summary["c"].0, 2);
+        assert_fuzzy_eq!(summary["c"].1, 8000.0, tol);
+        assert_eq!(summary["a"].0, 1);
+        assert_fuzzy_eq!(summary["a"].1, -1000.0, tol);
+        assert_eq!(summary["f"].0, 2);
+        assert_fuzzy_eq!(summary["f"].1, -15.5, tol);
+        assert_eq!(summary["d"].0, 1);
+        assert_fuzzy_eq!(summary["d"].1, 25.0, tol);
+        assert!(!summary.contains_key("t"));
+        assert!(transaction_summary(&[]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_try_add_quote() {
+        let db = prepare_db().await;
+
+        let stock_id = db
+            .insert_asset(&Asset::Stock(Stock::new(
+                None,
+                "No Quote Stock".to_string(),
+                None,
+                None,
+                None,
+            )))
+            .await
+            .unwrap();
+        let market = Market::new(Arc::new(db)).await;
+        let eur = market.get_currency_from_str("EUR").await.unwrap();
+        let time = make_time(2020, 1, 2, 10, 0, 0).unwrap();
+
+        let mut position = Position::new(Some(stock_id), eur);
+        let err = position.try_add_quote(time, &market).await.unwrap_err();
+        assert!(matches!(err, PositionError::NoPrice(id) if id == stock_id));
+        assert_eq!(position.last_quote, None);
+
+        // fall back to purchase price
+        position.position = 10.0;
+        position.purchase_value = -1000.0;
+        position.try_add_quote(time, &market).await.unwrap();
+        assert_eq!(position.last_quote, Some(100.0));
+        assert_eq!(position.last_quote_time, None);
+    }
+
+    #[tokio::test]
+    async fn test_value_in_reporting_currency() {
+        let tol = 1e-10;
+        let db = prepare_db().await;
+        let market = Market::new(Arc::new(db)).await;
+        let eur = market.get_currency_from_str("EUR").await.unwrap();
+        let usd = market.get_currency_from_str("USD").await.unwrap();
+        let time = make_time(2021, 11, 1, 18, 0, 0).unwrap();
+        crate::fx_rates::insert_fx_quote(1.2, eur, usd, time, market.db())
+            .await
+            .unwrap();
+
+        let mut portfolio = PortfolioPosition::new(eur);
+        portfolio.cash.position = 1000.0;
+        let mut stock = Position::new(Some(1), eur);
+        stock.position = 10.0;
+        stock.last_quote = Some(50.0);
+        portfolio.assets.insert(1, stock);
+
+        let value = portfolio.value_in(eur, &market, time).await.unwrap();
+        assert_fuzzy_eq!(value, 1500.0, tol);
+        let value = portfolio.value_in(usd, &market, time).await.unwrap();
+        assert_fuzzy_eq!(value, 1800.0, tol);
+        // no fx rate available before first quote
+        let earlier = make_time(2021, 10, 1, 18, 0, 0).unwrap();
+        assert!(portfolio.value_in(usd, &market, earlier).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_warning_reset_on_new_quote() {
+        let db = prepare_db().await;
+        let market = Market::new(Arc::new(db)).await;
+        let eur = market.get_currency_from_str("EUR").await.unwrap();
+        let asset_id = market
+            .db()
+            .insert_asset(&Asset::Stock(Stock::new(
+                None,
+                "Stock".to_string(),
+                None,
+                None,
+                None,
+            )))
+            .await
+            .unwrap();
+        let ticker = market
+            .db()
+            .insert_ticker(&Ticker {
+                id: None,
+                name: "STOCK.DE".to_string(),
+                asset: asset_id,
+                priority: 10,
+                currency: eur,
+                source: "manual".to_string(),
+                factor: 1.0,
+                tz: None,
+                cal: None,
+                active: true,
+            })
+            .await
+            .unwrap();
+        for (day, price) in [(1, f64::NAN), (3, 120.0)] {
+            market
+                .db()
+                .insert_quote(&Quote {
+                    id: None,
+                    ticker,
+                    price,
+                    time: make_time(2021, 6, day, 18, 0, 0).unwrap(),
+                    volume: None,
+                    stale: false,
+                })
+                .await
+                .unwrap();
+        }
+
+        let mut position = Position::new(Some(asset_id), eur);
+        position.position = 10.0;
+        position.purchase_value = -1000.0;
+        let start = make_time(2021, 6, 2, 18, 0, 0).unwrap();
+        position.try_add_quote(start, &market).await.unwrap();
+        assert_eq!(position.last_quote, Some(100.0));
+        assert!(position.warning.is_some());
+
+        let end = make_time(2021, 6, 4, 18, 0, 0).unwrap();
+        position.try_add_quote(end, &market).await.unwrap();
+        assert_eq!(position.last_quote, Some(120.0));
+        assert!(position.warning.is_none());
+    }
 }
@@ -1,10 +1,11 @@
 use async_trait::async_trait;
 
 use crate::datatypes::{
-    Asset, AssetHandler, AssetSelector, Currency, CurrencyISOCode, DataError, DataItem, Stock,
+    Asset, AssetHandler, AssetSelector, Currency, CurrencyISOCode, DataError, DataItem, Index,
+    Stock,
 };
 
-use super::PostgresDB;
+use super::{map_not_found, PostgresDB};
 
 /// helper struct
 struct ID {
@@ -53,6 +54,18 @@ impl AssetHandler for PostgresDB {
                 tx.commit().await?;
                 Ok(id)
             }
+            Asset::Index(i) => {
+                sqlx::query!(
+                    "INSERT INTO indices (id, name, note) VALUES ($1, $2, $3)",
+                    id,
+                    i.name,
+                    i.note
+                )
+                .execute(&self.pool)
+                .await?;
+                tx.commit().await?;
+                Ok(id)
+            }
         }
     }
 
@@ -79,6 +92,12 @@ impl AssetHandler for PostgresDB {
                         .ok()
                 }
             }
+            Asset::Index(i) => {
+                sqlx::query_as!(ID, "SELECT id FROM indices WHERE name = $1", i.name)
+                    .fetch_one(&self.pool)
+                    .await
+                    .ok()
+            }
         };
 
         id.map(|x| x.id)
@@ -93,7 +112,8 @@ impl AssetHandler for PostgresDB {
             id,
         )
         .fetch_one(&self.pool)
-        .await?;
+        .await
+        .map_err(|e| map_not_found(e, &format!("asset with id {}", id)))?;
 
         match row.asset_class.as_str() {
             "currency" => {
@@ -138,6 +158,21 @@ impl AssetHandler for PostgresDB {
                     row.note,
                 )))
             }
+            "index" => {
+                let row = sqlx::query!(
+                    r#"SELECT
+                        id,
+                        name,
+                        note
+                     FROM indices
+                     WHERE id = $1"#,
+                    id,
+                )
+                .fetch_one(&self.pool)
+                .await?;
+
+                Ok(Asset::Index(Index::new(Some(row.id), row.name, row.note)))
+            }
             _ => Err(DataError::InvalidAsset(row.asset_class)),
         }
     }
@@ -155,7 +190,8 @@ impl AssetHandler for PostgresDB {
             isin.to_string(),
         )
         .fetch_one(&self.pool)
-        .await?;
+        .await
+        .map_err(|e| map_not_found(e, &format!("asset with isin {}", isin)))?;
 
         Ok(Asset::Stock(Stock::new(
             Some(row.id),
@@ -190,12 +226,14 @@ impl AssetHandler for PostgresDB {
                 a.asset_class as "asset_class!",
                 CASE 
                     WHEN a.asset_class='currency' THEN c.iso_code 
+                    WHEN a.asset_class='index' THEN i.name
                     ELSE s.name
                 END as "name!"
             FROM 
                 assets a
                 LEFT JOIN stocks s ON a.id = s.id
-                LEFT JOIN currencies c ON a.id = c.id"#
+                LEFT JOIN currencies c ON a.id = c.id
+                LEFT JOIN indices i ON a.id = i.id"#
         )
         .fetch_all(&self.pool)
         .await?
@@ -257,6 +295,27 @@ impl AssetHandler for PostgresDB {
                     ))
                 }
             }
+            Asset::Index(i) => {
+                if let Some(id) = i.id {
+                    sqlx::query!(
+                        "UPDATE indices 
+                        SET 
+                            name=$2,
+                            note=$3
+                        WHERE id=$1;",
+                        id,
+                        i.name,
+                        i.note
+                    )
+                    .execute(&self.pool)
+                    .await?;
+                    Ok(())
+                } else {
+                    Err(DataError::NotFound(
+                        "not yet stored to database".to_string(),
+                    ))
+                }
+            }
         }
     }
 
@@ -287,12 +346,148 @@ impl AssetHandler for PostgresDB {
                 tx.commit().await?;
                 Ok(())
             }
+            "index" => {
+                let tx = self.pool.begin().await?;
+                sqlx::query!("DELETE FROM indices WHERE id=$1;", id)
+                    .execute(&self.pool)
+                    .await?;
+                sqlx::query!("DELETE FROM assets WHERE id=$1;", id)
+                    .execute(&self.pool)
+                    .await?;
+                tx.commit().await?;
+                Ok(())
+            }
             _ => Err(DataError::InvalidAsset(
                 "Could not delete unknown asset".to_string(),
             )),
         }
     }
 
+    async fn delete_asset_cascade(&self, id: i32) -> Result<(), DataError> {
+        let mut tx = self.pool.begin().await?;
+        let row = sqlx::query!("SELECT asset_class FROM assets WHERE id=$1", id)
+            .fetch_one(&mut tx)
+            .await
+            .map_err(|e| map_not_found(e, &format!("asset with id {}", id)))?;
+        sqlx::query!(
+            "DELETE FROM quotes WHERE ticker_id IN
+                (SELECT id FROM ticker WHERE asset_id=$1 OR currency_id=$1);",
+            id
+        )
+        .execute(&mut tx)
+        .await?;
+        sqlx::query!(
+            "DELETE FROM ticker WHERE asset_id=$1 OR currency_id=$1;",
+            id
+        )
+        .execute(&mut tx)
+        .await?;
+        match row.asset_class.as_str() {
+            "currency" => {
+                sqlx::query!("DELETE FROM currencies WHERE id=$1;", id)
+                    .execute(&mut tx)
+                    .await?;
+            }
+            "stock" => {
+                sqlx::query!("DELETE FROM stocks WHERE id=$1;", id)
+                    .execute(&mut tx)
+                    .await?;
+            }
+            "index" => {
+                sqlx::query!("DELETE FROM indices WHERE id=$1;", id)
+                    .execute(&mut tx)
+                    .await?;
+            }
+            _ => {
+                return Err(DataError::InvalidAsset(
+                    "Could not delete unknown asset".to_string(),
+                ))
+            }
+        }
+        sqlx::query!("DELETE FROM assets WHERE id=$1;", id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn merge_assets(&self, keep_id: i32, merge_id: i32) -> Result<(), DataError> {
+        if keep_id == merge_id {
+            return Err(DataError::InvalidAsset(
+                "Could not merge asset with itself".to_string(),
+            ));
+        }
+        let mut tx = self.pool.begin().await?;
+        let keep = sqlx::query!("SELECT asset_class FROM assets WHERE id=$1", keep_id)
+            .fetch_one(&mut tx)
+            .await
+            .map_err(|e| map_not_found(e, &format!("asset with id {}", keep_id)))?;
+        let merge = sqlx::query!("SELECT asset_class FROM assets WHERE id=$1", merge_id)
+            .fetch_one(&mut tx)
+            .await
+            .map_err(|e| map_not_found(e, &format!("asset with id {}", merge_id)))?;
+        if keep.asset_class != merge.asset_class {
+            return Err(DataError::InvalidAsset(
+                "Could not merge assets of different asset classes".to_string(),
+            ));
+        }
+        sqlx::query!(
+            "UPDATE transactions SET asset_id=$1 WHERE asset_id=$2;",
+            keep_id,
+            merge_id
+        )
+        .execute(&mut tx)
+        .await?;
+        sqlx::query!(
+            "UPDATE ticker SET asset_id=$1 WHERE asset_id=$2;",
+            keep_id,
+            merge_id
+        )
+        .execute(&mut tx)
+        .await?;
+        match merge.asset_class.as_str() {
+            "currency" => {
+                sqlx::query!(
+                    "UPDATE transactions SET cash_currency_id=$1 WHERE cash_currency_id=$2;",
+                    keep_id,
+                    merge_id
+                )
+                .execute(&mut tx)
+                .await?;
+                sqlx::query!(
+                    "UPDATE ticker SET currency_id=$1 WHERE currency_id=$2;",
+                    keep_id,
+                    merge_id
+                )
+                .execute(&mut tx)
+                .await?;
+                sqlx::query!("DELETE FROM currencies WHERE id=$1;", merge_id)
+                    .execute(&mut tx)
+                    .await?;
+            }
+            "stock" => {
+                sqlx::query!("DELETE FROM stocks WHERE id=$1;", merge_id)
+                    .execute(&mut tx)
+                    .await?;
+            }
+            "index" => {
+                sqlx::query!("DELETE FROM indices WHERE id=$1;", merge_id)
+                    .execute(&mut tx)
+                    .await?;
+            }
+            _ => {
+                return Err(DataError::InvalidAsset(
+                    "Could not merge unknown asset".to_string(),
+                ))
+            }
+        }
+        sqlx::query!("DELETE FROM assets WHERE id=$1;", merge_id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
     async fn get_all_currencies(&self) -> Result<Vec<Currency>, DataError> {
         let mut currencies = Vec::new();
         for row in sqlx::query!(
@@ -369,3 +564,167 @@ impl AssetHandler for PostgresDB {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    use crate::datatypes::{
+        date_time_helper::make_time, CashFlow, Quote, QuoteHandler, Ticker, Transaction,
+        TransactionHandler, TransactionType,
+    };
+
+    #[tokio::test]
+    async fn missing_asset_is_not_found() {
+        let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
+        assert!(
+            db_url.is_ok(),
+            "environment variable $FINQL_TEST_DATABASE_URL is not set"
+        );
+        let db = PostgresDB::new(&db_url.unwrap()).await.unwrap();
+        db.clean().await.unwrap();
+
+        let result = db.get_asset_by_id(1).await;
+        assert!(matches!(result, Err(DataError::NotFound(_))));
+        let result = db.get_asset_by_isin("DE0000000000").await;
+        assert!(matches!(result, Err(DataError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn delete_asset_with_ticker_and_quotes() {
+        let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
+        assert!(
+            db_url.is_ok(),
+            "environment variable $FINQL_TEST_DATABASE_URL is not set"
+        );
+        let db = PostgresDB::new(&db_url.unwrap()).await.unwrap();
+        db.clean().await.unwrap();
+
+        let eur = Currency::new(None, CurrencyISOCode::new("EUR").unwrap(), None);
+        let eur_id = db.insert_asset(&Asset::Currency(eur)).await.unwrap();
+        let eur = Currency::new(Some(eur_id), eur.iso_code, None);
+        let stock = Stock::new(None, "Apple".to_string(), None, None, None);
+        let stock_id = db.insert_asset(&Asset::Stock(stock)).await.unwrap();
+        let ticker_id = db
+            .insert_ticker(&Ticker {
+                id: None,
+                asset: stock_id,
+                name: "AAPL".to_string(),
+                currency: eur,
+                source: "manual".to_string(),
+                priority: 1,
+                factor: 1.0,
+                tz: None,
+                cal: None,
+                active: true,
+            })
+            .await
+            .unwrap();
+        db.insert_quote(&Quote {
+            id: None,
+            ticker: ticker_id,
+            price: 100.0,
+            time: make_time(2020, 1, 2, 18, 0, 0).unwrap(),
+            volume: None,
+            stale: false,
+        })
+        .await
+        .unwrap();
+
+        db.delete_asset_cascade(stock_id).await.unwrap();
+        assert!(matches!(
+            db.get_asset_by_id(stock_id).await,
+            Err(DataError::NotFound(_))
+        ));
+        assert!(db.get_all_ticker().await.unwrap().is_empty());
+        let quotes: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM quotes")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(quotes, 0);
+        let stocks: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM stocks")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(stocks, 0);
+        // the quote currency is not affected
+        assert!(db.get_asset_by_id(eur_id).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn merge_duplicate_assets() {
+        let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
+        assert!(
+            db_url.is_ok(),
+            "environment variable $FINQL_TEST_DATABASE_URL is not set"
+        );
+        let db = PostgresDB::new(&db_url.unwrap()).await.unwrap();
+        db.clean().await.unwrap();
+
+        let eur = db
+            .get_or_new_currency(CurrencyISOCode::new("EUR").unwrap())
+            .await
+            .unwrap();
+        let isin_stock = Stock::new(
+            None,
+            "Apple Inc.".to_string(),
+            Some("US0378331005".to_string()),
+            None,
+            None,
+        );
+        let keep_id = db.insert_asset(&Asset::Stock(isin_stock)).await.unwrap();
+        let vendor_stock = Stock::new(None, "AAPL".to_string(), None, None, None);
+        let merge_id = db.insert_asset(&Asset::Stock(vendor_stock)).await.unwrap();
+        for (asset_id, position) in [(keep_id, 10.0), (merge_id, 5.0)] {
+            db.insert_transaction(&Transaction {
+                id: None,
+                transaction_type: TransactionType::Asset { asset_id, position },
+                cash_flow: CashFlow::new(-100.0 * position, eur, NaiveDate::from_ymd(2020, 1, 2)),
+                note: None,
+                time_stamp: None,
+            })
+            .await
+            .unwrap();
+        }
+        db.insert_ticker(&Ticker {
+            id: None,
+            asset: merge_id,
+            name: "AAPL".to_string(),
+            currency: eur,
+            source: "manual".to_string(),
+            priority: 1,
+            factor: 1.0,
+            tz: None,
+            cal: None,
+            active: true,
+        })
+        .await
+        .unwrap();
+
+        assert!(matches!(
+            db.merge_assets(keep_id, eur.id.unwrap()).await,
+            Err(DataError::InvalidAsset(_))
+        ));
+        db.merge_assets(keep_id, merge_id).await.unwrap();
+        assert!(matches!(
+            db.get_asset_by_id(merge_id).await,
+            Err(DataError::NotFound(_))
+        ));
+        let transactions = db.get_all_transactions().await.unwrap();
+        assert_eq!(transactions.len(), 2);
+        for transaction in &transactions {
+            assert!(matches!(
+                transaction.transaction_type,
+                TransactionType::Asset { asset_id, .. } if asset_id == keep_id
+            ));
+        }
+        let ticker = db.get_all_ticker().await.unwrap();
+        assert_eq!(ticker.len(), 1);
+        assert_eq!(ticker[0].asset, keep_id);
+        assert!(matches!(
+            db.merge_assets(keep_id, merge_id).await,
+            Err(DataError::NotFound(_))
+        ));
+    }
+}
@@ -1,11 +1,22 @@
 ///! Implementation of PostgreSQL data handler
 use sqlx::postgres::{PgPoolOptions, Postgres};
 
+use crate::datatypes::DataError;
+
 pub mod asset_handler;
 pub mod object_handler;
 pub mod quote_handler;
 pub mod transaction_handler;
 
+/// Map errors of queries for a single row to `DataError::NotFound` if no row has been found,
+/// any other error is passed through as database error
+pub(crate) fn map_not_found(err: sqlx::Error, what: &str) -> DataError {
+    match err {
+        sqlx::Error::RowNotFound => DataError::NotFound(what.to_string()),
+        err => DataError::DatabaseError(err),
+    }
+}
+
 /// Struct to handle connections to postgres databases
 pub struct PostgresDB {
     /// pool is made public to allow extending this struct outside of the library
@@ -26,6 +37,9 @@ impl PostgresDB {
         sqlx::query!("DROP TABLE IF EXISTS transactions")
             .execute(&self.pool)
             .await?;
+        sqlx::query!("DROP TABLE IF EXISTS stock_splits")
+            .execute(&self.pool)
+            .await?;
         sqlx::query!("DROP TABLE IF EXISTS quotes")
             .execute(&self.pool)
             .await?;
@@ -41,6 +55,9 @@ impl PostgresDB {
         sqlx::query!("DROP TABLE IF EXISTS stocks")
             .execute(&self.pool)
             .await?;
+        sqlx::query!("DROP TABLE IF EXISTS indices")
+            .execute(&self.pool)
+            .await?;
         sqlx::query!("DROP TABLE IF EXISTS assets")
             .execute(&self.pool)
             .await?;
@@ -82,6 +99,16 @@ impl PostgresDB {
         )
         .execute(&self.pool)
         .await?;
+        sqlx::query!(
+            "CREATE TABLE IF NOT EXISTS indices (
+                  id INTEGER PRIMARY KEY,
+                  name TEXT NOT NULL UNIQUE,
+                  note TEXT,
+                  FOREIGN KEY(id) REFERENCES assets(id)
+                )"
+        )
+        .execute(&self.pool)
+        .await?;
         sqlx::query!(
             "CREATE TABLE IF NOT EXISTS transactions (
                 id SERIAL PRIMARY KEY,
@@ -93,6 +120,7 @@ impl PostgresDB {
                 related_trans INTEGER,
                 position FLOAT8,
                 note TEXT,
+                time_stamp TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
                 FOREIGN KEY(asset_id) REFERENCES assets(id),
                 FOREIGN KEY(cash_currency_id) REFERENCES currencies(id),
                 FOREIGN KEY(related_trans) REFERENCES transactions(id)
@@ -111,6 +139,8 @@ impl PostgresDB {
                 factor FLOAT8 NOT NULL DEFAULT 1.0,
                 tz TEXT,
                 cal TEXT,
+                active BOOLEAN NOT NULL DEFAULT TRUE,
+                UNIQUE (name, source),
                 FOREIGN KEY(asset_id) REFERENCES assets(id),
                 FOREIGN KEY(currency_id) REFERENCES currencies(id)
             )"
@@ -124,15 +154,28 @@ impl PostgresDB {
                 price FLOAT8 NOT NULL,
                 time TIMESTAMP WITH TIME ZONE NOT NULL,
                 volume FLOAT8,
+                stale BOOLEAN NOT NULL DEFAULT FALSE,
                 FOREIGN KEY(ticker_id) REFERENCES ticker(id) 
             )"
         )
         .execute(&self.pool)
         .await?;
+        sqlx::query!(
+            "CREATE TABLE IF NOT EXISTS stock_splits (
+                asset_id INTEGER NOT NULL,
+                split_date DATE NOT NULL,
+                ratio FLOAT8 NOT NULL,
+                PRIMARY KEY(asset_id, split_date),
+                FOREIGN KEY(asset_id) REFERENCES assets(id)
+            )"
+        )
+        .execute(&self.pool)
+        .await?;
 
         sqlx::query!(
             "CREATE TABLE IF NOT EXISTS objects (
             id TEXT PRIMARY KEY,
+            object_type TEXT,
             object JSON NOT NULL)"
         )
         .execute(&self.pool)
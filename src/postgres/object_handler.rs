@@ -12,14 +12,15 @@ impl ObjectHandler for PostgresDB {
     async fn store_object<T: Serialize + Sync>(
         &self,
         id: &str,
-        _object_type: &str,
+        object_type: &str,
         object: &T,
     ) -> Result<(), DataError> {
         let object_json = serde_json::to_value(&object)?;
 
         sqlx::query!(
-            "INSERT INTO objects (id, object) VALUES ($1, $2)",
+            "INSERT INTO objects (id, object_type, object) VALUES ($1, $2, $3)",
             id,
+            object_type,
             object_json
         )
         .execute(&self.pool)
@@ -34,4 +35,38 @@ impl ObjectHandler for PostgresDB {
         let object: T = serde_json::from_value(row.object)?;
         Ok(object)
     }
+
+    async fn update_object<T: Serialize + Sync>(
+        &self,
+        id: &str,
+        object: &T,
+    ) -> Result<(), DataError> {
+        let object_json = serde_json::to_value(object)?;
+
+        sqlx::query!("UPDATE objects SET object=$2 WHERE id=$1", id, object_json)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_object(&self, id: &str) -> Result<(), DataError> {
+        sqlx::query!("DELETE FROM objects WHERE id=$1", id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_object_ids(&self, object_type: &str) -> Result<Vec<String>, DataError> {
+        let mut ids = Vec::new();
+        for row in sqlx::query!(
+            "SELECT id FROM objects WHERE object_type=$1 ORDER BY id",
+            object_type
+        )
+        .fetch_all(&self.pool)
+        .await?
+        {
+            ids.push(row.id);
+        }
+        Ok(ids)
+    }
 }
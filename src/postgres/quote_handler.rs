@@ -6,9 +6,11 @@ use std::sync::Arc;
 
 use crate::datatypes::{
     Asset, AssetHandler, Currency, CurrencyISOCode, DataError, Quote, QuoteHandler, Ticker,
+    TiebreakStrategy,
 };
 
-use super::PostgresDB;
+use super::{map_not_found, PostgresDB};
+use crate::market_quotes::stats::TickerQuoteCoverage;
 
 /// PostgreSQL implementation of quote handler
 #[async_trait]
@@ -21,8 +23,8 @@ impl QuoteHandler for PostgresDB {
     async fn insert_ticker(&self, ticker: &Ticker) -> Result<i32, DataError> {
         let cid = ticker.currency.id;
         let row = sqlx::query!(
-            "INSERT INTO ticker (name, asset_id, source, priority, currency_id, factor, tz, cal)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id",
+            "INSERT INTO ticker (name, asset_id, source, priority, currency_id, factor, tz, cal, active)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING id",
             ticker.name,
             (ticker.asset as i32),
             (ticker.source.to_string()),
@@ -30,7 +32,8 @@ impl QuoteHandler for PostgresDB {
             cid,
             ticker.factor,
             ticker.tz,
-            ticker.cal
+            ticker.cal,
+            ticker.active
         )
         .fetch_one(&self.pool)
         .await?;
@@ -52,10 +55,26 @@ impl QuoteHandler for PostgresDB {
     }
 
     async fn insert_if_new_ticker(&self, ticker: &Ticker) -> Result<i32, DataError> {
-        match self.get_ticker_id(&ticker.name).await {
-            Some(id) => Ok(id),
-            None => self.insert_ticker(ticker).await,
-        }
+        let cid = ticker.currency.id;
+        // The no-op update makes sure the id of an already existing ticker is returned
+        let row = sqlx::query!(
+            "INSERT INTO ticker (name, asset_id, source, priority, currency_id, factor, tz, cal, active)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                ON CONFLICT (name, source) DO UPDATE SET name = EXCLUDED.name
+                RETURNING id",
+            ticker.name,
+            (ticker.asset as i32),
+            (ticker.source.to_string()),
+            ticker.priority,
+            cid,
+            ticker.factor,
+            ticker.tz,
+            ticker.cal,
+            ticker.active
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(row.id)
     }
 
     async fn get_ticker_by_id(&self, id: i32) -> Result<Ticker, DataError> {
@@ -68,6 +87,7 @@ impl QuoteHandler for PostgresDB {
                     t.factor,
                     t.tz,
                     t.cal,
+                    t.active,
                     c.id AS currency_id,
                     c.iso_code AS currency_iso_code,
                     c.rounding_digits AS currency_rounding_digits
@@ -77,7 +97,8 @@ impl QuoteHandler for PostgresDB {
             (id as i32),
         )
         .fetch_one(&self.pool)
-        .await?;
+        .await
+        .map_err(|e| map_not_found(e, &format!("ticker with id {}", id)))?;
         let name = row.name;
         let asset = row.asset_id;
         let source = row.source;
@@ -97,6 +118,7 @@ impl QuoteHandler for PostgresDB {
             factor: row.factor,
             tz: row.tz,
             cal: row.cal,
+            active: row.active,
         })
     }
 
@@ -112,6 +134,7 @@ impl QuoteHandler for PostgresDB {
                    t.factor AS "factor!",
                    t.tz,
                    t.cal,
+                   t.active AS "active!",
                    c.id AS "currency_id!",
                    c.iso_code AS "currency_iso_code!",
                    c.rounding_digits AS "currency_rounding_digits!"
@@ -139,6 +162,7 @@ impl QuoteHandler for PostgresDB {
                 factor,
                 tz: row.tz,
                 cal: row.cal,
+                active: row.active,
             });
         }
         Ok(all_ticker)
@@ -156,6 +180,7 @@ impl QuoteHandler for PostgresDB {
                    t.factor,
                    t.tz,
                    t.cal,
+                   t.active,
                    c.id AS currency_id,
                    c.iso_code AS currency_iso_code,
                    c.rounding_digits AS currency_rounding_digits
@@ -185,6 +210,7 @@ impl QuoteHandler for PostgresDB {
                 factor,
                 tz: row.tz,
                 cal: row.cal,
+                active: row.active,
             });
         }
         Ok(all_ticker)
@@ -202,6 +228,7 @@ impl QuoteHandler for PostgresDB {
                    t.factor,
                    t.tz,
                    t.cal,
+                   t.active,
                    c.id AS currency_id,
                    c.iso_code AS currency_iso_code,
                    c.rounding_digits AS currency_rounding_digits
@@ -231,6 +258,7 @@ impl QuoteHandler for PostgresDB {
                 factor,
                 tz: row.tz,
                 cal: row.cal,
+                active: row.active,
             });
         }
         Ok(all_ticker)
@@ -245,7 +273,8 @@ impl QuoteHandler for PostgresDB {
         let id = ticker.id.unwrap() as i32;
         let cid = ticker.currency.id.expect("currency asset_id required");
         sqlx::query!(
-                "UPDATE ticker SET name = $2, asset_id = $3, source = $4, priority = $5, currency_id = $6, factor = $7, tz = $8, cal = $9
+                "UPDATE ticker SET name = $2, asset_id = $3, source = $4, priority = $5, currency_id = $6, factor = $7, tz = $8, cal = $9,
+                active = $10
                 WHERE id = $1",
                 id,
                 ticker.name,
@@ -255,12 +284,20 @@ impl QuoteHandler for PostgresDB {
                 (cid as i32),
                 ticker.factor,
                 ticker.tz,
-                ticker.cal
+                ticker.cal,
+                ticker.active
             )
             .execute(&self.pool).await?;
         Ok(())
     }
 
+    async fn deactivate_ticker(&self, id: i32) -> Result<(), DataError> {
+        sqlx::query!("UPDATE ticker SET active = FALSE WHERE id=$1;", id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
     async fn delete_ticker(&self, id: i32) -> Result<(), DataError> {
         sqlx::query!("DELETE FROM ticker WHERE id=$1;", (id as i32))
             .execute(&self.pool)
@@ -271,12 +308,13 @@ impl QuoteHandler for PostgresDB {
     // insert, get, update and delete for market data sources
     async fn insert_quote(&self, quote: &Quote) -> Result<i32, DataError> {
         let row = sqlx::query!(
-            "INSERT INTO quotes (ticker_id, price, time, volume) 
-                VALUES ($1, $2, $3, $4) RETURNING id",
+            "INSERT INTO quotes (ticker_id, price, time, volume, stale) 
+                VALUES ($1, $2, $3, $4, $5) RETURNING id",
             (quote.ticker as i32),
             quote.price,
             quote.time,
             quote.volume,
+            quote.stale,
         )
         .fetch_one(&self.pool)
         .await?;
@@ -284,6 +322,33 @@ impl QuoteHandler for PostgresDB {
         Ok(id)
     }
 
+    async fn insert_quote_if_newer(&self, quote: &Quote) -> Result<Option<i32>, DataError> {
+        let mut tx = self.pool.begin().await?;
+        // Lock the ticker, so that concurrent inserts for the same ticker are serialized
+        sqlx::query!(
+            "SELECT id FROM ticker WHERE id = $1 FOR UPDATE",
+            (quote.ticker as i32),
+        )
+        .fetch_optional(&mut tx)
+        .await?;
+        let row = sqlx::query!(
+            "WITH latest AS (SELECT MAX(time) AS time FROM quotes WHERE ticker_id = $1)
+            INSERT INTO quotes (ticker_id, price, time, volume, stale)
+                SELECT $1::int4, $2::float8, $3::timestamptz, $4::float8, $5::bool FROM latest
+                WHERE latest.time IS NULL OR $3 > latest.time
+                RETURNING id",
+            (quote.ticker as i32),
+            quote.price,
+            quote.time,
+            quote.volume,
+            quote.stale,
+        )
+        .fetch_optional(&mut tx)
+        .await?;
+        tx.commit().await?;
+        Ok(row.map(|row| row.id))
+    }
+
     async fn get_last_fx_quote_before(
         &self,
         curr: &CurrencyISOCode,
@@ -296,6 +361,7 @@ impl QuoteHandler for PostgresDB {
                    q.price,
                    q.time,
                    q.volume,
+                   q.stale,
                    qc.id AS currency_id,
                    qc.iso_code,
                    qc.rounding_digits,
@@ -313,7 +379,8 @@ impl QuoteHandler for PostgresDB {
             time,
         )
         .fetch_one(&self.pool)
-        .await?;
+        .await
+        .map_err(|e| map_not_found(e, &format!("fx quote for {} before {}", curr, time)))?;
         let id = row.id;
         let c = Currency::new(
             Some(row.currency_id),
@@ -324,6 +391,7 @@ impl QuoteHandler for PostgresDB {
         let price = row.price;
         let time: DateTime<Local> = row.time.into();
         let volume = row.volume;
+        let stale = row.stale;
         Ok((
             Quote {
                 id: Some(id),
@@ -331,6 +399,7 @@ impl QuoteHandler for PostgresDB {
                 price,
                 time,
                 volume,
+                stale,
             },
             c,
         ))
@@ -341,8 +410,23 @@ impl QuoteHandler for PostgresDB {
         asset_id: i32,
         time: DateTime<Local>,
     ) -> Result<(Quote, Currency), DataError> {
+        self.try_get_last_quote_before_by_id(asset_id, time)
+            .await?
+            .ok_or_else(|| {
+                DataError::NotFound(format!(
+                    "quote for asset with id {} before {}",
+                    asset_id, time
+                ))
+            })
+    }
+
+    async fn try_get_last_quote_before_by_id(
+        &self,
+        asset_id: i32,
+        time: DateTime<Local>,
+    ) -> Result<Option<(Quote, Currency)>, DataError> {
         let row = sqlx::query!(
-            "SELECT q.id, q.ticker_id, q.price, q.time, q.volume, t.currency_id, t.priority
+            "SELECT q.id, q.ticker_id, q.price, q.time, q.volume, q.stale, t.currency_id, t.priority
                 FROM quotes q
                 JOIN ticker t ON t.id = q.ticker_id
                 WHERE t.asset_id = $1 AND q.time <= $2
@@ -351,27 +435,33 @@ impl QuoteHandler for PostgresDB {
             asset_id,
             time,
         )
-        .fetch_one(&self.pool)
+        .fetch_optional(&self.pool)
         .await?;
+        let row = match row {
+            Some(row) => row,
+            None => return Ok(None),
+        };
 
         let id = row.id;
         let ticker = row.ticker_id;
         let price = row.price;
         let time: DateTime<Local> = row.time.into();
         let volume = row.volume;
+        let stale = row.stale;
         let currency_id = row.currency_id;
 
         if let Ok(Asset::Currency(ca)) = self.get_asset_by_id(currency_id).await {
-            Ok((
+            Ok(Some((
                 Quote {
                     id: Some(id),
                     ticker,
                     price,
                     time,
                     volume,
+                    stale,
                 },
                 ca,
-            ))
+            )))
         } else {
             Err(DataError::InvalidAsset(format!(
                 "Couldn't find currency with id={}",
@@ -380,6 +470,106 @@ impl QuoteHandler for PostgresDB {
         }
     }
 
+    async fn get_last_quote_before_with_strategy(
+        &self,
+        asset_id: i32,
+        time: DateTime<Local>,
+        strategy: TiebreakStrategy,
+    ) -> Result<(Quote, Currency), DataError> {
+        let rows = sqlx::query!(
+            "SELECT q.id, q.ticker_id, q.price, q.time, q.volume, q.stale, t.currency_id
+                FROM quotes q
+                JOIN ticker t ON t.id = q.ticker_id
+                WHERE t.asset_id = $1 AND q.time = (
+                    SELECT MAX(q2.time) FROM quotes q2
+                    JOIN ticker t2 ON t2.id = q2.ticker_id
+                    WHERE t2.asset_id = $1 AND q2.time <= $2)
+                ORDER BY t.priority ASC, q.id ASC",
+            asset_id,
+            time,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let first = rows.first().ok_or_else(|| {
+            DataError::NotFound(format!(
+                "quote for asset with id {} before {}",
+                asset_id, time
+            ))
+        })?;
+        let row = match strategy {
+            TiebreakStrategy::HighestPriority | TiebreakStrategy::Average => first,
+            TiebreakStrategy::Latest => rows.iter().max_by_key(|row| row.id).unwrap(),
+        };
+        let mut quote = Quote {
+            id: Some(row.id),
+            ticker: row.ticker_id,
+            price: row.price,
+            time: row.time.into(),
+            volume: row.volume,
+            stale: row.stale,
+        };
+        if strategy == TiebreakStrategy::Average {
+            let prices: Vec<f64> = rows
+                .iter()
+                .filter(|r| r.currency_id == row.currency_id)
+                .map(|r| r.price)
+                .collect();
+            quote.id = None;
+            quote.price = prices.iter().sum::<f64>() / prices.len() as f64;
+        }
+
+        if let Ok(Asset::Currency(ca)) = self.get_asset_by_id(row.currency_id).await {
+            Ok((quote, ca))
+        } else {
+            Err(DataError::InvalidAsset(format!(
+                "Couldn't find currency with id={}",
+                row.currency_id
+            )))
+        }
+    }
+
+    async fn try_get_first_quote_after_by_id(
+        &self,
+        asset_id: i32,
+        time: DateTime<Local>,
+    ) -> Result<Option<(Quote, Currency)>, DataError> {
+        let row = sqlx::query!(
+            "SELECT q.id, q.ticker_id, q.price, q.time, q.volume, q.stale, t.currency_id, t.priority
+                FROM quotes q
+                JOIN ticker t ON t.id = q.ticker_id
+                WHERE t.asset_id = $1 AND q.time > $2
+                ORDER BY q.time ASC, t.priority ASC
+                LIMIT 1",
+            asset_id,
+            time,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        let row = match row {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+
+        if let Ok(Asset::Currency(ca)) = self.get_asset_by_id(row.currency_id).await {
+            Ok(Some((
+                Quote {
+                    id: Some(row.id),
+                    ticker: row.ticker_id,
+                    price: row.price,
+                    time: row.time.into(),
+                    volume: row.volume,
+                    stale: row.stale,
+                },
+                ca,
+            )))
+        } else {
+            Err(DataError::InvalidAsset(format!(
+                "Couldn't find currency with id={}",
+                row.currency_id
+            )))
+        }
+    }
+
     async fn get_quotes_in_range_by_id(
         &self,
         asset_id: i32,
@@ -388,7 +578,7 @@ impl QuoteHandler for PostgresDB {
     ) -> Result<Vec<(Quote, i32)>, DataError> {
         let mut quotes = Vec::new();
         for row in sqlx::query!(
-            "SELECT q.id, q.ticker_id, q.price, q.time, q.volume, t.currency_id, t.priority
+            "SELECT q.id, q.ticker_id, q.price, q.time, q.volume, q.stale, t.currency_id, t.priority
                 FROM quotes q
                 JOIN ticker t ON t.id = q.ticker_id
                 WHERE t.asset_id = $1 AND q.time>= $2 AND q.time <= $3
@@ -407,6 +597,43 @@ impl QuoteHandler for PostgresDB {
                     price: row.price,
                     time: row.time.into(),
                     volume: row.volume,
+                    stale: row.stale,
+                },
+                row.currency_id,
+            ));
+        }
+        Ok(quotes)
+    }
+
+    async fn get_quotes_in_range_for_assets(
+        &self,
+        asset_ids: &[i32],
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> Result<Vec<(i32, Quote, i32)>, DataError> {
+        let mut quotes = Vec::new();
+        for row in sqlx::query!(
+            "SELECT t.asset_id, q.id, q.ticker_id, q.price, q.time, q.volume, q.stale, t.currency_id, t.priority
+                FROM quotes q
+                JOIN ticker t ON t.id = q.ticker_id
+                WHERE t.asset_id = ANY($1) AND q.time>= $2 AND q.time <= $3
+                ORDER BY t.asset_id, q.time DESC, t.priority ASC",
+            asset_ids,
+            start,
+            end
+        )
+        .fetch_all(&self.pool)
+        .await?
+        {
+            quotes.push((
+                row.asset_id,
+                Quote {
+                    id: Some(row.id),
+                    ticker: row.ticker_id,
+                    price: row.price,
+                    time: row.time.into(),
+                    volume: row.volume,
+                    stale: row.stale,
                 },
                 row.currency_id,
             ));
@@ -414,10 +641,105 @@ impl QuoteHandler for PostgresDB {
         Ok(quotes)
     }
 
+    async fn get_quote_coverage_for_all_tickers(
+        &self,
+    ) -> Result<Vec<TickerQuoteCoverage>, DataError> {
+        let mut stats = Vec::new();
+        for row in sqlx::query!(
+            r#"SELECT t.id, t.name, COUNT(q.id) AS "quote_count!",
+                    MIN(q.time) AS first_quote, MAX(q.time) AS last_quote
+                FROM ticker t
+                LEFT JOIN quotes q ON q.ticker_id = t.id
+                GROUP BY t.id, t.name
+                ORDER BY t.id"#
+        )
+        .fetch_all(&self.pool)
+        .await?
+        {
+            stats.push(TickerQuoteCoverage::new(
+                row.id,
+                row.name,
+                row.quote_count as usize,
+                row.first_quote.map(|time| time.into()),
+                row.last_quote.map(|time| time.into()),
+            ));
+        }
+        Ok(stats)
+    }
+
+    async fn get_quote_range_for_asset(
+        &self,
+        asset_id: i32,
+    ) -> Result<Option<(DateTime<Local>, DateTime<Local>)>, DataError> {
+        let row = sqlx::query!(
+            r#"SELECT MIN(q.time) AS first_quote, MAX(q.time) AS last_quote
+                FROM quotes q
+                JOIN ticker t ON t.id = q.ticker_id
+                WHERE t.asset_id = $1"#,
+            asset_id,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(match (row.first_quote, row.last_quote) {
+            (Some(first), Some(last)) => Some((first.into(), last.into())),
+            _ => None,
+        })
+    }
+
+    async fn tickers_with_last_quote_time(
+        &self,
+    ) -> Result<Vec<(Ticker, Option<DateTime<Local>>)>, DataError> {
+        let mut tickers = Vec::new();
+        for row in sqlx::query!(
+            r#"SELECT
+                   t.id AS "id!",
+                   t.name AS "name!",
+                   t.asset_id AS "asset_id!",
+                   t.priority AS "priority!",
+                   t.source AS "source!",
+                   t.factor AS "factor!",
+                   t.tz,
+                   t.cal,
+                   t.active AS "active!",
+                   c.id AS "currency_id!",
+                   c.iso_code AS "currency_iso_code!",
+                   c.rounding_digits AS "currency_rounding_digits!",
+                   MAX(q.time) AS last_quote
+                 FROM ticker t
+                 JOIN currencies c ON c.id = t.currency_id
+                 LEFT JOIN quotes q ON q.ticker_id = t.id
+                 GROUP BY t.id, c.id
+                 ORDER BY t.id"#
+        )
+        .fetch_all(&self.pool)
+        .await?
+        {
+            let currency = Currency::new(
+                Some(row.currency_id),
+                CurrencyISOCode::from_str(&row.currency_iso_code)?,
+                Some(row.currency_rounding_digits),
+            );
+            let ticker = Ticker {
+                id: Some(row.id),
+                name: row.name,
+                asset: row.asset_id,
+                source: row.source,
+                priority: row.priority,
+                currency,
+                factor: row.factor,
+                tz: row.tz,
+                cal: row.cal,
+                active: row.active,
+            };
+            tickers.push((ticker, row.last_quote.map(|time| time.into())));
+        }
+        Ok(tickers)
+    }
+
     async fn get_all_quotes_for_ticker(&self, ticker_id: i32) -> Result<Vec<Quote>, DataError> {
         let mut quotes = Vec::new();
         for row in sqlx::query!(
-            "SELECT id, price, time, volume FROM quotes 
+            "SELECT id, price, time, volume, stale FROM quotes 
                 WHERE ticker_id=$1 ORDER BY time ASC;",
             (ticker_id as i32),
         )
@@ -432,11 +754,30 @@ impl QuoteHandler for PostgresDB {
                 price: row.price,
                 time,
                 volume: row.volume,
+                stale: row.stale,
             });
         }
         Ok(quotes)
     }
 
+    async fn get_last_quote_for_ticker(&self, ticker_id: i32) -> Result<Option<Quote>, DataError> {
+        let row = sqlx::query!(
+            "SELECT id, price, time, volume, stale FROM quotes
+                WHERE ticker_id=$1 ORDER BY time DESC LIMIT 1",
+            ticker_id,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|row| Quote {
+            id: Some(row.id),
+            ticker: ticker_id,
+            price: row.price,
+            time: row.time.into(),
+            volume: row.volume,
+            stale: row.stale,
+        }))
+    }
+
     async fn update_quote(&self, quote: &Quote) -> Result<(), DataError> {
         if quote.id.is_none() {
             return Err(DataError::NotFound(
@@ -445,13 +786,14 @@ impl QuoteHandler for PostgresDB {
         }
         let id = quote.id.unwrap() as i32;
         sqlx::query!(
-            "UPDATE quotes SET ticker_id=$2, price=$3, time=$4, volume=$5
+            "UPDATE quotes SET ticker_id=$2, price=$3, time=$4, volume=$5, stale=$6
                 WHERE id=$1",
             id,
             (quote.ticker as i32),
             quote.price,
             quote.time,
             quote.volume,
+            quote.stale,
         )
         .execute(&self.pool)
         .await?;
@@ -485,4 +827,413 @@ impl QuoteHandler for PostgresDB {
         .await?;
         Ok(())
     }
+
+    async fn remove_duplicates_with_tolerance(&self, tol: f64) -> Result<(), DataError> {
+        sqlx::query!(
+            "
+            delete from quotes q
+            where q.id in
+            (select q2.id
+            from
+                quotes q1,
+                quotes q2
+            where
+                q1.id < q2.id
+            and q1.ticker_id = q2.ticker_id
+            and q1.time = q2.time
+            and abs(q1.price - q2.price) <= $1)
+            ",
+            tol,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datatypes::{date_time_helper::make_time, Stock};
+
+    #[tokio::test]
+    async fn concurrent_insert_if_new_ticker() {
+        let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
+        assert!(
+            db_url.is_ok(),
+            "environment variable $FINQL_TEST_DATABASE_URL is not set"
+        );
+        let db = PostgresDB::new(&db_url.unwrap()).await.unwrap();
+        db.clean().await.unwrap();
+
+        let eur = Currency::new(None, CurrencyISOCode::new("EUR").unwrap(), None);
+        let eur_id = db.insert_asset(&Asset::Currency(eur)).await.unwrap();
+        let eur = Currency::new(Some(eur_id), eur.iso_code, None);
+        let stock = Stock::new(None, "Apple".to_string(), None, None, None);
+        let asset_id = db.insert_asset(&Asset::Stock(stock)).await.unwrap();
+        let ticker = Ticker {
+            id: None,
+            asset: asset_id,
+            name: "AAPL".to_string(),
+            currency: eur,
+            source: "manual".to_string(),
+            priority: 1,
+            factor: 1.0,
+            tz: None,
+            cal: None,
+            active: true,
+        };
+
+        let db = Arc::new(db);
+        let first = {
+            let db = db.clone();
+            let ticker = ticker.clone();
+            tokio::spawn(async move { db.insert_if_new_ticker(&ticker).await })
+        };
+        let second = {
+            let db = db.clone();
+            let ticker = ticker.clone();
+            tokio::spawn(async move { db.insert_if_new_ticker(&ticker).await })
+        };
+        let first_id = first.await.unwrap().unwrap();
+        let second_id = second.await.unwrap().unwrap();
+        assert_eq!(first_id, second_id);
+        assert_eq!(db.get_all_ticker().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn insert_only_newer_quotes() {
+        let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
+        assert!(
+            db_url.is_ok(),
+            "environment variable $FINQL_TEST_DATABASE_URL is not set"
+        );
+        let db = PostgresDB::new(&db_url.unwrap()).await.unwrap();
+        db.clean().await.unwrap();
+
+        let eur = Currency::new(None, CurrencyISOCode::new("EUR").unwrap(), None);
+        let eur_id = db.insert_asset(&Asset::Currency(eur)).await.unwrap();
+        let eur = Currency::new(Some(eur_id), eur.iso_code, None);
+        let stock = Stock::new(None, "Apple".to_string(), None, None, None);
+        let asset_id = db.insert_asset(&Asset::Stock(stock)).await.unwrap();
+        let ticker_id = db
+            .insert_ticker(&Ticker {
+                id: None,
+                asset: asset_id,
+                name: "AAPL".to_string(),
+                currency: eur,
+                source: "manual".to_string(),
+                priority: 1,
+                factor: 1.0,
+                tz: None,
+                cal: None,
+                active: true,
+            })
+            .await
+            .unwrap();
+
+        let time = Local::now();
+        let mut quote = Quote {
+            id: None,
+            ticker: ticker_id,
+            price: 100.0,
+            time,
+            volume: None,
+            stale: false,
+        };
+        assert!(db.insert_quote_if_newer(&quote).await.unwrap().is_some());
+        quote.time = time - chrono::Duration::seconds(1);
+        assert!(db.insert_quote_if_newer(&quote).await.unwrap().is_none());
+        quote.time = time;
+        assert!(db.insert_quote_if_newer(&quote).await.unwrap().is_none());
+        quote.time = time + chrono::Duration::seconds(1);
+        assert!(db.insert_quote_if_newer(&quote).await.unwrap().is_some());
+        assert_eq!(
+            db.get_all_quotes_for_ticker(ticker_id).await.unwrap().len(),
+            2
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_insert_quote_if_newer() {
+        let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
+        assert!(
+            db_url.is_ok(),
+            "environment variable $FINQL_TEST_DATABASE_URL is not set"
+        );
+        let db = PostgresDB::new(&db_url.unwrap()).await.unwrap();
+        db.clean().await.unwrap();
+
+        let eur = Currency::new(None, CurrencyISOCode::new("EUR").unwrap(), None);
+        let eur_id = db.insert_asset(&Asset::Currency(eur)).await.unwrap();
+        let eur = Currency::new(Some(eur_id), eur.iso_code, None);
+        let stock = Stock::new(None, "Apple".to_string(), None, None, None);
+        let asset_id = db.insert_asset(&Asset::Stock(stock)).await.unwrap();
+        let ticker_id = db
+            .insert_ticker(&Ticker {
+                id: None,
+                asset: asset_id,
+                name: "AAPL".to_string(),
+                currency: eur,
+                source: "manual".to_string(),
+                priority: 1,
+                factor: 1.0,
+                tz: None,
+                cal: None,
+                active: true,
+            })
+            .await
+            .unwrap();
+
+        let quote = Quote {
+            id: None,
+            ticker: ticker_id,
+            price: 100.0,
+            time: Local::now(),
+            volume: None,
+            stale: false,
+        };
+        let db = Arc::new(db);
+        let inserts: Vec<_> = (0..8)
+            .map(|_| {
+                let db = db.clone();
+                let quote = quote.clone();
+                tokio::spawn(async move { db.insert_quote_if_newer(&quote).await })
+            })
+            .collect();
+        let mut inserted = 0;
+        for insert in inserts {
+            if insert.await.unwrap().unwrap().is_some() {
+                inserted += 1;
+            }
+        }
+        assert_eq!(inserted, 1);
+        assert_eq!(
+            db.get_all_quotes_for_ticker(ticker_id).await.unwrap().len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn no_last_quote_for_asset_without_quotes() {
+        let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
+        assert!(
+            db_url.is_ok(),
+            "environment variable $FINQL_TEST_DATABASE_URL is not set"
+        );
+        let db = PostgresDB::new(&db_url.unwrap()).await.unwrap();
+        db.clean().await.unwrap();
+
+        let stock = Stock::new(None, "Apple".to_string(), None, None, None);
+        let asset_id = db.insert_asset(&Asset::Stock(stock)).await.unwrap();
+        let quote = db
+            .try_get_last_quote_before_by_id(asset_id, Local::now())
+            .await
+            .unwrap();
+        assert!(quote.is_none());
+        let result = db.get_last_quote_before_by_id(asset_id, Local::now()).await;
+        assert!(matches!(result, Err(DataError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn last_quote_with_tiebreak_strategy() {
+        let tol = 1e-10;
+        let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
+        assert!(
+            db_url.is_ok(),
+            "environment variable $FINQL_TEST_DATABASE_URL is not set"
+        );
+        let db = PostgresDB::new(&db_url.unwrap()).await.unwrap();
+        db.clean().await.unwrap();
+
+        let eur = Currency::new(None, CurrencyISOCode::new("EUR").unwrap(), None);
+        let eur_id = db.insert_asset(&Asset::Currency(eur)).await.unwrap();
+        let eur = Currency::new(Some(eur_id), eur.iso_code, None);
+        let stock = Stock::new(None, "Apple".to_string(), None, None, None);
+        let asset_id = db.insert_asset(&Asset::Stock(stock)).await.unwrap();
+        let time = make_time(2021, 11, 5, 18, 0, 0).unwrap();
+        // the low priority ticker's quote is inserted last
+        let mut ticker_ids = Vec::new();
+        for (priority, price) in [(1, 100.0), (2, 102.0)] {
+            let ticker_id = db
+                .insert_ticker(&Ticker {
+                    id: None,
+                    asset: asset_id,
+                    name: format!("AAPL{}", priority),
+                    currency: eur,
+                    source: "manual".to_string(),
+                    priority,
+                    factor: 1.0,
+                    tz: None,
+                    cal: None,
+                    active: true,
+                })
+                .await
+                .unwrap();
+            db.insert_quote(&Quote {
+                id: None,
+                ticker: ticker_id,
+                price,
+                time,
+                volume: None,
+                stale: false,
+            })
+            .await
+            .unwrap();
+            ticker_ids.push(ticker_id);
+        }
+
+        let (quote, _) = db
+            .get_last_quote_before_by_id(asset_id, Local::now())
+            .await
+            .unwrap();
+        assert_eq!(quote.ticker, ticker_ids[0]);
+        let (quote, currency) = db
+            .get_last_quote_before_with_strategy(
+                asset_id,
+                Local::now(),
+                TiebreakStrategy::HighestPriority,
+            )
+            .await
+            .unwrap();
+        assert_eq!(quote.ticker, ticker_ids[0]);
+        assert_fuzzy_eq!(quote.price, 100.0, tol);
+        assert_eq!(currency, eur);
+        let (quote, _) = db
+            .get_last_quote_before_with_strategy(asset_id, Local::now(), TiebreakStrategy::Latest)
+            .await
+            .unwrap();
+        assert_eq!(quote.ticker, ticker_ids[1]);
+        assert_fuzzy_eq!(quote.price, 102.0, tol);
+        let (quote, _) = db
+            .get_last_quote_before_with_strategy(asset_id, Local::now(), TiebreakStrategy::Average)
+            .await
+            .unwrap();
+        assert_eq!(quote.id, None);
+        assert_fuzzy_eq!(quote.price, 101.0, tol);
+        let result = db
+            .get_last_quote_before_with_strategy(
+                asset_id,
+                make_time(2021, 11, 4, 18, 0, 0).unwrap(),
+                TiebreakStrategy::Average,
+            )
+            .await;
+        assert!(matches!(result, Err(DataError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn remove_near_duplicate_quotes() {
+        let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
+        assert!(
+            db_url.is_ok(),
+            "environment variable $FINQL_TEST_DATABASE_URL is not set"
+        );
+        let db = PostgresDB::new(&db_url.unwrap()).await.unwrap();
+        db.clean().await.unwrap();
+
+        let eur = Currency::new(None, CurrencyISOCode::new("EUR").unwrap(), None);
+        let eur_id = db.insert_asset(&Asset::Currency(eur)).await.unwrap();
+        let eur = Currency::new(Some(eur_id), eur.iso_code, None);
+        let stock = Stock::new(None, "Apple".to_string(), None, None, None);
+        let asset_id = db.insert_asset(&Asset::Stock(stock)).await.unwrap();
+        let ticker_id = db
+            .insert_ticker(&Ticker {
+                id: None,
+                asset: asset_id,
+                name: "AAPL".to_string(),
+                currency: eur,
+                source: "manual".to_string(),
+                priority: 1,
+                factor: 1.0,
+                tz: None,
+                cal: None,
+                active: true,
+            })
+            .await
+            .unwrap();
+        let time = make_time(2021, 11, 5, 18, 0, 0).unwrap();
+        let mut quote_ids = Vec::new();
+        for price in [1.23, 1.2300000001, 1.25] {
+            let quote_id = db
+                .insert_quote(&Quote {
+                    id: None,
+                    ticker: ticker_id,
+                    price,
+                    time,
+                    volume: None,
+                    stale: false,
+                })
+                .await
+                .unwrap();
+            quote_ids.push(quote_id);
+        }
+
+        db.remove_duplicates().await.unwrap();
+        assert_eq!(
+            db.get_all_quotes_for_ticker(ticker_id).await.unwrap().len(),
+            3
+        );
+        db.remove_duplicates_with_tolerance(1e-6).await.unwrap();
+        let quotes = db.get_all_quotes_for_ticker(ticker_id).await.unwrap();
+        let mut ids: Vec<i32> = quotes.iter().map(|q| q.id.unwrap()).collect();
+        ids.sort_unstable();
+        assert_eq!(ids, vec![quote_ids[0], quote_ids[2]]);
+    }
+
+    #[tokio::test]
+    async fn last_quote_time_per_ticker() {
+        let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
+        assert!(
+            db_url.is_ok(),
+            "environment variable $FINQL_TEST_DATABASE_URL is not set"
+        );
+        let db = PostgresDB::new(&db_url.unwrap()).await.unwrap();
+        db.clean().await.unwrap();
+
+        let eur = Currency::new(None, CurrencyISOCode::new("EUR").unwrap(), None);
+        let eur_id = db.insert_asset(&Asset::Currency(eur)).await.unwrap();
+        let eur = Currency::new(Some(eur_id), eur.iso_code, None);
+        let stock = Stock::new(None, "Apple".to_string(), None, None, None);
+        let asset_id = db.insert_asset(&Asset::Stock(stock)).await.unwrap();
+        let mut ticker_ids = Vec::new();
+        for source in ["manual", "yahoo"] {
+            let ticker_id = db
+                .insert_ticker(&Ticker {
+                    id: None,
+                    asset: asset_id,
+                    name: "AAPL".to_string(),
+                    currency: eur,
+                    source: source.to_string(),
+                    priority: 1,
+                    factor: 1.0,
+                    tz: None,
+                    cal: None,
+                    active: true,
+                })
+                .await
+                .unwrap();
+            ticker_ids.push(ticker_id);
+        }
+        let last_time = make_time(2021, 11, 5, 18, 0, 0).unwrap();
+        for time in [make_time(2021, 11, 4, 18, 0, 0).unwrap(), last_time] {
+            db.insert_quote(&Quote {
+                id: None,
+                ticker: ticker_ids[0],
+                price: 100.0,
+                time,
+                volume: None,
+                stale: false,
+            })
+            .await
+            .unwrap();
+        }
+
+        let tickers = db.tickers_with_last_quote_time().await.unwrap();
+        assert_eq!(tickers.len(), 2);
+        assert_eq!(tickers[0].0.id, Some(ticker_ids[0]));
+        assert_eq!(tickers[0].0.currency, eur);
+        assert_eq!(tickers[0].1, Some(last_time));
+        assert_eq!(tickers[1].0.id, Some(ticker_ids[1]));
+        assert_eq!(tickers[1].1, None);
+    }
 }
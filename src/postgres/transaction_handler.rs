@@ -1,13 +1,15 @@
 use async_trait::async_trait;
-use chrono::NaiveDate;
+use chrono::{DateTime, Local, NaiveDate};
 use std::str::FromStr;
 
 use crate::datatypes::cash_flow::{CashAmount, CashFlow};
 use crate::datatypes::currency::Currency;
 use crate::datatypes::transaction::{Transaction, TransactionType};
-use crate::datatypes::{CurrencyISOCode, DataError, TransactionHandler};
+use crate::datatypes::{
+    CompositeTransaction, CurrencyISOCode, DataError, StockSplit, TransactionHandler,
+};
 
-use super::PostgresDB;
+use super::{map_not_found, PostgresDB};
 
 pub struct RawTransaction {
     pub id: Option<i32>,
@@ -19,6 +21,7 @@ pub struct RawTransaction {
     pub related_trans: Option<i32>,
     pub position: Option<f64>,
     pub note: Option<String>,
+    pub time_stamp: Option<DateTime<Local>>,
 }
 
 /// Raw transaction type constants
@@ -76,6 +79,7 @@ impl RawTransaction {
             transaction_type,
             cash_flow,
             note,
+            time_stamp: self.time_stamp,
         })
     }
 
@@ -85,7 +89,7 @@ impl RawTransaction {
         let note = transaction.note.clone();
         let mut raw_transaction = RawTransaction {
             id: transaction.id,
-            trans_type: String::new(),
+            trans_type: transaction.transaction_type.code().to_string(),
             asset: None,
             cash_amount,
             cash_currency,
@@ -93,28 +97,21 @@ impl RawTransaction {
             related_trans: None,
             position: None,
             note,
+            time_stamp: transaction.time_stamp,
         };
         match transaction.transaction_type {
-            TransactionType::Cash => raw_transaction.trans_type = CASH.to_string(),
+            TransactionType::Cash => {}
             TransactionType::Asset { asset_id, position } => {
-                raw_transaction.trans_type = ASSET.to_string();
                 raw_transaction.asset = Some(asset_id);
                 raw_transaction.position = Some(position);
             }
             TransactionType::Dividend { asset_id } => {
-                raw_transaction.trans_type = DIVIDEND.to_string();
                 raw_transaction.asset = Some(asset_id);
             }
             TransactionType::Interest { asset_id } => {
-                raw_transaction.trans_type = INTEREST.to_string();
                 raw_transaction.asset = Some(asset_id);
             }
-            TransactionType::Tax { transaction_ref } => {
-                raw_transaction.trans_type = TAX.to_string();
-                raw_transaction.related_trans = transaction_ref;
-            }
-            TransactionType::Fee { transaction_ref } => {
-                raw_transaction.trans_type = FEE.to_string();
+            TransactionType::Tax { transaction_ref } | TransactionType::Fee { transaction_ref } => {
                 raw_transaction.related_trans = transaction_ref;
             }
         };
@@ -122,29 +119,38 @@ impl RawTransaction {
     }
 }
 
+/// Insert transaction using the given executor, i.e. either the connection pool
+/// or an open database transaction
+async fn insert_raw_transaction<'e, E: sqlx::PgExecutor<'e>>(
+    executor: E,
+    transaction: &Transaction,
+) -> Result<i32, DataError> {
+    let transaction = RawTransaction::from_transaction(transaction);
+    let row = sqlx::query!(
+        "INSERT INTO transactions (trans_type, asset_id, cash_amount,
+                cash_currency_id, cash_date, related_trans, position,
+                note) 
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id",
+        transaction.trans_type,
+        transaction.asset,
+        transaction.cash_amount,
+        transaction.cash_currency.id,
+        transaction.cash_date,
+        transaction.related_trans,
+        transaction.position,
+        transaction.note,
+    )
+    .fetch_one(executor)
+    .await?;
+    Ok(row.id)
+}
+
 /// Handler for globally available data
 #[async_trait]
 impl TransactionHandler for PostgresDB {
     // insert, get, update and delete for transactions
     async fn insert_transaction(&self, transaction: &Transaction) -> Result<i32, DataError> {
-        let transaction = RawTransaction::from_transaction(transaction);
-        let row = sqlx::query!(
-            "INSERT INTO transactions (trans_type, asset_id, cash_amount,
-                cash_currency_id, cash_date, related_trans, position,
-                note) 
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id",
-            transaction.trans_type,
-            transaction.asset,
-            transaction.cash_amount,
-            transaction.cash_currency.id,
-            transaction.cash_date,
-            transaction.related_trans,
-            transaction.position,
-            transaction.note,
-        )
-        .fetch_one(&self.pool)
-        .await?;
-        Ok(row.id)
+        insert_raw_transaction(&self.pool, transaction).await
     }
 
     async fn get_transaction_by_id(&self, id: i32) -> Result<Transaction, DataError> {
@@ -160,14 +166,16 @@ impl TransactionHandler for PostgresDB {
                 t.cash_date,
                 t.related_trans,
                 t.position,
-                t.note
+                t.note,
+                t.time_stamp
                 FROM transactions t
                 JOIN currencies c ON c.id = t.cash_currency_id
                 WHERE t.id = $1",
             id,
         )
         .fetch_one(&self.pool)
-        .await?;
+        .await
+        .map_err(|e| map_not_found(e, &format!("transaction with id {}", id)))?;
         let transaction = RawTransaction {
             id: Some(id),
             trans_type: row.trans_type,
@@ -183,6 +191,7 @@ impl TransactionHandler for PostgresDB {
             related_trans: row.related_trans,
             position: row.position,
             note: row.note,
+            time_stamp: Some(row.time_stamp.into()),
         };
         Ok(transaction.to_transaction()?)
     }
@@ -201,7 +210,8 @@ impl TransactionHandler for PostgresDB {
                 t.cash_date AS "cash_date!",
                 t.related_trans,
                 t.position,
-                t.note
+                t.note,
+                t.time_stamp
                 FROM transactions t
                 JOIN currencies c ON c.id = t.cash_currency_id"#
         )
@@ -223,6 +233,7 @@ impl TransactionHandler for PostgresDB {
                 related_trans: row.related_trans,
                 position: row.position,
                 note: row.note,
+                time_stamp: Some(row.time_stamp.into()),
             };
             transactions.push(transaction.to_transaction()?);
         }
@@ -268,4 +279,156 @@ impl TransactionHandler for PostgresDB {
             .await?;
         Ok(())
     }
+
+    async fn insert_composite(
+        &self,
+        composite: &CompositeTransaction,
+    ) -> Result<Vec<i32>, DataError> {
+        let mut tx = self.pool.begin().await?;
+        let trade_id = insert_raw_transaction(&mut tx, &composite.trade).await?;
+        let mut ids = vec![trade_id];
+        for (transaction, expected_type) in composite
+            .fees
+            .iter()
+            .map(|fee| (fee, FEE))
+            .chain(composite.taxes.iter().map(|tax| (tax, TAX)))
+        {
+            if transaction.transaction_type.code() != expected_type {
+                return Err(DataError::InvalidTransaction(format!(
+                    "unexpected transaction type {:?} for fees or taxes",
+                    transaction.transaction_type
+                )));
+            }
+            let mut transaction = transaction.clone();
+            transaction.set_transaction_ref(trade_id);
+            ids.push(insert_raw_transaction(&mut tx, &transaction).await?);
+        }
+        tx.commit().await?;
+        Ok(ids)
+    }
+
+    async fn apply_stock_splits(
+        &self,
+        asset_id: i32,
+        splits: &[StockSplit],
+    ) -> Result<usize, DataError> {
+        if let Some(split) = splits
+            .iter()
+            .find(|split| !split.ratio.is_finite() || split.ratio <= 0.0)
+        {
+            return Err(DataError::InvalidTransaction(format!(
+                "invalid ratio {} of stock split on {}",
+                split.ratio, split.date
+            )));
+        }
+        let mut tx = self.pool.begin().await?;
+        // The transactions adjusted for the latest split include all transactions
+        // adjusted for earlier splits
+        let mut adjusted = 0;
+        for split in splits {
+            // Record the split within the same transaction, splits already recorded
+            // for this asset and date have been applied before and are skipped
+            let recorded = sqlx::query!(
+                "INSERT INTO stock_splits (asset_id, split_date, ratio) VALUES ($1, $2, $3)
+                ON CONFLICT (asset_id, split_date) DO NOTHING",
+                asset_id,
+                split.date,
+                split.ratio
+            )
+            .execute(&mut tx)
+            .await?;
+            if recorded.rows_affected() == 0 {
+                continue;
+            }
+            let result = sqlx::query!(
+                "UPDATE transactions SET position = position * $4
+                WHERE asset_id = $1 AND trans_type = $2 AND cash_date < $3",
+                asset_id,
+                ASSET,
+                split.date,
+                split.ratio,
+            )
+            .execute(&mut tx)
+            .await?;
+            adjusted = adjusted.max(result.rows_affected() as usize);
+        }
+        tx.commit().await?;
+        Ok(adjusted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datatypes::{Asset, AssetHandler, Stock};
+
+    #[tokio::test]
+    async fn insert_trade_with_fee_and_tax() {
+        let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
+        assert!(
+            db_url.is_ok(),
+            "environment variable $FINQL_TEST_DATABASE_URL is not set"
+        );
+        let db = PostgresDB::new(&db_url.unwrap()).await.unwrap();
+        db.clean().await.unwrap();
+
+        let eur = db
+            .get_or_new_currency(CurrencyISOCode::new("EUR").unwrap())
+            .await
+            .unwrap();
+        let stock = Stock::new(None, "Apple".to_string(), None, None, None);
+        let asset_id = db.insert_asset(&Asset::Stock(stock)).await.unwrap();
+        let date = NaiveDate::from_ymd(2021, 6, 1);
+        let composite = CompositeTransaction {
+            trade: Transaction {
+                id: None,
+                transaction_type: TransactionType::Asset {
+                    asset_id,
+                    position: 10.0,
+                },
+                cash_flow: CashFlow::new(-1000.0, eur, date),
+                note: None,
+                time_stamp: None,
+            },
+            fees: vec![Transaction {
+                id: None,
+                transaction_type: TransactionType::Fee {
+                    transaction_ref: None,
+                },
+                cash_flow: CashFlow::new(-5.0, eur, date),
+                note: None,
+                time_stamp: None,
+            }],
+            taxes: vec![Transaction {
+                id: None,
+                transaction_type: TransactionType::Tax {
+                    transaction_ref: None,
+                },
+                cash_flow: CashFlow::new(-2.5, eur, date),
+                note: None,
+                time_stamp: None,
+            }],
+        };
+        let ids = db.insert_composite(&composite).await.unwrap();
+        assert_eq!(ids.len(), 3);
+        let fee = db.get_transaction_by_id(ids[1]).await.unwrap();
+        assert!(matches!(
+            fee.transaction_type,
+            TransactionType::Fee { transaction_ref: Some(id) } if id == ids[0]
+        ));
+        let tax = db.get_transaction_by_id(ids[2]).await.unwrap();
+        assert!(matches!(
+            tax.transaction_type,
+            TransactionType::Tax { transaction_ref: Some(id) } if id == ids[0]
+        ));
+
+        // nothing is stored if any part of the composite is invalid
+        let mut invalid = composite.clone();
+        invalid.taxes.push(composite.trade.clone());
+        assert!(matches!(
+            db.insert_composite(&invalid).await,
+            Err(DataError::InvalidTransaction(_))
+        ));
+        assert_eq!(db.get_all_transactions().await.unwrap().len(), 3);
+    }
 }
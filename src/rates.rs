@@ -4,7 +4,12 @@ use serde::{Deserialize, Serialize};
 use crate::datatypes::cash_flow::{CashAmount, CashFlow};
 use crate::datatypes::currency::Currency;
 
-use crate::day_count_conv::DayCountConv;
+use crate::day_count_conv::{DayCountConv, DayCountConvError};
+
+/// Maximum number of iterations of the root finding in `irr`
+const MAX_IRR_ITERATIONS: usize = 200;
+/// Absolute tolerance of the internal rate of return
+const IRR_TOLERANCE: f64 = 1e-12;
 
 /// Methods for compounding interest rates
 #[derive(Deserialize, Serialize, Clone, Copy, Debug)]
@@ -19,10 +24,42 @@ pub enum Compounding {
     Quarterly,
     #[serde(rename = "monthly")]
     Monthly,
+    /// Compounding with the given number of periods per year, zero periods are treated as one
+    #[serde(rename = "periodic")]
+    Periodic(u16),
     #[serde(rename = "continuous")]
     Continuous,
 }
 
+impl Compounding {
+    /// Number of compounding periods per year, or `None` for continuous compounding.
+    /// Simple compounding is treated as annual, which is equivalent over a one year horizon.
+    /// Periodic compounding with zero periods is treated as annual as well.
+    fn periods_per_year(&self) -> Option<f64> {
+        match self {
+            Compounding::Simple | Compounding::Annual => Some(1.),
+            Compounding::SemiAnnual => Some(2.),
+            Compounding::Quarterly => Some(4.),
+            Compounding::Monthly => Some(12.),
+            Compounding::Periodic(n) => Some(f64::from((*n).max(1))),
+            Compounding::Continuous => None,
+        }
+    }
+}
+
+/// Convert an interest rate given in one compounding method into the equivalent rate
+/// of another compounding method, i.e. the rate that yields the same growth over one year.
+pub fn convert_rate(rate: f64, from: Compounding, to: Compounding) -> f64 {
+    let log_growth = match from.periods_per_year() {
+        Some(n) => n * (rate / n).ln_1p(),
+        None => rate,
+    };
+    match to.periods_per_year() {
+        Some(n) => n * (log_growth / n).exp_m1(),
+        None => log_growth,
+    }
+}
+
 /// Error related to market data object
 #[derive(Debug)]
 pub struct DiscountError;
@@ -42,6 +79,44 @@ impl std::error::Error for DiscountError {
     }
 }
 
+/// Error related to the calculation of rates
+#[derive(Debug)]
+pub enum RatesError {
+    DayCountError(DayCountConvError),
+    NoSignChange,
+    NoConvergence,
+}
+
+impl std::fmt::Display for RatesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RatesError::DayCountError(_) => {
+                write!(f, "invalid day count convention in this context")
+            }
+            RatesError::NoSignChange => write!(
+                f,
+                "cash flows must contain positive as well as negative amounts"
+            ),
+            RatesError::NoConvergence => write!(f, "no internal rate of return found"),
+        }
+    }
+}
+
+impl std::error::Error for RatesError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RatesError::DayCountError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<DayCountConvError> for RatesError {
+    fn from(error: DayCountConvError) -> Self {
+        RatesError::DayCountError(error)
+    }
+}
+
 /// The `Discounter` trait provides a method for calculating discount factors.
 /// This could be applied to falt raters, rate curves, or more complex models.
 pub trait Discounter {
@@ -121,14 +196,7 @@ impl Discounter for FlatRate {
             .day_count_conv
             .year_fraction(today, pay_date, None, None)
             .unwrap();
-        match self.compounding {
-            Compounding::Simple => 1. / (1. + self.rate * yf),
-            Compounding::Annual => (1. + self.rate).powf(-yf),
-            Compounding::SemiAnnual => (1. + 0.5 * self.rate).powf(-2. * yf),
-            Compounding::Quarterly => (1. + 0.25 * self.rate).powf(-4. * yf),
-            Compounding::Monthly => (1. + self.rate / 12.).powf(-12. * yf),
-            Compounding::Continuous => (-self.rate * yf).exp(),
-        }
+        zero_to_df(self.rate, yf, self.compounding)
     }
 
     fn currency(&self) -> Currency {
@@ -136,6 +204,102 @@ impl Discounter for FlatRate {
     }
 }
 
+/// Discount factor for the time `t` in years given the zero rate under the given compounding
+pub fn zero_to_df(rate: f64, t: f64, comp: Compounding) -> f64 {
+    match comp {
+        Compounding::Simple => 1. / (1. + rate * t),
+        Compounding::Continuous => (-rate * t).exp(),
+        _ => {
+            let n = comp.periods_per_year().unwrap();
+            (1. + rate / n).powf(-n * t)
+        }
+    }
+}
+
+/// Zero rate under the given compounding implied by the discount factor for the time `t`
+/// in years; `t` must be positive.
+pub fn df_to_zero(df: f64, t: f64, comp: Compounding) -> f64 {
+    match comp {
+        Compounding::Simple => (1. / df - 1.) / t,
+        Compounding::Continuous => -df.ln() / t,
+        _ => {
+            let n = comp.periods_per_year().unwrap();
+            n * (df.powf(-1. / (n * t)) - 1.)
+        }
+    }
+}
+
+/// Calculate the internal rate of return, i.e. the annually compounded flat rate for which
+/// the net present value of the cash flows at the valuation date is zero.
+/// Newton's method is tried first; if it fails to converge, the root is searched
+/// by bisection within a bracket of rates above -100%.
+pub fn irr(
+    cash_flows: &[CashFlow],
+    valuation_date: NaiveDate,
+    dcc: DayCountConv,
+) -> Result<f64, RatesError> {
+    if !cash_flows.iter().any(|cf| cf.amount.amount > 0.)
+        || !cash_flows.iter().any(|cf| cf.amount.amount < 0.)
+    {
+        return Err(RatesError::NoSignChange);
+    }
+    let mut flows = Vec::new();
+    for cf in cash_flows {
+        let yf = dcc.year_fraction(valuation_date, cf.date, None, None)?;
+        flows.push((cf.amount.amount, yf));
+    }
+    let npv = |rate: f64| -> f64 {
+        flows
+            .iter()
+            .map(|(amount, yf)| amount * (1. + rate).powf(-yf))
+            .sum()
+    };
+    let npv_derivative = |rate: f64| -> f64 {
+        flows
+            .iter()
+            .map(|(amount, yf)| -yf * amount * (1. + rate).powf(-yf - 1.))
+            .sum()
+    };
+
+    let mut rate = 0.1;
+    for _ in 0..MAX_IRR_ITERATIONS {
+        let derivative = npv_derivative(rate);
+        if derivative == 0. || !derivative.is_finite() {
+            break;
+        }
+        let step = npv(rate) / derivative;
+        rate -= step;
+        if rate <= -1. || !rate.is_finite() {
+            break;
+        }
+        if step.abs() < IRR_TOLERANCE {
+            return Ok(rate);
+        }
+    }
+
+    // Fall back to bisection
+    let mut low = -0.99;
+    let mut high = 1.;
+    while npv(low).signum() == npv(high).signum() {
+        high *= 2.;
+        if high > 1e6 {
+            return Err(RatesError::NoConvergence);
+        }
+    }
+    for _ in 0..MAX_IRR_ITERATIONS {
+        let mid = 0.5 * (low + high);
+        if npv(mid).signum() == npv(low).signum() {
+            low = mid;
+        } else {
+            high = mid;
+        }
+        if high - low < IRR_TOLERANCE {
+            return Ok(0.5 * (low + high));
+        }
+    }
+    Err(RatesError::NoConvergence)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -205,6 +369,18 @@ mod tests {
             tol
         );
 
+        let rate = FlatRate {
+            rate: 0.05,
+            day_count_conv: DayCountConv::Act365,
+            compounding: Compounding::Periodic(12),
+            currency: curr,
+        };
+        assert_fuzzy_eq!(
+            rate.discount_factor(start_date, end_date),
+            f64::powf(1.0 + 0.05 / 12., -yf * 12.),
+            tol
+        );
+
         let rate = FlatRate {
             rate: 0.05,
             day_count_conv: DayCountConv::Act365,
@@ -288,4 +464,109 @@ mod tests {
             tol
         );
     }
+
+    #[test]
+    fn internal_rate_of_return() {
+        let tol = 1e-10;
+        let curr = Currency::from_str("EUR").unwrap();
+        let start = NaiveDate::from_ymd(2020, 1, 1);
+        let cash_flows = [
+            CashFlow::new(-1000., curr, start),
+            CashFlow::new(1100., curr, NaiveDate::from_ymd(2021, 1, 1)),
+        ];
+        let rate = irr(&cash_flows, start, DayCountConv::D30_360).unwrap();
+        assert_fuzzy_eq!(rate, 0.1, tol);
+
+        // drawdowns and distributions of a private equity fund
+        let cash_flows = [
+            CashFlow::new(-1000., curr, start),
+            CashFlow::new(-500., curr, NaiveDate::from_ymd(2021, 1, 1)),
+            CashFlow::new(300., curr, NaiveDate::from_ymd(2022, 1, 1)),
+            CashFlow::new(-200., curr, NaiveDate::from_ymd(2023, 1, 1)),
+            CashFlow::new(2200., curr, NaiveDate::from_ymd(2024, 1, 1)),
+        ];
+        let rate = irr(&cash_flows, start, DayCountConv::Act365).unwrap();
+        let npv = crate::datatypes::npv(&cash_flows, rate, start, DayCountConv::Act365).unwrap();
+        assert_fuzzy_eq!(npv, 0., 1e-8);
+        assert!(rate > 0. && rate < 0.2);
+
+        let cash_flows = [
+            CashFlow::new(1000., curr, start),
+            CashFlow::new(1100., curr, NaiveDate::from_ymd(2021, 1, 1)),
+        ];
+        assert!(matches!(
+            irr(&cash_flows, start, DayCountConv::Act365),
+            Err(RatesError::NoSignChange)
+        ));
+    }
+
+    #[test]
+    fn convert_compounding() {
+        let tol = 1e-12;
+        let continuous = convert_rate(0.05, Compounding::Annual, Compounding::Continuous);
+        assert_fuzzy_eq!(continuous, 1.05_f64.ln(), tol);
+        assert_fuzzy_eq!(continuous, 0.04879, 1e-5);
+        assert_fuzzy_eq!(
+            convert_rate(continuous, Compounding::Continuous, Compounding::Annual),
+            0.05,
+            tol
+        );
+
+        let semi_annual = convert_rate(0.05, Compounding::Annual, Compounding::SemiAnnual);
+        assert_fuzzy_eq!((1. + semi_annual / 2.).powi(2), 1.05, tol);
+        assert_fuzzy_eq!(
+            convert_rate(
+                semi_annual,
+                Compounding::SemiAnnual,
+                Compounding::Periodic(2)
+            ),
+            semi_annual,
+            tol
+        );
+        assert_fuzzy_eq!(
+            convert_rate(0.05, Compounding::Simple, Compounding::Annual),
+            0.05,
+            tol
+        );
+    }
+
+    #[test]
+    fn discount_factor_zero_rate_round_trip() {
+        let tol = 1e-12;
+        let conventions = [
+            Compounding::Simple,
+            Compounding::Annual,
+            Compounding::SemiAnnual,
+            Compounding::Quarterly,
+            Compounding::Monthly,
+            Compounding::Periodic(52),
+            Compounding::Periodic(0),
+            Compounding::Continuous,
+        ];
+        for comp in conventions {
+            for t in [0.25, 1., 2.5, 10.] {
+                for rate in [-0.005, 0.01, 0.05] {
+                    let df = zero_to_df(rate, t, comp);
+                    assert_fuzzy_eq!(df_to_zero(df, t, comp), rate, tol);
+                }
+            }
+        }
+        assert_fuzzy_eq!(zero_to_df(0.05, 2., Compounding::Simple), 1. / 1.1, tol);
+        assert_fuzzy_eq!(zero_to_df(0.05, 2., Compounding::Annual), 1. / 1.1025, tol);
+        assert_fuzzy_eq!(
+            zero_to_df(0.05, 2., Compounding::Periodic(0)),
+            1. / 1.1025,
+            tol
+        );
+        assert_fuzzy_eq!(
+            convert_rate(0.05, Compounding::Periodic(0), Compounding::Annual),
+            0.05,
+            tol
+        );
+        assert_fuzzy_eq!(
+            df_to_zero((-0.1_f64).exp(), 2., Compounding::Continuous),
+            0.05,
+            tol
+        );
+    }
 }
@@ -0,0 +1,594 @@
+//! Statistics of asset returns
+//! Returns of several assets are aligned on a common date grid to allow the calculation
+//! of covariance and correlation matrices.
+
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+use thiserror::Error;
+
+use crate::datatypes::date_time_helper::{naive_date_to_date_time, DateTimeError};
+use crate::datatypes::{Currency, DataError};
+use crate::market::{Market, MarketError};
+
+/// Maximum number of sweeps in the Jacobi eigenvalue algorithm
+const MAX_JACOBI_SWEEPS: usize = 100;
+
+/// Default minimum number of return observations required by the statistics functions.
+/// Estimates of volatilities, correlations or betas from only a handful of returns have
+/// such a large sampling error that they are practically meaningless. 20 observations
+/// correspond to roughly one month of daily returns. A minimum of two observations is
+/// always enforced, since the sample variance is not defined otherwise.
+pub const DEFAULT_MIN_OBSERVATIONS: usize = 20;
+
+/// Error related to statistical calculations
+#[derive(Error, Debug)]
+pub enum StatError {
+    #[error("Failed to fetch quotes")]
+    DataError(#[from] DataError),
+    #[error("Invalid date or time")]
+    DateTimeError(#[from] DateTimeError),
+    #[error("Failed to convert quotes into common currency")]
+    MarketError(#[from] MarketError),
+    #[error("No quotes for asset with id {0} in given date range")]
+    NoQuotes(i32),
+    #[error("Not enough quotes to calculate returns")]
+    NotEnoughData,
+}
+
+/// Log returns of a set of assets on a common date grid
+#[derive(Debug, Clone)]
+pub struct ReturnsMatrix {
+    pub asset_ids: Vec<i32>,
+    /// End dates of the return periods
+    pub dates: Vec<NaiveDate>,
+    /// Return series per asset, in the same order as `asset_ids`
+    pub returns: Vec<Vec<f64>>,
+}
+
+impl ReturnsMatrix {
+    /// Calculate daily log returns of the given assets from quotes within the given date range
+    /// The date grid consists of all dates with at least one quote, starting at the first date
+    /// quotes of all assets are available. Missing quotes are forward filled. If there
+    /// are several quotes per day, the last one is used. All quotes are converted into
+    /// the given currency before returns are calculated.
+    /// Fails with `StatError::NotEnoughData` if there are less than `min_observations` returns,
+    /// see `DEFAULT_MIN_OBSERVATIONS`.
+    pub async fn from_market(
+        asset_ids: &[i32],
+        currency: Currency,
+        start: NaiveDate,
+        end: NaiveDate,
+        min_observations: usize,
+        market: &Market,
+    ) -> Result<ReturnsMatrix, StatError> {
+        let start_time = naive_date_to_date_time(&start, 0, None)?;
+        let end_time = naive_date_to_date_time(&end, 24, None)?;
+        let quotes = market
+            .db()
+            .get_quotes_in_range_for_assets(asset_ids, start_time, end_time)
+            .await?;
+        // Quotes are sorted by time in descending order and by priority within the same time,
+        // therefore the first quote found per day is the one to be used.
+        let mut daily_quotes = BTreeMap::new();
+        for (asset_id, quote, currency_id) in quotes {
+            daily_quotes
+                .entry((asset_id, quote.time.naive_local().date()))
+                .or_insert((quote, currency_id));
+        }
+        let mut prices: BTreeMap<i32, BTreeMap<NaiveDate, f64>> = BTreeMap::new();
+        for ((asset_id, date), (quote, currency_id)) in daily_quotes {
+            let price = market
+                .convert_price(quote.price, currency_id, currency, quote.time)
+                .await?;
+            prices.entry(asset_id).or_default().insert(date, price);
+        }
+
+        let mut first_date = start;
+        let mut all_dates = Vec::new();
+        for asset_id in asset_ids {
+            let series = prices.get(asset_id).ok_or(StatError::NoQuotes(*asset_id))?;
+            first_date = first_date.max(*series.keys().next().unwrap());
+            all_dates.extend(series.keys().cloned());
+        }
+        all_dates.sort();
+        all_dates.dedup();
+        let grid: Vec<NaiveDate> = all_dates.into_iter().filter(|d| *d >= first_date).collect();
+        if grid.len() <= min_observations.max(2) {
+            return Err(StatError::NotEnoughData);
+        }
+
+        let mut returns = Vec::new();
+        for asset_id in asset_ids {
+            let series = &prices[asset_id];
+            // forward fill by taking the last price on or before each grid date
+            let aligned: Vec<f64> = grid
+                .iter()
+                .map(|date| *series.range(..=*date).next_back().unwrap().1)
+                .collect();
+            returns.push(aligned.windows(2).map(|p| (p[1] / p[0]).ln()).collect());
+        }
+        Ok(ReturnsMatrix {
+            asset_ids: asset_ids.to_vec(),
+            dates: grid[1..].to_vec(),
+            returns,
+        })
+    }
+
+    /// Sample covariance matrix of the returns
+    pub fn covariance_matrix(&self) -> Vec<Vec<f64>> {
+        let n = self.dates.len() as f64;
+        let means: Vec<f64> = self
+            .returns
+            .iter()
+            .map(|r| r.iter().sum::<f64>() / n)
+            .collect();
+        let size = self.returns.len();
+        let mut cov = vec![vec![0.0; size]; size];
+        for i in 0..size {
+            for j in 0..=i {
+                let sum: f64 = self.returns[i]
+                    .iter()
+                    .zip(self.returns[j].iter())
+                    .map(|(x, y)| (x - means[i]) * (y - means[j]))
+                    .sum();
+                cov[i][j] = sum / (n - 1.0);
+                cov[j][i] = cov[i][j];
+            }
+        }
+        cov
+    }
+
+    /// Mean returns per year, assuming `periods_per_year` return periods per year,
+    /// e.g. 252 for daily returns of equities or 365 for crypto currencies
+    pub fn annualized_mean_returns(&self, periods_per_year: f64) -> Vec<f64> {
+        let n = self.dates.len() as f64;
+        self.returns
+            .iter()
+            .map(|r| r.iter().sum::<f64>() / n * periods_per_year)
+            .collect()
+    }
+
+    /// Volatilities per year, assuming `periods_per_year` return periods per year
+    pub fn annualized_volatilities(&self, periods_per_year: f64) -> Vec<f64> {
+        let cov = self.covariance_matrix();
+        (0..cov.len())
+            .map(|i| (cov[i][i] * periods_per_year).sqrt())
+            .collect()
+    }
+
+    /// Correlation matrix of the returns
+    pub fn correlation_matrix(&self) -> Vec<Vec<f64>> {
+        let cov = self.covariance_matrix();
+        let std_dev: Vec<f64> = (0..cov.len()).map(|i| cov[i][i].sqrt()).collect();
+        cov.iter()
+            .enumerate()
+            .map(|(i, row)| {
+                row.iter()
+                    .enumerate()
+                    .map(|(j, c)| c / (std_dev[i] * std_dev[j]))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Eigenvalues of the covariance matrix in descending order
+    pub fn eigenvalues(&self) -> Vec<f64> {
+        let mut eigenvalues = jacobi_eigenvalues(self.covariance_matrix());
+        eigenvalues.sort_by(|a, b| b.total_cmp(a));
+        eigenvalues
+    }
+}
+
+/// Arithmetic mean of a series
+fn mean(x: &[f64]) -> f64 {
+    x.iter().sum::<f64>() / x.len() as f64
+}
+
+/// Sample covariance of two series of equal length
+fn covariance(x: &[f64], y: &[f64]) -> f64 {
+    let mean_x = mean(x);
+    let mean_y = mean(y);
+    let sum: f64 = x
+        .iter()
+        .zip(y.iter())
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    sum / (x.len() as f64 - 1.0)
+}
+
+/// Beta of portfolio returns with respect to benchmark returns of the same periods,
+/// i.e. cov(portfolio, benchmark) / var(benchmark). Returns `None` if the series differ
+/// in length, have less than `min_observations` elements, or the benchmark returns are
+/// constant.
+pub fn beta(
+    portfolio_returns: &[f64],
+    benchmark_returns: &[f64],
+    min_observations: usize,
+) -> Option<f64> {
+    if portfolio_returns.len() != benchmark_returns.len()
+        || benchmark_returns.len() < min_observations.max(2)
+    {
+        return None;
+    }
+    let benchmark_var = covariance(benchmark_returns, benchmark_returns);
+    if benchmark_var == 0.0 {
+        return None;
+    }
+    Some(covariance(portfolio_returns, benchmark_returns) / benchmark_var)
+}
+
+/// Tracking error, i.e. the annualized standard deviation of the differences between
+/// portfolio and benchmark returns, assuming `periods_per_year` return periods per year.
+/// Returns `None` if the series differ in length or have less than `min_observations` elements.
+pub fn tracking_error(
+    portfolio_returns: &[f64],
+    benchmark_returns: &[f64],
+    periods_per_year: f64,
+    min_observations: usize,
+) -> Option<f64> {
+    if portfolio_returns.len() != benchmark_returns.len()
+        || benchmark_returns.len() < min_observations.max(2)
+    {
+        return None;
+    }
+    let active_returns: Vec<f64> = portfolio_returns
+        .iter()
+        .zip(benchmark_returns.iter())
+        .map(|(p, b)| p - b)
+        .collect();
+    Some((covariance(&active_returns, &active_returns) * periods_per_year).sqrt())
+}
+
+/// Sharpe ratio, i.e. the annualized mean excess return over `risk_free` (given per period)
+/// divided by the annualized volatility of the returns. Returns `None` for less than
+/// `min_observations` returns or constant returns.
+pub fn sharpe_ratio(
+    returns: &[f64],
+    risk_free: f64,
+    periods_per_year: f64,
+    min_observations: usize,
+) -> Option<f64> {
+    if returns.len() < min_observations.max(2) {
+        return None;
+    }
+    let volatility = (covariance(returns, returns) * periods_per_year).sqrt();
+    if volatility == 0.0 {
+        return None;
+    }
+    Some((mean(returns) - risk_free) * periods_per_year / volatility)
+}
+
+/// Sortino ratio, i.e. the annualized mean excess return over `target` (given per period)
+/// divided by the annualized downside deviation, which only takes into account returns
+/// below `target`. Returns `None` for less than `min_observations` returns or if there are
+/// no returns below `target`.
+pub fn sortino_ratio(
+    returns: &[f64],
+    target: f64,
+    periods_per_year: f64,
+    min_observations: usize,
+) -> Option<f64> {
+    if returns.len() < min_observations.max(2) {
+        return None;
+    }
+    let downside_sum: f64 = returns.iter().map(|r| (r - target).min(0.0).powi(2)).sum();
+    if downside_sum == 0.0 {
+        return None;
+    }
+    let downside_deviation = (downside_sum / returns.len() as f64 * periods_per_year).sqrt();
+    Some((mean(returns) - target) * periods_per_year / downside_deviation)
+}
+
+/// Calculate eigenvalues of a symmetric matrix with the cyclic Jacobi method
+fn jacobi_eigenvalues(mut a: Vec<Vec<f64>>) -> Vec<f64> {
+    let n = a.len();
+    for _ in 0..MAX_JACOBI_SWEEPS {
+        let off_diagonal: f64 = (0..n)
+            .flat_map(|i| (0..n).filter(move |j| *j != i).map(move |j| (i, j)))
+            .map(|(i, j)| a[i][j] * a[i][j])
+            .sum();
+        if off_diagonal < 1e-22 {
+            break;
+        }
+        for p in 0..n {
+            for q in p + 1..n {
+                if a[p][q] == 0.0 {
+                    continue;
+                }
+                // rotation angle which eliminates a[p][q]
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let t = if theta == 0.0 { 1.0 } else { t };
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                for row in a.iter_mut() {
+                    let (akp, akq) = (row[p], row[q]);
+                    row[p] = c * akp - s * akq;
+                    row[q] = s * akp + c * akq;
+                }
+                let (head, tail) = a.split_at_mut(q);
+                for (apk, aqk) in head[p].iter_mut().zip(tail[0].iter_mut()) {
+                    let (x, y) = (*apk, *aqk);
+                    *apk = c * x - s * y;
+                    *aqk = s * x + c * y;
+                }
+            }
+        }
+    }
+    (0..n).map(|i| a[i][i]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use crate::datatypes::{
+        date_time_helper::make_time, Asset, AssetHandler, Currency, CurrencyISOCode, Quote,
+        QuoteHandler, Stock, Ticker,
+    };
+    use crate::postgres::PostgresDB;
+
+    #[test]
+    fn correlation_of_correlated_series() {
+        let tol = 1e-10;
+        let first = vec![0.01, -0.02, 0.015, 0.003, -0.007];
+        let second: Vec<f64> = first.iter().map(|r| 2.0 * r + 0.001).collect();
+        let dates = (1..=5).map(|d| NaiveDate::from_ymd(2021, 3, d)).collect();
+        let matrix = ReturnsMatrix {
+            asset_ids: vec![1, 2],
+            dates,
+            returns: vec![first, second],
+        };
+        let corr = matrix.correlation_matrix();
+        assert_fuzzy_eq!(corr[0][0], 1.0, tol);
+        assert_fuzzy_eq!(corr[0][1], 1.0, tol);
+        assert_fuzzy_eq!(corr[1][0], 1.0, tol);
+
+        // covariance matrix of perfectly correlated series has rank one
+        let cov = matrix.covariance_matrix();
+        let eigenvalues = matrix.eigenvalues();
+        assert_fuzzy_eq!(eigenvalues[0], cov[0][0] + cov[1][1], tol);
+        assert_fuzzy_eq!(eigenvalues[1], 0.0, tol);
+    }
+
+    #[test]
+    fn annualization_of_returns() {
+        let tol = 1e-10;
+        let returns = vec![0.01, -0.02, 0.015, 0.003, -0.007];
+        let dates = (1..=5).map(|d| NaiveDate::from_ymd(2021, 3, d)).collect();
+        let matrix = ReturnsMatrix {
+            asset_ids: vec![1],
+            dates,
+            returns: vec![returns],
+        };
+        let daily_vol = matrix.covariance_matrix()[0][0].sqrt();
+        let equity_vol = matrix.annualized_volatilities(252.0)[0];
+        let crypto_vol = matrix.annualized_volatilities(365.0)[0];
+        assert_fuzzy_eq!(equity_vol, daily_vol * 252.0_f64.sqrt(), tol);
+        assert_fuzzy_eq!(crypto_vol, daily_vol * 365.0_f64.sqrt(), tol);
+        assert_fuzzy_eq!(crypto_vol / equity_vol, (365.0_f64 / 252.0).sqrt(), tol);
+
+        let equity_mean = matrix.annualized_mean_returns(252.0)[0];
+        let crypto_mean = matrix.annualized_mean_returns(365.0)[0];
+        assert_fuzzy_eq!(equity_mean, 0.0002 * 252.0, tol);
+        assert_fuzzy_eq!(crypto_mean, 0.0002 * 365.0, tol);
+    }
+
+    #[test]
+    fn beta_of_leveraged_portfolio() {
+        let tol = 1e-10;
+        let benchmark = vec![0.01, -0.02, 0.015, 0.003, -0.007];
+        let portfolio: Vec<f64> = benchmark.iter().map(|r| 2.0 * r).collect();
+        assert_fuzzy_eq!(beta(&portfolio, &benchmark, 2).unwrap(), 2.0, tol);
+        assert_fuzzy_eq!(beta(&benchmark, &portfolio, 2).unwrap(), 0.5, tol);
+        assert_eq!(beta(&portfolio[1..], &benchmark, 2), None);
+        assert_eq!(beta(&portfolio, &[0.01; 5], 2), None);
+    }
+
+    #[test]
+    fn tracking_error_of_active_returns() {
+        let tol = 1e-10;
+        let benchmark = vec![0.01, -0.02, 0.015, 0.003, -0.007];
+        let constant: Vec<f64> = benchmark.iter().map(|r| r + 0.001).collect();
+        assert_fuzzy_eq!(
+            tracking_error(&constant, &benchmark, 252.0, 2).unwrap(),
+            0.0,
+            tol
+        );
+
+        let active = [0.002, -0.001, 0.0, 0.003, -0.004];
+        let varying: Vec<f64> = benchmark
+            .iter()
+            .zip(active.iter())
+            .map(|(r, a)| r + a)
+            .collect();
+        // sample variance of active returns is 0.00003/4
+        let expected = (0.00003 / 4.0 * 252.0_f64).sqrt();
+        assert_fuzzy_eq!(
+            tracking_error(&varying, &benchmark, 252.0, 2).unwrap(),
+            expected,
+            tol
+        );
+        assert_eq!(tracking_error(&varying[1..], &benchmark, 252.0, 2), None);
+    }
+
+    #[test]
+    fn sortino_and_sharpe_ratio() {
+        let tol = 1e-10;
+        let returns = [
+            0.01, 0.012, 0.008, 0.011, -0.03, 0.009, 0.01, -0.02, 0.012, 0.01,
+        ];
+        let mean_return = 0.0032;
+        let downside_deviation = ((0.03_f64.powi(2) + 0.02_f64.powi(2)) / 10.0 * 252.0).sqrt();
+        let sortino = sortino_ratio(&returns, 0.0, 252.0, 2).unwrap();
+        assert_fuzzy_eq!(sortino, mean_return * 252.0 / downside_deviation, tol);
+
+        // upside fluctuations are small, so the volatility is dominated by the losses
+        // and is larger than the downside deviation
+        let sharpe = sharpe_ratio(&returns, 0.0, 252.0, 2).unwrap();
+        let volatility = (covariance(&returns, &returns) * 252.0).sqrt();
+        assert_fuzzy_eq!(sharpe, mean_return * 252.0 / volatility, tol);
+        assert!(volatility > downside_deviation);
+        assert!(sortino > sharpe);
+
+        // no downside, no Sortino ratio
+        assert_eq!(sortino_ratio(&[0.01, 0.02], 0.0, 252.0, 2), None);
+    }
+
+    #[test]
+    fn jacobi_eigenvalues_of_symmetric_matrix() {
+        let tol = 1e-10;
+        let a = vec![
+            vec![2.0, -1.0, 0.0],
+            vec![-1.0, 2.0, -1.0],
+            vec![0.0, -1.0, 2.0],
+        ];
+        let mut eigenvalues = jacobi_eigenvalues(a);
+        eigenvalues.sort_by(f64::total_cmp);
+        let sqrt2 = 2.0_f64.sqrt();
+        assert_fuzzy_eq!(eigenvalues[0], 2.0 - sqrt2, tol);
+        assert_fuzzy_eq!(eigenvalues[1], 2.0, tol);
+        assert_fuzzy_eq!(eigenvalues[2], 2.0 + sqrt2, tol);
+    }
+
+    #[tokio::test]
+    async fn returns_matrix_from_market() {
+        let tol = 1e-10;
+        let db_url = std::env::var("FINQL_TEST_DATABASE_URL");
+        assert!(
+            db_url.is_ok(),
+            "environment variable $FINQL_TEST_DATABASE_URL is not set"
+        );
+        let db = PostgresDB::new(&db_url.unwrap()).await.unwrap();
+        db.clean().await.unwrap();
+
+        let eur = Currency::new(None, CurrencyISOCode::new("EUR").unwrap(), None);
+        let eur_id = db.insert_asset(&Asset::Currency(eur)).await.unwrap();
+        let eur = Currency::new(Some(eur_id), eur.iso_code, None);
+        // second asset has no quote on 2nd and 3rd of March
+        let prices = [
+            vec![(1, 100.0), (2, 101.0), (3, 102.0), (4, 103.0)],
+            vec![(1, 50.0), (4, 55.0)],
+        ];
+        let mut asset_ids = Vec::new();
+        for (i, series) in prices.iter().enumerate() {
+            let stock = Stock::new(None, format!("Stock {}", i), None, None, None);
+            let asset_id = db.insert_asset(&Asset::Stock(stock)).await.unwrap();
+            let ticker_id = db
+                .insert_ticker(&Ticker {
+                    id: None,
+                    asset: asset_id,
+                    name: format!("STOCK{}", i),
+                    currency: eur,
+                    source: "manual".to_string(),
+                    priority: 1,
+                    factor: 1.0,
+                    tz: None,
+                    cal: None,
+                    active: true,
+                })
+                .await
+                .unwrap();
+            for (day, price) in series {
+                db.insert_quote(&Quote {
+                    id: None,
+                    ticker: ticker_id,
+                    price: *price,
+                    time: make_time(2021, 3, *day, 20, 0, 0).unwrap(),
+                    volume: None,
+                    stale: false,
+                })
+                .await
+                .unwrap();
+            }
+            asset_ids.push(asset_id);
+        }
+
+        // third asset is quoted in USD with a constant price, its returns in EUR
+        // are due to changes of the fx rate only
+        let usd = Currency::new(None, CurrencyISOCode::new("USD").unwrap(), None);
+        let usd_id = db.insert_asset(&Asset::Currency(usd)).await.unwrap();
+        let usd = Currency::new(Some(usd_id), usd.iso_code, None);
+        let stock = Stock::new(None, "US Stock".to_string(), None, None, None);
+        let us_stock_id = db.insert_asset(&Asset::Stock(stock)).await.unwrap();
+        for (asset_id, name, currency, series) in [
+            (usd_id, "USDEUR", eur, vec![(1, 0.8), (4, 0.9)]),
+            (
+                us_stock_id,
+                "USSTOCK",
+                usd,
+                vec![(1, 10.0), (2, 10.0), (3, 10.0), (4, 10.0)],
+            ),
+        ] {
+            let ticker_id = db
+                .insert_ticker(&Ticker {
+                    id: None,
+                    asset: asset_id,
+                    name: name.to_string(),
+                    currency,
+                    source: "manual".to_string(),
+                    priority: 1,
+                    factor: 1.0,
+                    tz: None,
+                    cal: None,
+                    active: true,
+                })
+                .await
+                .unwrap();
+            for (day, price) in series {
+                db.insert_quote(&Quote {
+                    id: None,
+                    ticker: ticker_id,
+                    price,
+                    time: make_time(2021, 3, day, 18, 0, 0).unwrap(),
+                    volume: None,
+                    stale: false,
+                })
+                .await
+                .unwrap();
+            }
+        }
+        asset_ids.push(us_stock_id);
+        let market = Market::new(Arc::new(db)).await;
+        let matrix = ReturnsMatrix::from_market(
+            &asset_ids,
+            eur,
+            NaiveDate::from_ymd(2021, 3, 1),
+            NaiveDate::from_ymd(2021, 3, 31),
+            2,
+            &market,
+        )
+        .await
+        .unwrap();
+        assert_eq!(matrix.dates.len(), 3);
+        assert_eq!(matrix.dates[0], NaiveDate::from_ymd(2021, 3, 2));
+        assert_fuzzy_eq!(matrix.returns[0][0], (101.0_f64 / 100.0).ln(), tol);
+        assert_fuzzy_eq!(matrix.returns[1][0], 0.0, tol);
+        assert_fuzzy_eq!(matrix.returns[1][1], 0.0, tol);
+        assert_fuzzy_eq!(matrix.returns[1][2], (55.0_f64 / 50.0).ln(), tol);
+        assert_fuzzy_eq!(matrix.returns[2][0], 0.0, tol);
+        assert_fuzzy_eq!(matrix.returns[2][2], (0.9_f64 / 0.8).ln(), tol);
+    }
+
+    #[test]
+    fn minimum_number_of_observations() {
+        let short = [0.01, -0.02, 0.015, 0.003, -0.007];
+        let long: Vec<f64> = (0..30)
+            .map(|i| 0.01 * ((i * 7 % 11) as f64 - 5.0))
+            .collect();
+        let leveraged = |returns: &[f64]| returns.iter().map(|r| 2.0 * r).collect::<Vec<f64>>();
+        let min = DEFAULT_MIN_OBSERVATIONS;
+
+        assert_eq!(beta(&leveraged(&short), &short, min), None);
+        assert_eq!(tracking_error(&leveraged(&short), &short, 252.0, min), None);
+        assert_eq!(sharpe_ratio(&short, 0.0, 252.0, min), None);
+        assert_eq!(sortino_ratio(&short, 0.0, 252.0, min), None);
+
+        assert!(beta(&leveraged(&long), &long, min).is_some());
+        assert!(tracking_error(&leveraged(&long), &long, 252.0, min).is_some());
+        assert!(sharpe_ratio(&long, 0.0, 252.0, min).is_some());
+        assert!(sortino_ratio(&long, 0.0, 252.0, min).is_some());
+    }
+}
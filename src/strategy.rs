@@ -107,6 +107,7 @@ impl Strategy for StaticInSingleStock {
                 },
                 cash_flow: dividend,
                 note: None,
+                time_stamp: None,
             };
             trace!(
                 "ReinvestInSingleStock: added transaction {:?}",
@@ -122,6 +123,7 @@ impl Strategy for StaticInSingleStock {
                     },
                     cash_flow: tax,
                     note: None,
+                    time_stamp: None,
                 };
                 trace!(
                     "ReinvestInSingleStock: added transaction {:?}",
@@ -191,6 +193,7 @@ impl Strategy for ReInvestInSingleStock {
                 },
                 cash_flow: dividend,
                 note: None,
+                time_stamp: None,
             };
             trace!(
                 "ReinvestInSingleStock: added transaction {:?}",
@@ -205,6 +208,7 @@ impl Strategy for ReInvestInSingleStock {
                     },
                     cash_flow: tax,
                     note: None,
+                    time_stamp: None,
                 };
                 trace!(
                     "ReinvestInSingleStock: added transaction {:?}",
@@ -236,6 +240,7 @@ impl Strategy for ReInvestInSingleStock {
                         date,
                     ),
                     note: None,
+                    time_stamp: None,
                 };
                 trace!(
                     "ReinvestInSingleStock: added transaction {:?}",
@@ -250,6 +255,7 @@ impl Strategy for ReInvestInSingleStock {
                         },
                         cash_flow: CashFlow::new(-fee, position.cash.currency, date),
                         note: None,
+                        time_stamp: None,
                     };
                     trace!(
                         "ReinvestInSingleStock: added transaction {:?}",
@@ -148,6 +148,11 @@ impl TimePeriod {
         }
     }
 
+    /// Returns true if the time period moves dates forward
+    pub fn is_positive(&self) -> bool {
+        self.num > 0
+    }
+
     /// Returns the frequency per year, if this is possible,
     /// otherwise return error
     pub fn frequency(&self) -> Result<u16, TimePeriodError> {
@@ -0,0 +1,328 @@
+//! Implied volatility surfaces
+//! A volatility surface is a set of implied volatilities per strike and time to expiry,
+//! typically derived from the option chain of an underlying. Surfaces are stored
+//! persistently as objects via an `ObjectHandler`.
+
+use std::f64;
+
+use argmin::core::{CostFunction, Error, Executor};
+use argmin::solver::brent::BrentRoot;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::datatypes::{DataError, ObjectHandler};
+use crate::market_quotes::OptionType;
+
+/// Object type used to store volatility surfaces
+pub const VOL_SURFACE: &str = "vol_surface";
+
+/// Single point of a volatility surface
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct VolPoint {
+    pub strike: f64,
+    pub expiry_years: f64,
+    pub implied_vol: f64,
+}
+
+impl VolPoint {
+    /// A point is valid if it has a positive strike and finite values only
+    pub fn is_valid(&self) -> bool {
+        self.strike > 0.
+            && self.strike.is_finite()
+            && self.expiry_years.is_finite()
+            && self.implied_vol.is_finite()
+    }
+}
+
+/// Implied volatilities of an asset's options at a given time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VolatilitySurface {
+    pub asset_id: i32,
+    pub generated_at: DateTime<Local>,
+    pub points: Vec<VolPoint>,
+}
+
+/// Interpolate implied volatility for given strike and time to expiry (in years)
+/// The surface is interpolated bilinearly in (log(K/F), T), i.e. linear in log-moneyness
+/// for each expiry and linear in time between neighbouring expiries. Since all points
+/// share the same reference level F, linear weights in log(K/F) equal those in log(K).
+/// Outside the range of the surface, volatilities are extrapolated flat.
+/// Points with non-positive strikes or non-finite values are ignored.
+/// Returns NaN if the surface has no valid points or the strike is not positive.
+pub fn interpolate_vol(surface: &VolatilitySurface, strike: f64, expiry: f64) -> f64 {
+    if strike.is_nan() || strike <= 0. {
+        return f64::NAN;
+    }
+    let valid_points: Vec<&VolPoint> = surface.points.iter().filter(|p| p.is_valid()).collect();
+    let mut expiries: Vec<f64> = valid_points.iter().map(|p| p.expiry_years).collect();
+    expiries.sort_by(f64::total_cmp);
+    expiries.dedup();
+    if expiries.is_empty() {
+        return f64::NAN;
+    }
+    let smile_vol = |t: f64| {
+        let mut smile: Vec<&VolPoint> = valid_points
+            .iter()
+            .copied()
+            .filter(|p| p.expiry_years == t)
+            .collect();
+        smile.sort_by(|a, b| a.strike.total_cmp(&b.strike));
+        let points: Vec<(f64, f64)> = smile
+            .iter()
+            .map(|p| (p.strike.ln(), p.implied_vol))
+            .collect();
+        linear_interpolation(&points, strike.ln())
+    };
+    match expiries.iter().position(|t| *t >= expiry) {
+        Some(0) => smile_vol(expiries[0]),
+        None => smile_vol(expiries[expiries.len() - 1]),
+        Some(idx) => {
+            let (t1, t2) = (expiries[idx - 1], expiries[idx]);
+            linear_interpolation(&[(t1, smile_vol(t1)), (t2, smile_vol(t2))], expiry)
+        }
+    }
+}
+
+/// Linear interpolation of sorted points (x, y) with flat extrapolation
+fn linear_interpolation(points: &[(f64, f64)], x: f64) -> f64 {
+    match points.iter().position(|p| p.0 >= x) {
+        Some(0) => points[0].1,
+        None => points[points.len() - 1].1,
+        Some(idx) => {
+            let (x1, y1) = points[idx - 1];
+            let (x2, y2) = points[idx];
+            y1 + (y2 - y1) * (x - x1) / (x2 - x1)
+        }
+    }
+}
+
+/// Cumulative distribution function of the standard normal distribution
+fn norm_cdf(x: f64) -> f64 {
+    0.5 * erfc(-x / f64::consts::SQRT_2)
+}
+
+/// Complementary error function, see Numerical Recipes, 3rd edition, section 6.2.2
+/// (fractional error less than 1.2e-7)
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1. / (1. + 0.5 * z);
+    let ans = t
+        * (-z * z - 1.26551223
+            + t * (1.00002368
+                + t * (0.37409196
+                    + t * (0.09678418
+                        + t * (-0.18628806
+                            + t * (0.27886807
+                                + t * (-1.13520398
+                                    + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277)))))))))
+            .exp();
+    if x >= 0. {
+        ans
+    } else {
+        2. - ans
+    }
+}
+
+/// Black-Scholes price of a European option on a non dividend paying underlying
+/// with continuously compounded risk free rate `rate`
+pub fn black_scholes_price(
+    option_type: OptionType,
+    spot: f64,
+    strike: f64,
+    expiry_years: f64,
+    rate: f64,
+    vol: f64,
+) -> f64 {
+    let discount = (-rate * expiry_years).exp();
+    let std_dev = vol * expiry_years.sqrt();
+    let d1 = ((spot / strike).ln() + rate * expiry_years) / std_dev + 0.5 * std_dev;
+    let d2 = d1 - std_dev;
+    match option_type {
+        OptionType::Call => spot * norm_cdf(d1) - strike * discount * norm_cdf(d2),
+        OptionType::Put => strike * discount * norm_cdf(-d2) - spot * norm_cdf(-d1),
+    }
+}
+
+/// Calculate the Black-Scholes implied volatility for a given option price
+/// Returns None if the price is not within the arbitrage-free bounds or the solver fails,
+/// or if any of the inputs is not finite or spot, strike or time to expiry are not positive.
+pub fn implied_vol(
+    option_type: OptionType,
+    price: f64,
+    spot: f64,
+    strike: f64,
+    expiry_years: f64,
+    rate: f64,
+) -> Option<f64> {
+    if [price, spot, strike, expiry_years, rate]
+        .iter()
+        .any(|x| !x.is_finite())
+        || spot <= 0.
+        || strike <= 0.
+        || expiry_years <= 0.
+    {
+        return None;
+    }
+    let min_vol = 1e-4;
+    let max_vol = 5.0;
+    let func = BlackScholesPricer {
+        option_type,
+        price,
+        spot,
+        strike,
+        expiry_years,
+        rate,
+    };
+    // Check that a solution exists within the search interval
+    let low = func.cost(&min_vol).ok()?;
+    let high = func.cost(&max_vol).ok()?;
+    if low * high > 0. {
+        return None;
+    }
+    let solver = BrentRoot::new(min_vol, max_vol, 1e-11);
+    let res = Executor::new(func, solver)
+        .configure(|state| state.max_iters(100).param(0.2))
+        .run();
+    match res {
+        Ok(mut val) => val.state.take_param(),
+        Err(_) => None,
+    }
+}
+
+/// Difference between Black-Scholes price for given volatility and the market price
+#[derive(Clone)]
+struct BlackScholesPricer {
+    option_type: OptionType,
+    price: f64,
+    spot: f64,
+    strike: f64,
+    expiry_years: f64,
+    rate: f64,
+}
+
+impl CostFunction for BlackScholesPricer {
+    // one dimensional problem, no vector needed
+    type Param = f64;
+    type Output = f64;
+
+    fn cost(&self, p: &Self::Param) -> Result<Self::Output, Error> {
+        Ok(black_scholes_price(
+            self.option_type,
+            self.spot,
+            self.strike,
+            self.expiry_years,
+            self.rate,
+            *p,
+        ) - self.price)
+    }
+}
+
+/// Id of the object the volatility surface of an asset is stored under
+fn vol_surface_id(asset_id: i32) -> String {
+    format!("{}_{}", VOL_SURFACE, asset_id)
+}
+
+/// Store volatility surface in database, replacing any previous surface of the same asset
+pub async fn save_vol_surface<DB: ObjectHandler + Sync>(
+    db: &DB,
+    surface: &VolatilitySurface,
+) -> Result<(), DataError> {
+    let id = vol_surface_id(surface.asset_id);
+    let ids = db.get_object_ids(VOL_SURFACE).await?;
+    if ids.contains(&id) {
+        db.update_object(&id, surface).await
+    } else {
+        db.store_object(&id, VOL_SURFACE, surface).await
+    }
+}
+
+/// Load volatility surface of an asset from database
+pub async fn load_vol_surface<DB: ObjectHandler + Sync>(
+    db: &DB,
+    asset_id: i32,
+) -> Result<VolatilitySurface, DataError> {
+    db.get_object(&vol_surface_id(asset_id)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock_surface() -> VolatilitySurface {
+        let mut points = Vec::new();
+        for &(expiry_years, base_vol) in [(0.5, 0.3), (1.0, 0.25)].iter() {
+            for &(strike, skew) in [(80., 0.05), (100., 0.0), (120., 0.02)].iter() {
+                points.push(VolPoint {
+                    strike,
+                    expiry_years,
+                    implied_vol: base_vol + skew,
+                });
+            }
+        }
+        VolatilitySurface {
+            asset_id: 1,
+            generated_at: Local::now(),
+            points,
+        }
+    }
+
+    #[test]
+    fn interpolate_vol_surface() {
+        let tol = 1e-11;
+        let surface = mock_surface();
+        // grid points are matched exactly
+        assert_fuzzy_eq!(interpolate_vol(&surface, 100., 0.5), 0.3, tol);
+        assert_fuzzy_eq!(interpolate_vol(&surface, 120., 1.0), 0.27, tol);
+
+        // values between grid points lie between the surrounding grid values
+        let vol = interpolate_vol(&surface, 90., 0.75);
+        assert!(vol > 0.25 && vol < 0.35);
+        let vol = interpolate_vol(&surface, 110., 0.5);
+        assert!(vol > 0.3 && vol < 0.32);
+        let vol = interpolate_vol(&surface, 100., 0.75);
+        assert_fuzzy_eq!(vol, 0.275, tol);
+
+        // flat extrapolation
+        assert_fuzzy_eq!(interpolate_vol(&surface, 50., 0.1), 0.35, tol);
+        assert_fuzzy_eq!(interpolate_vol(&surface, 150., 2.0), 0.27, tol);
+
+        // invalid points are ignored, invalid strikes give NaN
+        let mut surface_with_invalid_points = surface.clone();
+        surface_with_invalid_points.points.push(VolPoint {
+            strike: 0.,
+            expiry_years: 0.75,
+            implied_vol: 0.4,
+        });
+        surface_with_invalid_points.points.push(VolPoint {
+            strike: 100.,
+            expiry_years: f64::NAN,
+            implied_vol: 0.4,
+        });
+        assert_fuzzy_eq!(
+            interpolate_vol(&surface_with_invalid_points, 100., 0.75),
+            0.275,
+            tol
+        );
+        assert!(interpolate_vol(&surface, 0., 0.75).is_nan());
+        assert!(interpolate_vol(&surface, -10., 0.75).is_nan());
+    }
+
+    #[test]
+    fn black_scholes_implied_vol() {
+        let tol = 1e-6;
+        let call = black_scholes_price(OptionType::Call, 100., 100., 1., 0.05, 0.2);
+        // reference value from standard Black-Scholes tables
+        assert_fuzzy_eq!(call, 10.450584, tol);
+        let put = black_scholes_price(OptionType::Put, 100., 100., 1., 0.05, 0.2);
+        // put-call parity
+        assert_fuzzy_eq!(call - put, 100. - 100. * (-0.05_f64).exp(), tol);
+
+        let vol = implied_vol(OptionType::Put, put, 100., 100., 1., 0.05).unwrap();
+        assert_fuzzy_eq!(vol, 0.2, tol);
+        // price below intrinsic value
+        assert!(implied_vol(OptionType::Call, 1., 120., 100., 1., 0.0).is_none());
+        // invalid strike or price
+        assert!(implied_vol(OptionType::Put, put, 100., 0., 1., 0.05).is_none());
+        assert!(implied_vol(OptionType::Put, f64::NAN, 100., 100., 1., 0.05).is_none());
+    }
+}